@@ -0,0 +1,167 @@
+// src/image/cast.rs
+use num_traits::NumCast;
+
+use super::dyn_image::{DynImage, Pixel};
+use super::image::Image;
+
+/// What `Image::cast` does with values the target type can not hold.
+/// Fractions are truncated toward zero for integer targets, as `as` does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CastPolicy {
+    /// Clamp to the target's range, NaN becomes 0 for integer targets.
+    Saturating,
+    /// Keep the low bits like integer `as` casts, e.g. 300 becomes 44 as `u8`.
+    Wrapping,
+    /// Fail on the first value out of range (or NaN for integer targets).
+    Checked,
+}
+
+/// A value `CastPolicy::Checked` could not convert.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CastError {
+    /// Voxel index `[x, y, z]` of the value.
+    pub index: [usize; 3],
+    /// The value, as f64.
+    pub value: f64,
+    /// Name of the target type, e.g. "u8".
+    pub target: &'static str,
+}
+
+impl std::fmt::Display for CastError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Value {} at {:?} does not fit {}", self.value, self.index, self.target)
+    }
+}
+
+impl std::error::Error for CastError {}
+
+
+// Integers are carried as i128 so 64 bit values stay exact.
+#[derive(Debug, Clone, Copy)]
+enum Value {
+    Int(i128),
+    Float(f64),
+}
+
+impl Value {
+    fn of<T: Pixel>(v: T) -> Self {
+        if T::IS_FLOAT {
+            Value::Float(v.to_f64().unwrap_or(f64::NAN))
+        } else {
+            Value::Int(v.to_i128().unwrap_or_default())
+        }
+    }
+
+    fn to<U: Pixel>(self, policy: CastPolicy) -> Option<U> {
+        if U::IS_FLOAT {
+            let v = match self {
+                Value::Int(i) => i as f64,
+                Value::Float(f) => f,
+            };
+            let max = U::max_value().to_f64().unwrap_or(f64::INFINITY);
+            return match policy {
+                _ if !v.is_finite() || v.abs() <= max => NumCast::from(v),
+                CastPolicy::Saturating => NumCast::from(v.clamp(-max, max)),
+                // Out of range for f32 (from f64), `as` gives infinity.
+                CastPolicy::Wrapping => NumCast::from(v.signum() * f64::INFINITY),
+                CastPolicy::Checked => None,
+            };
+        }
+
+        let (min, max) = (U::min_value().to_i128().unwrap_or_default(), U::max_value().to_i128().unwrap_or_default());
+        let i = match self {
+            Value::Int(i) => i,
+            Value::Float(f) if f.is_nan() => return if policy == CastPolicy::Checked { None } else { NumCast::from(0) },
+            // `as` saturates at the i128 range, far beyond any 64 bit target.
+            Value::Float(f) => f as i128,
+        };
+        let i = match policy {
+            _ if (min..=max).contains(&i) => i,
+            CastPolicy::Saturating => i.clamp(min, max),
+            CastPolicy::Wrapping => {
+                let bits = 8 * size_of::<U>() as u32;
+                let wrapped = i.rem_euclid(1 << bits);
+                if wrapped > max { wrapped - (1 << bits) } else { wrapped }
+            }
+            CastPolicy::Checked => return None,
+        };
+        NumCast::from(i)
+    }
+}
+
+
+impl<T: Pixel> Image<T> {
+    /// Convert the voxels to `U`, keeping the geometry and metadata. Only
+    /// `CastPolicy::Checked` can fail.
+    pub fn cast<U: Pixel>(&self, policy: CastPolicy) -> Result<Image<U>, CastError> {
+        let voxels = self.voxels.iter().enumerate()
+            .map(|(i, &v)| Value::of(v).to(policy).ok_or_else(|| CastError {
+                index: self.voxel_index(i),
+                value: v.to_f64().unwrap_or(f64::NAN),
+                target: U::NAME,
+            }))
+            .collect::<Result<Vec<U>, _>>()?;
+        Ok(self.with_voxels(voxels))
+    }
+
+    /// Map the intensity range of the image linearly onto `[out_min, out_max]`,
+    /// e.g. `rescale_intensity::<u8>(0.0, 255.0)` for display. Integer
+    /// results are rounded; a constant image maps to `out_min`. NaN voxels
+    /// are ignored for the range and saturate like `CastPolicy::Saturating`.
+    pub fn rescale_intensity<U: Pixel>(&self, out_min: f64, out_max: f64) -> Image<U> {
+        let values = || self.voxels.iter().filter_map(|v| v.to_f64()).filter(|v| !v.is_nan());
+        let min = values().fold(f64::INFINITY, f64::min);
+        let max = values().fold(f64::NEG_INFINITY, f64::max);
+        let scale = if max > min { (out_max - out_min) / (max - min) } else { 0.0 };
+        let voxels = self.voxels.iter()
+            .map(|v| {
+                let v = out_min + (v.to_f64().unwrap_or(f64::NAN) - min) * scale;
+                let v = if U::IS_FLOAT { v } else { v.round() };
+                Value::Float(v).to(CastPolicy::Saturating).unwrap_or_else(U::min_value)
+            })
+            .collect();
+        self.with_voxels(voxels)
+    }
+
+    fn with_voxels<U>(&self, voxels: Vec<U>) -> Image<U> {
+        Image {
+            voxels,
+            width: self.width,
+            height: self.height,
+            depth: self.depth,
+            spacing: self.spacing,
+            origin: self.origin,
+            direction: self.direction,
+            metadata: self.metadata.clone(),
+        }
+    }
+}
+
+
+impl DynImage {
+    /// Convert to `Image<U>` whatever the pixel type, see `Image::cast`.
+    pub fn cast<U: Pixel>(&self, policy: CastPolicy) -> Result<Image<U>, CastError> {
+        crate::dispatch_image!(self, image => image.cast(policy))
+    }
+
+    /// As `f32`, e.g. CT `i16` data for processing. Integers beyond 2^24
+    /// lose precision, `f64` values beyond the `f32` range saturate.
+    pub fn to_f32(&self) -> Image<f32> {
+        crate::dispatch_image!(self, image => image.cast(CastPolicy::Saturating).unwrap())
+    }
+
+    /// As `f64`, exact for all types but 64 bit integers beyond 2^53.
+    pub fn to_f64(&self) -> Image<f64> {
+        crate::dispatch_image!(self, image => image.cast(CastPolicy::Saturating).unwrap())
+    }
+
+    /// Rescaled to the full `u8` range, for display.
+    pub fn to_u8_display(&self) -> Image<u8> {
+        crate::dispatch_image!(self, image => image.rescale_intensity(0.0, 255.0))
+    }
+
+    /// See `Image::rescale_intensity`.
+    pub fn rescale_intensity<U: Pixel>(&self, out_min: f64, out_max: f64) -> Image<U> {
+        crate::dispatch_image!(self, image => image.rescale_intensity(out_min, out_max))
+    }
+}
@@ -0,0 +1,278 @@
+// src/image/dyn_image.rs
+use bytemuck::Pod;
+use num_traits::{Bounded, NumCast};
+
+use super::image::{AnyImage, Image};
+use super::metadata::Metadata;
+use super::nd_image::NdImage;
+use super::vector_image::VectorImage;
+
+/// A scalar 3D image of any supported pixel type, for code that learns
+/// the type at runtime (e.g. from a loaded file). Use `dispatch_image!` to
+/// run generic code on the typed image inside.
+///
+/// Only `Image<T>` fits: `NdImage<T>` and `VectorImage<T>` have no variant.
+/// The savers take all three, so they find `T` with `first_pixel_type!`,
+/// which tries the same pixel types in the same order, and read the image
+/// through `ImageParts`.
+#[derive(Debug, Clone)]
+pub enum DynImage {
+    U8(Image<u8>),
+    I8(Image<i8>),
+    U16(Image<u16>),
+    I16(Image<i16>),
+    U32(Image<u32>),
+    I32(Image<i32>),
+    F32(Image<f32>),
+    U64(Image<u64>),
+    I64(Image<i64>),
+    F64(Image<f64>),
+}
+
+/// Run `$body` with `$image` bound to the `Image<T>` inside a `DynImage`,
+/// whatever `T` is. Works on owned, borrowed and mutably borrowed images:
+///
+/// `let sum = dispatch_image!(&image, img => img.voxels.iter().map(|&v| v as f64).sum::<f64>());`
+#[macro_export]
+macro_rules! dispatch_image {
+    ($dyn_image:expr, $image:ident => $body:expr) => {
+        match $dyn_image {
+            $crate::image::DynImage::U8($image) => $body,
+            $crate::image::DynImage::I8($image) => $body,
+            $crate::image::DynImage::U16($image) => $body,
+            $crate::image::DynImage::I16($image) => $body,
+            $crate::image::DynImage::U32($image) => $body,
+            $crate::image::DynImage::I32($image) => $body,
+            $crate::image::DynImage::F32($image) => $body,
+            $crate::image::DynImage::U64($image) => $body,
+            $crate::image::DynImage::I64($image) => $body,
+            $crate::image::DynImage::F64($image) => $body,
+        }
+    };
+}
+
+// `$try_fn::<T>(args)` for each pixel type a `DynImage` can hold, in variant
+// order, until one returns `Some`. `$try_fn` downcasts to the image types it
+// handles and returns `None` for anything else.
+macro_rules! first_pixel_type {
+    ($try_fn:ident($($arg:expr),* $(,)?)) => {
+        $try_fn::<u8>($($arg),*)
+            .or_else(|| $try_fn::<i8>($($arg),*))
+            .or_else(|| $try_fn::<u16>($($arg),*))
+            .or_else(|| $try_fn::<i16>($($arg),*))
+            .or_else(|| $try_fn::<u32>($($arg),*))
+            .or_else(|| $try_fn::<i32>($($arg),*))
+            .or_else(|| $try_fn::<f32>($($arg),*))
+            .or_else(|| $try_fn::<u64>($($arg),*))
+            .or_else(|| $try_fn::<i64>($($arg),*))
+            .or_else(|| $try_fn::<f64>($($arg),*))
+    };
+}
+pub(crate) use first_pixel_type;
+
+// What the savers write of an `Image<T>`, `NdImage<T>` or `VectorImage<T>`,
+// borrowed. `size` has an entry per axis (3 unless it is an `NdImage`),
+// `direction` is `size.len()` squared.
+pub(crate) struct ImageParts<'a, T> {
+    pub voxels: &'a [T],
+    pub size: Vec<u32>,
+    pub channels: u32,
+    pub spacing: &'a [f64],
+    pub origin: &'a [f64],
+    pub direction: &'a [f64],
+    pub metadata: &'a Metadata,
+}
+
+impl<'a, T: 'static> ImageParts<'a, T> {
+    // `None` if `img` is none of the three with voxels of type `T`.
+    pub(crate) fn of(img: &'a dyn AnyImage) -> Option<Self> {
+        let img = img.as_any();
+        if let Some(i) = img.downcast_ref::<Image<T>>() {
+            Some(ImageParts {
+                voxels: &i.voxels,
+                size: vec![i.width, i.height, i.depth],
+                channels: 1,
+                spacing: &i.spacing,
+                origin: &i.origin,
+                direction: &i.direction,
+                metadata: &i.metadata,
+            })
+        } else if let Some(i) = img.downcast_ref::<NdImage<T>>() {
+            Some(ImageParts {
+                voxels: &i.voxels,
+                size: i.size.clone(),
+                channels: 1,
+                spacing: &i.spacing,
+                origin: &i.origin,
+                direction: &i.direction,
+                metadata: &i.metadata,
+            })
+        } else {
+            img.downcast_ref::<VectorImage<T>>().map(|i| ImageParts {
+                voxels: &i.voxels,
+                size: vec![i.width, i.height, i.depth],
+                channels: i.channels,
+                spacing: &i.spacing,
+                origin: &i.origin,
+                direction: &i.direction,
+                metadata: &i.metadata,
+            })
+        }
+    }
+}
+
+/// Pixel types a `DynImage` can hold.
+pub trait Pixel: Pod + NumCast + Bounded + Copy + 'static {
+    /// Name of the type, e.g. "u8".
+    const NAME: &'static str;
+    /// Whether this is `f32` or `f64`.
+    const IS_FLOAT: bool;
+
+    fn into_dyn(image: Image<Self>) -> DynImage;
+    #[allow(clippy::result_large_err)] // Hands the image back, as `try_into_typed` does.
+    fn from_dyn(image: DynImage) -> Result<Image<Self>, DynImage>;
+    fn from_dyn_ref(image: &DynImage) -> Option<&Image<Self>>;
+    fn from_dyn_mut(image: &mut DynImage) -> Option<&mut Image<Self>>;
+}
+
+macro_rules! impl_pixel {
+    ($($t:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl Pixel for $t {
+                const NAME: &'static str = stringify!($t);
+                const IS_FLOAT: bool = Self::NAME.as_bytes()[0] == b'f';
+
+                fn into_dyn(image: Image<Self>) -> DynImage {
+                    DynImage::$variant(image)
+                }
+                fn from_dyn(image: DynImage) -> Result<Image<Self>, DynImage> {
+                    match image {
+                        DynImage::$variant(image) => Ok(image),
+                        other => Err(other),
+                    }
+                }
+                fn from_dyn_ref(image: &DynImage) -> Option<&Image<Self>> {
+                    match image {
+                        DynImage::$variant(image) => Some(image),
+                        _ => None,
+                    }
+                }
+                fn from_dyn_mut(image: &mut DynImage) -> Option<&mut Image<Self>> {
+                    match image {
+                        DynImage::$variant(image) => Some(image),
+                        _ => None,
+                    }
+                }
+            }
+
+            impl From<Image<$t>> for DynImage {
+                fn from(image: Image<$t>) -> Self {
+                    DynImage::$variant(image)
+                }
+            }
+        )*
+    };
+}
+
+impl_pixel!(
+    u8 => U8, i8 => I8,
+    u16 => U16, i16 => I16,
+    u32 => U32, i32 => I32, f32 => F32,
+    u64 => U64, i64 => I64, f64 => F64,
+);
+
+
+// `Image<T>` inside `image` if it holds one, moved out of the box.
+fn take_typed<T: Pixel>(image: Box<dyn AnyImage>) -> Result<DynImage, Box<dyn AnyImage>> {
+    if !image.as_any().is::<Image<T>>() {
+        return Err(image);
+    }
+    let image: Box<dyn std::any::Any> = image;
+    Ok(T::into_dyn(*image.downcast::<Image<T>>().unwrap()))
+}
+
+impl DynImage {
+    /// Name of the pixel type, e.g. "u8".
+    pub fn pixel_type(&self) -> &'static str {
+        fn name<T: Pixel>(_: &Image<T>) -> &'static str {
+            T::NAME
+        }
+        dispatch_image!(self, image => name(image))
+    }
+
+    /// The typed image, or `self` back if it holds another pixel type.
+    #[allow(clippy::result_large_err)]
+    pub fn try_into_typed<T: Pixel>(self) -> Result<Image<T>, DynImage> {
+        T::from_dyn(self)
+    }
+
+    pub fn as_typed<T: Pixel>(&self) -> Option<&Image<T>> {
+        T::from_dyn_ref(self)
+    }
+
+    pub fn as_typed_mut<T: Pixel>(&mut self) -> Option<&mut Image<T>> {
+        T::from_dyn_mut(self)
+    }
+
+    /// The image behind the type-erased interface, e.g. to pass it to `save`.
+    pub fn as_any_image(&self) -> &dyn AnyImage {
+        dispatch_image!(self, image => image)
+    }
+
+    pub fn into_any_image(self) -> Box<dyn AnyImage> {
+        dispatch_image!(self, image => Box::new(image))
+    }
+
+    pub fn width(&self) -> u32 {
+        dispatch_image!(self, image => image.width)
+    }
+
+    pub fn height(&self) -> u32 {
+        dispatch_image!(self, image => image.height)
+    }
+
+    pub fn depth(&self) -> u32 {
+        dispatch_image!(self, image => image.depth)
+    }
+
+    pub fn spacing(&self) -> [f64; 3] {
+        dispatch_image!(self, image => image.spacing)
+    }
+
+    pub fn origin(&self) -> [f64; 3] {
+        dispatch_image!(self, image => image.origin)
+    }
+
+    pub fn direction(&self) -> [f64; 9] {
+        dispatch_image!(self, image => image.direction)
+    }
+
+    pub fn metadata(&self) -> &Metadata {
+        dispatch_image!(self, image => &image.metadata)
+    }
+}
+
+/// Takes scalar 3D images (`Image<T>`) out of the box; `NdImage`,
+/// `VectorImage` and other images come back as the error.
+impl TryFrom<Box<dyn AnyImage>> for DynImage {
+    type Error = Box<dyn AnyImage>;
+
+    fn try_from(image: Box<dyn AnyImage>) -> Result<Self, Self::Error> {
+        take_typed::<u8>(image)
+            .or_else(take_typed::<i8>)
+            .or_else(take_typed::<u16>)
+            .or_else(take_typed::<i16>)
+            .or_else(take_typed::<u32>)
+            .or_else(take_typed::<i32>)
+            .or_else(take_typed::<f32>)
+            .or_else(take_typed::<u64>)
+            .or_else(take_typed::<i64>)
+            .or_else(take_typed::<f64>)
+    }
+}
+
+impl From<DynImage> for Box<dyn AnyImage> {
+    fn from(image: DynImage) -> Self {
+        image.into_any_image()
+    }
+}
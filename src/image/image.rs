@@ -1,8 +1,11 @@
 // src/image/image.rs
 use std::any::Any;
+use std::ops::{Index, IndexMut};
 use bytemuck::Pod;
 use num_traits::NumCast;
 
+use super::metadata::Metadata;
+
 #[derive(Debug, Clone)]
 pub struct Image<T> {
     pub voxels: Vec<T>,
@@ -12,12 +15,91 @@ pub struct Image<T> {
     pub spacing: [f64; 3],
     pub origin:  [f64; 3],
     pub direction: [f64; 9],
+    pub metadata: Metadata,
 }
 
 impl<T> Image<T> {
     pub fn num_voxels(&self) -> usize {
         (self.width as usize) * (self.height as usize) * (self.depth as usize)
     }
+
+    /// Whether `[x, y, z]` lies inside the image.
+    pub fn contains(&self, [x, y, z]: [usize; 3]) -> bool {
+        x < self.width as usize && y < self.height as usize && z < self.depth as usize
+    }
+
+    /// Position of voxel `[x, y, z]` in `voxels`, x varies fastest. Not bounds checked.
+    pub fn linear_index(&self, [x, y, z]: [usize; 3]) -> usize {
+        let (width, height) = (self.width as usize, self.height as usize);
+        x + y * width + z * width * height
+    }
+
+    /// Voxel index `[x, y, z]` of position `index` in `voxels`.
+    pub fn voxel_index(&self, index: usize) -> [usize; 3] {
+        let (width, height) = (self.width as usize, self.height as usize);
+        [index % width, index / width % height, index / (width * height)]
+    }
+
+    /// Voxel at `(x, y, z)`, `None` if any coordinate is out of bounds.
+    pub fn checked_get(&self, x: usize, y: usize, z: usize) -> Option<&T> {
+        if !self.contains([x, y, z]) {
+            return None;
+        }
+        self.voxels.get(self.linear_index([x, y, z]))
+    }
+
+    pub fn checked_get_mut(&mut self, x: usize, y: usize, z: usize) -> Option<&mut T> {
+        if !self.contains([x, y, z]) {
+            return None;
+        }
+        let index = self.linear_index([x, y, z]);
+        self.voxels.get_mut(index)
+    }
+
+    /// Voxel at `(x, y, z)`.
+    ///
+    /// Panics if any coordinate is out of bounds, also when the flat index
+    /// would still fall inside `voxels` (e.g. `x == width` on row 0).
+    pub fn get(&self, x: usize, y: usize, z: usize) -> &T {
+        let size = [self.width, self.height, self.depth];
+        self.checked_get(x, y, z)
+            .unwrap_or_else(|| panic!("voxel index {:?} out of bounds for size {:?}", [x, y, z], size))
+    }
+
+    pub fn get_mut(&mut self, x: usize, y: usize, z: usize) -> &mut T {
+        let size = [self.width, self.height, self.depth];
+        self.checked_get_mut(x, y, z)
+            .unwrap_or_else(|| panic!("voxel index {:?} out of bounds for size {:?}", [x, y, z], size))
+    }
+
+    /// Iterate through `([x, y, z], &voxel)` in memory order.
+    pub fn indexed_iter(&self) -> impl Iterator<Item = ([usize; 3], &T)> + '_ {
+        let (width, height) = (self.width as usize, self.height as usize);
+        self.voxels.iter().enumerate()
+            .map(move |(i, v)| ([i % width, i / width % height, i / (width * height)], v))
+    }
+
+    /// Iterate through `([x, y, z], &mut voxel)` in memory order.
+    pub fn indexed_iter_mut(&mut self) -> impl Iterator<Item = ([usize; 3], &mut T)> + '_ {
+        let (width, height) = (self.width as usize, self.height as usize);
+        self.voxels.iter_mut().enumerate()
+            .map(move |(i, v)| ([i % width, i / width % height, i / (width * height)], v))
+    }
+}
+
+impl<T> Index<[usize; 3]> for Image<T> {
+    type Output = T;
+
+    /// Voxel at `[x, y, z]`, panics if out of bounds (see `Image::get`).
+    fn index(&self, [x, y, z]: [usize; 3]) -> &T {
+        self.get(x, y, z)
+    }
+}
+
+impl<T> IndexMut<[usize; 3]> for Image<T> {
+    fn index_mut(&mut self, [x, y, z]: [usize; 3]) -> &mut T {
+        self.get_mut(x, y, z)
+    }
 }
 
 /// We don't know T at compiletime so this is a placeholder.
@@ -29,6 +111,21 @@ pub trait AnyImage: Any {
     fn spacing(&self) -> [f64; 3];
     fn origin(&self)  -> [f64; 3];
     fn direction(&self)  -> [f64; 9];
+    /// Header fields without a dedicated place, kept for round-trips.
+    fn metadata(&self) -> &Metadata;
+
+    /// Number of dimensions, 3 unless this is an `NdImage`.
+    fn ndims(&self) -> usize {
+        3
+    }
+    /// Size along every dimension.
+    fn size(&self) -> Vec<u32> {
+        vec![self.width(), self.height(), self.depth()]
+    }
+    /// Number of components per voxel, 1 unless this is a `VectorImage`.
+    fn channels(&self) -> u32 {
+        1
+    }
 
     /// Iterate (lazily) through all values as f64.
     fn iter_f64(&self) -> Box<dyn Iterator<Item = f64> + '_>;
@@ -61,6 +158,9 @@ where
     fn direction(&self) -> [f64; 9] {
         self.direction
     }
+    fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
 
     fn iter_f64(&self) -> Box<dyn Iterator<Item = f64> + '_> {
         Box::new(self.voxels.iter().map(|&x| NumCast::from(x).unwrap()))
@@ -0,0 +1,121 @@
+// src/image/mapped_image.rs
+use std::any::Any;
+use std::marker::PhantomData;
+use bytemuck::Pod;
+use memmap2::Mmap;
+use num_traits::NumCast;
+
+use super::image::{AnyImage, Image};
+use super::metadata::Metadata;
+
+/// Read-only 3D image whose voxels live in a memory-mapped file.
+///
+/// Nothing is copied on open, pages are read from disk on first access.
+/// The file must not be modified while it is mapped.
+#[derive(Debug)]
+pub struct MappedImage<T> {
+    pub(crate) mmap: Mmap,
+    pub(crate) data_offset: usize,
+    pub(crate) byte_order_msb: bool,
+    pub width:  u32,
+    pub height: u32,
+    pub depth:  u32,
+    pub spacing: [f64; 3],
+    pub origin:  [f64; 3],
+    pub direction: [f64; 9],
+    pub metadata: Metadata,
+    pub(crate) _voxel: PhantomData<T>,
+}
+
+impl<T: Pod> MappedImage<T> {
+    pub fn num_voxels(&self) -> usize {
+        (self.width as usize) * (self.height as usize) * (self.depth as usize)
+    }
+
+    /// The raw voxel bytes, in the file's byte order.
+    pub fn as_bytes(&self) -> &[u8] {
+        let len = self.num_voxels() * std::mem::size_of::<T>();
+        &self.mmap[self.data_offset..self.data_offset + len]
+    }
+
+    /// Zero-copy view of the voxels. `None` if the data is not aligned for `T`
+    /// in the file (e.g. an .mha header of odd length) or is stored in
+    /// non-native byte order, use `get`/`iter` in that case.
+    pub fn voxels(&self) -> Option<&[T]> {
+        if std::mem::size_of::<T>() > 1 && self.byte_order_msb != cfg!(target_endian = "big") {
+            return None;
+        }
+        bytemuck::try_cast_slice(self.as_bytes()).ok()
+    }
+
+    /// Voxel at flat index `index`, works regardless of alignment and byte order.
+    pub fn get(&self, index: usize) -> T {
+        let size = std::mem::size_of::<T>();
+        let bytes = &self.as_bytes()[index * size..(index + 1) * size];
+        let mut value: T = bytemuck::pod_read_unaligned(bytes);
+        if size > 1 && self.byte_order_msb != cfg!(target_endian = "big") {
+            bytemuck::bytes_of_mut(&mut value).reverse();
+        }
+        value
+    }
+
+    /// Iterate through all voxels by value in memory order.
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        (0..self.num_voxels()).map(move |index| self.get(index))
+    }
+
+    /// Copy the voxels into an owned `Image<T>`.
+    pub fn to_image(&self) -> Image<T> {
+        Image {
+            voxels: match self.voxels() {
+                Some(voxels) => voxels.to_vec(),
+                None => self.iter().collect(),
+            },
+            width: self.width,
+            height: self.height,
+            depth: self.depth,
+            spacing: self.spacing,
+            origin: self.origin,
+            direction: self.direction,
+            metadata: self.metadata.clone(),
+        }
+    }
+}
+
+impl<T> AnyImage for MappedImage<T>
+where
+    T: Pod + NumCast + Copy + 'static,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn width(&self) -> u32 {
+        self.width
+    }
+    fn height(&self) -> u32 {
+        self.height
+    }
+    fn depth(&self) -> u32 {
+        self.depth
+    }
+    fn spacing(&self) -> [f64; 3] {
+        self.spacing
+    }
+    fn origin(&self) -> [f64; 3] {
+        self.origin
+    }
+    fn direction(&self) -> [f64; 9] {
+        self.direction
+    }
+    fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    fn iter_f64(&self) -> Box<dyn Iterator<Item = f64> + '_> {
+        match self.voxels() {
+            Some(voxels) => Box::new(voxels.iter().map(|&x| NumCast::from(x).unwrap())),
+            None => Box::new(self.iter().map(|x| NumCast::from(x).unwrap())),
+        }
+    }
+}
@@ -1,3 +1,22 @@
+#[allow(clippy::module_inception)]
 pub mod image;
+pub mod cast;
+pub mod dyn_image;
+pub mod mapped_image;
+pub mod metadata;
+pub mod neighborhood;
+pub mod nd_image;
+pub mod transform;
+pub mod vector_image;
+pub mod view;
 
 pub use image::{Image, AnyImage};
+pub use cast::{CastError, CastPolicy};
+pub use dyn_image::{DynImage, Pixel};
+pub use mapped_image::MappedImage;
+pub use metadata::Metadata;
+pub use neighborhood::{Boundary, Neighborhood, Neighborhoods};
+pub use nd_image::NdImage;
+pub use transform::IndexTransform;
+pub use vector_image::VectorImage;
+pub use view::{ImageView, ImageViewMut};
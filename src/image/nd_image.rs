@@ -0,0 +1,130 @@
+// src/image/nd_image.rs
+use std::any::Any;
+use bytemuck::Pod;
+use num_traits::NumCast;
+
+use super::image::{AnyImage, Image};
+use super::metadata::Metadata;
+
+/// N-dimensional companion of `Image<T>`, e.g. for 2D slices or 4D time series.
+///
+/// `size`, `spacing` and `origin` hold one value per dimension, `direction`
+/// is the row-major `ndims x ndims` direction matrix.
+#[derive(Debug, Clone)]
+pub struct NdImage<T> {
+    pub voxels: Vec<T>,
+    pub size: Vec<u32>,
+    pub spacing: Vec<f64>,
+    pub origin: Vec<f64>,
+    pub direction: Vec<f64>,
+    pub metadata: Metadata,
+}
+
+impl<T> NdImage<T> {
+    pub fn ndims(&self) -> usize {
+        self.size.len()
+    }
+
+    pub fn num_voxels(&self) -> usize {
+        self.size.iter().map(|&s| s as usize).product()
+    }
+}
+
+// Top-left 3x3 block of an n x n direction matrix, padded with identity.
+pub(crate) fn direction3(direction: &[f64], n: usize) -> [f64; 9] {
+    let mut direction3 = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+    for row in 0..n.min(3) {
+        for col in 0..n.min(3) {
+            direction3[row * 3 + col] = direction[row * n + col];
+        }
+    }
+    direction3
+}
+
+// First three values, padded with `fill` for lower dimensional images.
+pub(crate) fn padded3(values: &[f64], fill: f64) -> [f64; 3] {
+    let mut out = [fill; 3];
+    for (o, v) in out.iter_mut().zip(values) {
+        *o = *v;
+    }
+    out
+}
+
+impl<T> From<Image<T>> for NdImage<T> {
+    fn from(img: Image<T>) -> Self {
+        NdImage {
+            voxels: img.voxels,
+            size: vec![img.width, img.height, img.depth],
+            spacing: img.spacing.to_vec(),
+            origin: img.origin.to_vec(),
+            direction: img.direction.to_vec(),
+            metadata: img.metadata,
+        }
+    }
+}
+
+/// Images with up to three dimensions convert, missing axes get size 1.
+/// Higher dimensional images are handed back unchanged.
+impl<T> TryFrom<NdImage<T>> for Image<T> {
+    type Error = NdImage<T>;
+
+    fn try_from(img: NdImage<T>) -> Result<Self, Self::Error> {
+        if img.ndims() > 3 {
+            return Err(img);
+        }
+        let size = |axis: usize| img.size.get(axis).copied().unwrap_or(1);
+        Ok(Image {
+            width: size(0),
+            height: size(1),
+            depth: size(2),
+            spacing: padded3(&img.spacing, 1.0),
+            origin: padded3(&img.origin, 0.0),
+            direction: direction3(&img.direction, img.ndims()),
+            voxels: img.voxels,
+            metadata: img.metadata,
+        })
+    }
+}
+
+/// `width`/`height`/`depth` and the geometry report the first three axes,
+/// use `ndims` and `size` for the full shape.
+impl<T> AnyImage for NdImage<T>
+where
+    T: Pod + NumCast + Copy + 'static,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn width(&self) -> u32 {
+        self.size.first().copied().unwrap_or(1)
+    }
+    fn height(&self) -> u32 {
+        self.size.get(1).copied().unwrap_or(1)
+    }
+    fn depth(&self) -> u32 {
+        self.size.get(2).copied().unwrap_or(1)
+    }
+    fn spacing(&self) -> [f64; 3] {
+        padded3(&self.spacing, 1.0)
+    }
+    fn origin(&self) -> [f64; 3] {
+        padded3(&self.origin, 0.0)
+    }
+    fn direction(&self) -> [f64; 9] {
+        direction3(&self.direction, self.ndims())
+    }
+    fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+    fn ndims(&self) -> usize {
+        NdImage::ndims(self)
+    }
+    fn size(&self) -> Vec<u32> {
+        self.size.clone()
+    }
+
+    fn iter_f64(&self) -> Box<dyn Iterator<Item = f64> + '_> {
+        Box::new(self.voxels.iter().map(|&x| NumCast::from(x).unwrap()))
+    }
+}
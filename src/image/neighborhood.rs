@@ -0,0 +1,163 @@
+// src/image/neighborhood.rs
+use super::image::Image;
+use super::view::ImageView;
+
+/// What a `Neighborhood` reads at positions outside the image (or view).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Boundary<T> {
+    /// A fixed value, e.g. 0 (zero padding).
+    Constant(T),
+    /// The nearest voxel on the edge (ITK's zero flux Neumann condition).
+    Clamp,
+    /// Reflected at the edge voxel, which is not repeated: -1 reads 1.
+    Mirror,
+    /// Wrapped around to the other side, as if the image were periodic.
+    Wrap,
+}
+
+impl<T: Copy> Boundary<T> {
+    // Index along an axis of `size` voxels that position `i` reads, or the constant value.
+    fn resolve(&self, i: isize, size: usize) -> Result<usize, T> {
+        if (0..size as isize).contains(&i) {
+            return Ok(i as usize);
+        }
+        let last = size as isize - 1;
+        match *self {
+            Boundary::Constant(value) => Err(value),
+            Boundary::Clamp => Ok(i.clamp(0, last) as usize),
+            Boundary::Mirror if size == 1 => Ok(0),
+            Boundary::Mirror => {
+                let i = i.rem_euclid(2 * last);
+                Ok(if i > last { 2 * last - i } else { i } as usize)
+            }
+            Boundary::Wrap => Ok(i.rem_euclid(size as isize) as usize),
+        }
+    }
+}
+
+
+/// The voxels within `radius` of a center voxel, a box of
+/// `2 * radius + 1` voxels along each axis.
+#[derive(Debug, Clone, Copy)]
+pub struct Neighborhood<'a, T> {
+    image: ImageView<'a, T>,
+    center: [usize; 3],
+    radius: [usize; 3],
+    boundary: Boundary<T>,
+}
+
+impl<'a, T: Copy> Neighborhood<'a, T> {
+    pub fn center(&self) -> [usize; 3] {
+        self.center
+    }
+
+    pub fn radius(&self) -> [usize; 3] {
+        self.radius
+    }
+
+    /// Number of positions in the neighborhood, including the center.
+    pub fn len(&self) -> usize {
+        self.radius.iter().map(|r| 2 * r + 1).product()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    pub fn center_value(&self) -> T {
+        self.image[self.center]
+    }
+
+    /// Value at `offset` from the center, following the boundary condition
+    /// outside the image, or outside the view for a view's neighborhoods
+    /// (the voxels around it in the image are not read). Offsets beyond the
+    /// radius are allowed.
+    pub fn get(&self, offset: [isize; 3]) -> T {
+        let size = self.image.size();
+        let mut index = [0; 3];
+        for axis in 0..3 {
+            let i = self.center[axis] as isize + offset[axis];
+            match self.boundary.resolve(i, size[axis] as usize) {
+                Ok(i) => index[axis] = i,
+                Err(value) => return value,
+            }
+        }
+        self.image[index]
+    }
+
+    /// Iterate through `(offset, value)` for all positions, x offset fastest.
+    pub fn iter(&self) -> impl Iterator<Item = ([isize; 3], T)> + '_ {
+        let [rx, ry, rz] = self.radius.map(|r| r as isize);
+        (-rz..=rz).flat_map(move |z| (-ry..=ry).flat_map(move |y| (-rx..=rx).map(move |x| [x, y, z])))
+            .map(|offset| (offset, self.get(offset)))
+    }
+
+    /// All values, in the order of `iter`.
+    pub fn values(&self) -> impl Iterator<Item = T> + '_ {
+        self.iter().map(|(_, value)| value)
+    }
+}
+
+
+/// Iterator over the neighborhood of every voxel, in memory order.
+#[derive(Debug, Clone)]
+pub struct Neighborhoods<'a, T> {
+    image: ImageView<'a, T>,
+    radius: [usize; 3],
+    boundary: Boundary<T>,
+    next: usize,
+}
+
+impl<'a, T: Copy> Iterator for Neighborhoods<'a, T> {
+    type Item = Neighborhood<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.image.num_voxels() {
+            return None;
+        }
+        let center = self.image.voxel_index(self.next);
+        self.next += 1;
+        Some(Neighborhood { image: self.image, center, radius: self.radius, boundary: self.boundary })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.image.num_voxels().saturating_sub(self.next);
+        (remaining, Some(remaining))
+    }
+}
+
+impl<T: Copy> ExactSizeIterator for Neighborhoods<'_, T> {}
+
+
+impl<'a, T: Copy> ImageView<'a, T> {
+    /// Neighborhood of `radius` voxels around `center`, in view indices.
+    ///
+    /// Panics if `center` is outside the view.
+    pub fn neighborhood(&self, center: [usize; 3], radius: [usize; 3], boundary: Boundary<T>) -> Neighborhood<'a, T> {
+        assert!(
+            self.contains(center),
+            "voxel index {:?} out of bounds for size {:?}", center, self.size(),
+        );
+        Neighborhood { image: *self, center, radius, boundary }
+    }
+
+    /// Iterate through the neighborhood of every voxel of the view, x fastest.
+    pub fn neighborhoods(&self, radius: [usize; 3], boundary: Boundary<T>) -> Neighborhoods<'a, T> {
+        Neighborhoods { image: *self, radius, boundary, next: 0 }
+    }
+}
+
+impl<T: Copy> Image<T> {
+    /// Neighborhood of `radius` voxels around `center`.
+    ///
+    /// Panics if `center` is outside the image.
+    pub fn neighborhood(&self, center: [usize; 3], radius: [usize; 3], boundary: Boundary<T>) -> Neighborhood<'_, T> {
+        self.view().neighborhood(center, radius, boundary)
+    }
+
+    /// Iterate through the neighborhood of every voxel in memory order,
+    /// e.g. `image.neighborhoods([1, 1, 1], Boundary::Clamp)` for a 3x3x3 filter.
+    pub fn neighborhoods(&self, radius: [usize; 3], boundary: Boundary<T>) -> Neighborhoods<'_, T> {
+        self.view().neighborhoods(radius, boundary)
+    }
+}
@@ -0,0 +1,104 @@
+// src/image/transform.rs
+use super::image::Image;
+
+/// Mapping between voxel indices and physical (world) coordinates, as ITK
+/// defines it: `physical = origin + D * diag(spacing) * index`, where the
+/// columns of D are the axis directions, i.e. the rows of `Image::direction`.
+///
+/// The inverse matrix is computed once on construction, keep the transform
+/// around when mapping many points. The `Image` methods of the same names
+/// are one-off shortcuts that build a new transform on each call.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IndexTransform {
+    size: [u32; 3],
+    origin: [f64; 3],
+    // D * diag(spacing), row-major: physical axis j = row j.
+    matrix: [f64; 9],
+    inverse: [f64; 9],
+}
+
+// Inverse of a row-major 3x3 matrix, NaN if it is singular.
+fn invert(m: &[f64; 9]) -> [f64; 9] {
+    let cofactor = |r0: usize, r1: usize, c0: usize, c1: usize| m[r0 * 3 + c0] * m[r1 * 3 + c1] - m[r0 * 3 + c1] * m[r1 * 3 + c0];
+    let adjugate = [
+        cofactor(1, 2, 1, 2), -cofactor(0, 2, 1, 2), cofactor(0, 1, 1, 2),
+        -cofactor(1, 2, 0, 2), cofactor(0, 2, 0, 2), -cofactor(0, 1, 0, 2),
+        cofactor(1, 2, 0, 1), -cofactor(0, 2, 0, 1), cofactor(0, 1, 0, 1),
+    ];
+    let det = m[0] * adjugate[0] + m[1] * adjugate[3] + m[2] * adjugate[6];
+    if det == 0.0 || !det.is_finite() {
+        return [f64::NAN; 9];
+    }
+    adjugate.map(|a| a / det)
+}
+
+impl IndexTransform {
+    pub fn new(size: [u32; 3], spacing: [f64; 3], origin: [f64; 3], direction: [f64; 9]) -> Self {
+        let matrix = std::array::from_fn(|i| {
+            let (row, axis) = (i / 3, i % 3);
+            direction[axis * 3 + row] * spacing[axis]
+        });
+        IndexTransform { size, origin, matrix, inverse: invert(&matrix) }
+    }
+
+    /// Physical position of a (continuous) index.
+    pub fn index_to_physical(&self, index: [f64; 3]) -> [f64; 3] {
+        std::array::from_fn(|row| {
+            self.origin[row] + (0..3).map(|axis| self.matrix[row * 3 + axis] * index[axis]).sum::<f64>()
+        })
+    }
+
+    /// Continuous index of a physical position, all NaN if the direction
+    /// matrix or the spacing is degenerate.
+    pub fn physical_to_continuous_index(&self, point: [f64; 3]) -> [f64; 3] {
+        let offset: [f64; 3] = std::array::from_fn(|j| point[j] - self.origin[j]);
+        std::array::from_fn(|axis| (0..3).map(|j| self.inverse[axis * 3 + j] * offset[j]).sum())
+    }
+
+    /// Index of the voxel whose center is nearest to `point`, with halves
+    /// rounded up as ITK does. `None` if that voxel is outside the image.
+    pub fn physical_to_index(&self, point: [f64; 3]) -> Option<[usize; 3]> {
+        let continuous = self.physical_to_continuous_index(point);
+        let mut index = [0; 3];
+        for axis in 0..3 {
+            let i = (continuous[axis] + 0.5).floor();
+            // NaN fails this check as well.
+            if !(i >= 0.0 && i < self.size[axis] as f64) {
+                return None;
+            }
+            index[axis] = i as usize;
+        }
+        Some(index)
+    }
+}
+
+
+impl<T> Image<T> {
+    /// The index to physical transform of this image's geometry, see `IndexTransform`.
+    pub fn index_transform(&self) -> IndexTransform {
+        IndexTransform::new([self.width, self.height, self.depth], self.spacing, self.origin, self.direction)
+    }
+
+    /// Physical position (e.g. in mm) of the center of voxel `index`.
+    ///
+    /// Builds the transform on every call, hold on to `index_transform()`
+    /// when mapping many points.
+    pub fn index_to_physical(&self, index: [usize; 3]) -> [f64; 3] {
+        self.index_transform().index_to_physical(index.map(|i| i as f64))
+    }
+
+    /// Continuous index of `point`, which may lie outside the image.
+    ///
+    /// Builds the transform and inverts its matrix on every call, use the
+    /// cached inverse of `index_transform()` when mapping many points.
+    pub fn physical_to_continuous_index(&self, point: [f64; 3]) -> [f64; 3] {
+        self.index_transform().physical_to_continuous_index(point)
+    }
+
+    /// Voxel nearest to `point`, `None` outside the image.
+    ///
+    /// Inverts the matrix on every call, as `physical_to_continuous_index` does.
+    pub fn physical_to_index(&self, point: [f64; 3]) -> Option<[usize; 3]> {
+        self.index_transform().physical_to_index(point)
+    }
+}
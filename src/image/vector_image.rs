@@ -0,0 +1,134 @@
+// src/image/vector_image.rs
+use std::any::Any;
+use bytemuck::Pod;
+use num_traits::NumCast;
+
+use super::image::{AnyImage, Image};
+use super::metadata::Metadata;
+
+/// 3D image with `channels` components per voxel, e.g. RGB images or
+/// displacement fields. Components are interleaved: `voxels` holds all
+/// channels of the first voxel, then all channels of the second, etc.
+#[derive(Debug, Clone)]
+pub struct VectorImage<T> {
+    pub voxels: Vec<T>,
+    pub channels: u32,
+    pub width:  u32,
+    pub height: u32,
+    pub depth:  u32,
+    pub spacing: [f64; 3],
+    pub origin:  [f64; 3],
+    pub direction: [f64; 9],
+    pub metadata: Metadata,
+}
+
+impl<T> VectorImage<T> {
+    pub fn num_voxels(&self) -> usize {
+        (self.width as usize) * (self.height as usize) * (self.depth as usize)
+    }
+
+    /// All channels of the voxel at flat index `index`.
+    pub fn pixel(&self, index: usize) -> &[T] {
+        let c = self.channels as usize;
+        &self.voxels[index * c..(index + 1) * c]
+    }
+
+    pub fn pixel_mut(&mut self, index: usize) -> &mut [T] {
+        let c = self.channels as usize;
+        &mut self.voxels[index * c..(index + 1) * c]
+    }
+
+    /// Iterate through voxels in memory order, one slice of channels each.
+    pub fn pixels(&self) -> std::slice::ChunksExact<'_, T> {
+        self.voxels.chunks_exact(self.channels as usize)
+    }
+
+    /// Iterate through a single channel.
+    pub fn channel_iter(&self, channel: usize) -> impl Iterator<Item = &T> + '_ {
+        assert!(channel < self.channels as usize, "channel {} out of range", channel);
+        self.voxels.iter().skip(channel).step_by(self.channels as usize)
+    }
+}
+
+impl<T: Copy> VectorImage<T> {
+    /// Copy a single channel out into a scalar image.
+    pub fn channel(&self, channel: usize) -> Image<T> {
+        Image {
+            voxels: self.channel_iter(channel).copied().collect(),
+            width: self.width,
+            height: self.height,
+            depth: self.depth,
+            spacing: self.spacing,
+            origin: self.origin,
+            direction: self.direction,
+            metadata: self.metadata.clone(),
+        }
+    }
+
+    /// Interleave scalar images into one vector image, geometry is taken from
+    /// the first image. Returns `None` if `images` is empty or sizes differ.
+    pub fn from_channels(images: &[Image<T>]) -> Option<Self> {
+        let first = images.first()?;
+        let same_size = images.iter().all(|i| {
+            (i.width, i.height, i.depth) == (first.width, first.height, first.depth)
+                && i.voxels.len() == first.voxels.len()
+        });
+        if !same_size {
+            return None;
+        }
+
+        let voxels = (0..first.voxels.len())
+            .flat_map(|index| images.iter().map(move |i| i.voxels[index]))
+            .collect();
+        Some(VectorImage {
+            voxels,
+            channels: images.len() as u32,
+            width: first.width,
+            height: first.height,
+            depth: first.depth,
+            spacing: first.spacing,
+            origin: first.origin,
+            direction: first.direction,
+            metadata: first.metadata.clone(),
+        })
+    }
+}
+
+impl<T> AnyImage for VectorImage<T>
+where
+    T: Pod + NumCast + Copy + 'static,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn width(&self) -> u32 {
+        self.width
+    }
+    fn height(&self) -> u32 {
+        self.height
+    }
+    fn depth(&self) -> u32 {
+        self.depth
+    }
+    fn spacing(&self) -> [f64; 3] {
+        self.spacing
+    }
+    fn origin(&self) -> [f64; 3] {
+        self.origin
+    }
+    fn direction(&self) -> [f64; 9] {
+        self.direction
+    }
+    fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+    fn channels(&self) -> u32 {
+        self.channels
+    }
+
+    /// Iterates through all components, interleaved.
+    fn iter_f64(&self) -> Box<dyn Iterator<Item = f64> + '_> {
+        Box::new(self.voxels.iter().map(|&x| NumCast::from(x).unwrap()))
+    }
+}
@@ -0,0 +1,376 @@
+// src/image/view.rs
+use std::ops::{Index, IndexMut};
+
+use super::image::Image;
+use super::metadata::Metadata;
+use super::transform::IndexTransform;
+
+// Where the voxels of a view sit in the parent's `voxels`, and the geometry
+// of the view. Along every axis of size > 1 the strides grow with the axis,
+// so iterating x fastest visits the voxels in memory order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Layout {
+    offset: usize,
+    strides: [usize; 3],
+    size: [usize; 3],
+    spacing: [f64; 3],
+    origin: [f64; 3],
+    direction: [f64; 9],
+}
+
+impl Layout {
+    fn of<T>(image: &Image<T>) -> Self {
+        let (width, height) = (image.width as usize, image.height as usize);
+        Layout {
+            offset: 0,
+            strides: [1, width, width * height],
+            size: [width, height, image.depth as usize],
+            spacing: image.spacing,
+            origin: image.origin,
+            direction: image.direction,
+        }
+    }
+
+    fn num_voxels(&self) -> usize {
+        self.size.iter().product()
+    }
+
+    fn contains(&self, index: [usize; 3]) -> bool {
+        (0..3).all(|axis| index[axis] < self.size[axis])
+    }
+
+    // Position of `index` in the parent's voxels. Not bounds checked.
+    fn position(&self, index: [usize; 3]) -> usize {
+        self.offset + (0..3).map(|axis| index[axis] * self.strides[axis]).sum::<usize>()
+    }
+
+    // Index of the `i`th voxel, x varies fastest.
+    fn voxel_index(&self, i: usize) -> [usize; 3] {
+        let [width, height, _] = self.size;
+        [i % width, i / width % height, i / (width * height)]
+    }
+
+    fn transform(&self) -> IndexTransform {
+        IndexTransform::new(self.size.map(|n| n as u32), self.spacing, self.origin, self.direction)
+    }
+
+    // `(index, position)` of every voxel, with increasing positions.
+    fn positions(self) -> impl Iterator<Item = ([usize; 3], usize)> {
+        (0..self.num_voxels()).map(move |i| {
+            let index = self.voxel_index(i);
+            (index, self.position(index))
+        })
+    }
+
+    fn region(&self, start: [usize; 3], size: [usize; 3]) -> Self {
+        let fits = (0..3).all(|axis| start[axis].checked_add(size[axis]).is_some_and(|end| end <= self.size[axis]));
+        assert!(fits, "region at {:?} of size {:?} out of bounds for size {:?}", start, size, self.size);
+        Layout {
+            offset: self.position(start),
+            size,
+            origin: self.transform().index_to_physical(start.map(|i| i as f64)),
+            ..*self
+        }
+    }
+
+    // The remaining axes keep their order and become x and y, the sliced
+    // axis becomes z with size 1.
+    fn slice(&self, axis: usize, index: usize) -> Self {
+        assert!(axis < 3, "slice axis {} is not 0, 1 or 2", axis);
+        assert!(
+            index < self.size[axis],
+            "slice {} along axis {} out of bounds for size {:?}", index, axis, self.size,
+        );
+        let order = match axis {
+            0 => [1, 2, 0],
+            1 => [0, 2, 1],
+            _ => [0, 1, 2],
+        };
+        let mut start = [0; 3];
+        start[axis] = index;
+        Layout {
+            offset: self.position(start),
+            strides: order.map(|a| self.strides[a]),
+            size: order.map(|a| if a == axis { 1 } else { self.size[a] }),
+            spacing: order.map(|a| self.spacing[a]),
+            origin: self.transform().index_to_physical(start.map(|i| i as f64)),
+            direction: std::array::from_fn(|i| self.direction[order[i / 3] * 3 + i % 3]),
+        }
+    }
+}
+
+
+/// A borrowed, axis-aligned part of an `Image` (a region or a 2D slice),
+/// read in place without copying. The geometry is that of the part itself:
+/// index `[0, 0, 0]` is the first voxel of the view and `origin()` its
+/// physical position, so the view lies where it did in the image.
+#[derive(Debug)]
+pub struct ImageView<'a, T> {
+    voxels: &'a [T],
+    metadata: &'a Metadata,
+    layout: Layout,
+}
+
+impl<T> Clone for ImageView<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ImageView<'_, T> {}
+
+impl<'a, T> ImageView<'a, T> {
+    pub fn width(&self) -> u32 {
+        self.layout.size[0] as u32
+    }
+
+    pub fn height(&self) -> u32 {
+        self.layout.size[1] as u32
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.layout.size[2] as u32
+    }
+
+    pub fn size(&self) -> [u32; 3] {
+        self.layout.size.map(|n| n as u32)
+    }
+
+    pub fn spacing(&self) -> [f64; 3] {
+        self.layout.spacing
+    }
+
+    pub fn origin(&self) -> [f64; 3] {
+        self.layout.origin
+    }
+
+    pub fn direction(&self) -> [f64; 9] {
+        self.layout.direction
+    }
+
+    /// Metadata of the image the view is part of.
+    pub fn metadata(&self) -> &'a Metadata {
+        self.metadata
+    }
+
+    pub fn num_voxels(&self) -> usize {
+        self.layout.num_voxels()
+    }
+
+    pub fn contains(&self, index: [usize; 3]) -> bool {
+        self.layout.contains(index)
+    }
+
+    // Index of the `i`th voxel in the order of `iter`.
+    pub(crate) fn voxel_index(&self, i: usize) -> [usize; 3] {
+        self.layout.voxel_index(i)
+    }
+
+    /// The index to physical transform of the view, see `IndexTransform`.
+    pub fn index_transform(&self) -> IndexTransform {
+        self.layout.transform()
+    }
+
+    /// Voxel at `(x, y, z)` of the view, `None` if out of bounds.
+    pub fn checked_get(&self, x: usize, y: usize, z: usize) -> Option<&'a T> {
+        if !self.contains([x, y, z]) {
+            return None;
+        }
+        self.voxels.get(self.layout.position([x, y, z]))
+    }
+
+    /// Voxel at `(x, y, z)` of the view.
+    ///
+    /// Panics if any coordinate is out of bounds of the view.
+    pub fn get(&self, x: usize, y: usize, z: usize) -> &'a T {
+        self.checked_get(x, y, z)
+            .unwrap_or_else(|| panic!("voxel index {:?} out of bounds for size {:?}", [x, y, z], self.size()))
+    }
+
+    /// Iterate through the voxels, x fastest.
+    pub fn iter(&self) -> impl Iterator<Item = &'a T> + 'a {
+        self.indexed_iter().map(|(_, v)| v)
+    }
+
+    /// Iterate through `([x, y, z], &voxel)`, x fastest.
+    pub fn indexed_iter(&self) -> impl Iterator<Item = ([usize; 3], &'a T)> + 'a {
+        let voxels = self.voxels;
+        self.layout.positions().map(move |(index, position)| (index, &voxels[position]))
+    }
+
+    /// Sub-region of `size` voxels starting at `start`, in view indices.
+    ///
+    /// Panics if the region does not fit in the view.
+    pub fn region(&self, start: [usize; 3], size: [usize; 3]) -> ImageView<'a, T> {
+        ImageView { layout: self.layout.region(start, size), ..*self }
+    }
+
+    /// 2D slice at `index` along `axis` (0, 1 or 2). The other two axes
+    /// become x and y in their original order, e.g. a slice along x has
+    /// size `[height, depth, 1]`, with the spacing and direction rows
+    /// reordered to match.
+    ///
+    /// Panics if `axis` is not 0, 1 or 2 or `index` is out of bounds.
+    pub fn slice(&self, axis: usize, index: usize) -> ImageView<'a, T> {
+        ImageView { layout: self.layout.slice(axis, index), ..*self }
+    }
+
+    /// Copy of the view as an image of its own, with the view's geometry.
+    pub fn to_image(&self) -> Image<T>
+    where
+        T: Clone,
+    {
+        let [width, height, depth] = self.size();
+        Image {
+            voxels: self.iter().cloned().collect(),
+            width,
+            height,
+            depth,
+            spacing: self.spacing(),
+            origin: self.origin(),
+            direction: self.direction(),
+            metadata: self.metadata.clone(),
+        }
+    }
+}
+
+impl<T> Index<[usize; 3]> for ImageView<'_, T> {
+    type Output = T;
+
+    /// Voxel at `[x, y, z]` of the view, panics if out of bounds.
+    fn index(&self, [x, y, z]: [usize; 3]) -> &T {
+        self.get(x, y, z)
+    }
+}
+
+
+/// A mutably borrowed region or 2D slice of an `Image`, see `ImageView`.
+/// Writes go straight to the image.
+#[derive(Debug)]
+pub struct ImageViewMut<'a, T> {
+    voxels: &'a mut [T],
+    metadata: &'a Metadata,
+    layout: Layout,
+}
+
+impl<'a, T> ImageViewMut<'a, T> {
+    /// Read-only view of the same voxels, which has the geometry and
+    /// read access methods.
+    pub fn as_view(&self) -> ImageView<'_, T> {
+        ImageView { voxels: self.voxels, metadata: self.metadata, layout: self.layout }
+    }
+
+    pub fn into_view(self) -> ImageView<'a, T> {
+        ImageView { voxels: self.voxels, metadata: self.metadata, layout: self.layout }
+    }
+
+    pub fn size(&self) -> [u32; 3] {
+        self.layout.size.map(|n| n as u32)
+    }
+
+    pub fn origin(&self) -> [f64; 3] {
+        self.layout.origin
+    }
+
+    pub fn checked_get_mut(&mut self, x: usize, y: usize, z: usize) -> Option<&mut T> {
+        if !self.layout.contains([x, y, z]) {
+            return None;
+        }
+        self.voxels.get_mut(self.layout.position([x, y, z]))
+    }
+
+    /// Panics if any coordinate is out of bounds of the view.
+    pub fn get_mut(&mut self, x: usize, y: usize, z: usize) -> &mut T {
+        let size = self.size();
+        self.checked_get_mut(x, y, z)
+            .unwrap_or_else(|| panic!("voxel index {:?} out of bounds for size {:?}", [x, y, z], size))
+    }
+
+    /// Iterate through the voxels, x fastest.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> + '_ {
+        self.indexed_iter_mut().map(|(_, v)| v)
+    }
+
+    /// Iterate through `([x, y, z], &mut voxel)`, x fastest.
+    pub fn indexed_iter_mut(&mut self) -> impl Iterator<Item = ([usize; 3], &mut T)> + '_ {
+        // Positions only increase, so each voxel is split off the front
+        // of what is left of the slice.
+        let mut rest = &mut self.voxels[..];
+        let mut skipped = 0;
+        self.layout.positions().map(move |(index, position)| {
+            let (_, tail) = std::mem::take(&mut rest).split_at_mut(position - skipped);
+            let (voxel, tail) = tail.split_first_mut().unwrap();
+            rest = tail;
+            skipped = position + 1;
+            (index, voxel)
+        })
+    }
+
+    /// Set every voxel of the view to `value`.
+    pub fn fill(&mut self, value: T)
+    where
+        T: Clone,
+    {
+        self.iter_mut().for_each(|v| *v = value.clone());
+    }
+
+    /// Sub-region of this view, see `ImageView::region`.
+    pub fn region_mut(&mut self, start: [usize; 3], size: [usize; 3]) -> ImageViewMut<'_, T> {
+        ImageViewMut { layout: self.layout.region(start, size), voxels: &mut *self.voxels, metadata: self.metadata }
+    }
+
+    /// 2D slice of this view, see `ImageView::slice`.
+    pub fn slice_mut(&mut self, axis: usize, index: usize) -> ImageViewMut<'_, T> {
+        ImageViewMut { layout: self.layout.slice(axis, index), voxels: &mut *self.voxels, metadata: self.metadata }
+    }
+}
+
+impl<T> Index<[usize; 3]> for ImageViewMut<'_, T> {
+    type Output = T;
+
+    fn index(&self, [x, y, z]: [usize; 3]) -> &T {
+        self.as_view().get(x, y, z)
+    }
+}
+
+impl<T> IndexMut<[usize; 3]> for ImageViewMut<'_, T> {
+    fn index_mut(&mut self, [x, y, z]: [usize; 3]) -> &mut T {
+        self.get_mut(x, y, z)
+    }
+}
+
+
+impl<T> Image<T> {
+    /// The whole image as a view.
+    pub fn view(&self) -> ImageView<'_, T> {
+        ImageView { voxels: &self.voxels, metadata: &self.metadata, layout: Layout::of(self) }
+    }
+
+    pub fn view_mut(&mut self) -> ImageViewMut<'_, T> {
+        let layout = Layout::of(self);
+        ImageViewMut { voxels: &mut self.voxels, metadata: &self.metadata, layout }
+    }
+
+    /// Region of `size` voxels starting at voxel `start`, without copying.
+    /// Use `to_image` on the view to crop.
+    ///
+    /// Panics if the region does not fit in the image.
+    pub fn region(&self, start: [usize; 3], size: [usize; 3]) -> ImageView<'_, T> {
+        self.view().region(start, size)
+    }
+
+    pub fn region_mut(&mut self, start: [usize; 3], size: [usize; 3]) -> ImageViewMut<'_, T> {
+        let layout = Layout::of(self).region(start, size);
+        ImageViewMut { voxels: &mut self.voxels, metadata: &self.metadata, layout }
+    }
+
+    /// 2D slice at `index` along `axis`, see `ImageView::slice`.
+    pub fn slice(&self, axis: usize, index: usize) -> ImageView<'_, T> {
+        self.view().slice(axis, index)
+    }
+
+    pub fn slice_mut(&mut self, axis: usize, index: usize) -> ImageViewMut<'_, T> {
+        let layout = Layout::of(self).slice(axis, index);
+        ImageViewMut { voxels: &mut self.voxels, metadata: &self.metadata, layout }
+    }
+}
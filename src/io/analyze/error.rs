@@ -0,0 +1,35 @@
+#[derive(Debug)]
+pub enum AnalyzeError {
+    Io(std::io::Error),     // Generic I/O error (file issue)
+    InvalidHeader(String),  // Not an Analyze 7.5 header, or inconsistent fields
+    UnsupportedDataType(i16),
+    Unsupported(String),
+    Truncated { expected: usize, found: usize },
+}
+
+impl std::fmt::Display for AnalyzeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AnalyzeError::Io(e) => write!(f, "I/O error: {}", e),
+            AnalyzeError::InvalidHeader(what) => write!(f, "Invalid Analyze header: {}", what),
+            AnalyzeError::UnsupportedDataType(code) => write!(f, "Unsupported Analyze datatype: {}", code),
+            AnalyzeError::Unsupported(what) => write!(f, "Unsupported Analyze image: {}", what),
+            AnalyzeError::Truncated { expected, found } => {
+                write!(f, "Truncated voxel data: expected {} bytes, found {}", expected, found)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AnalyzeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AnalyzeError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for AnalyzeError {
+    fn from(e: std::io::Error) -> Self { AnalyzeError::Io(e) }
+}
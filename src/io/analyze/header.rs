@@ -0,0 +1,85 @@
+use crate::io::nifti::header::Fields;
+
+use super::error::AnalyzeError;
+
+pub(crate) const HEADER_SIZE: usize = 348;
+
+/// The fields of an Analyze 7.5 header (`struct dsr`) that we use.
+#[derive(Debug, Clone)]
+pub(super) struct AnalyzeHeader {
+    pub byte_order_msb: bool,
+    pub dim: [i16; 8],
+    pub datatype: i16,
+    pub bitpix: i16,
+    pub pixdim: [f64; 8],
+    pub vox_offset: f64,
+    pub descrip: String,
+    pub aux_file: String,
+    /// Slice orientation code, 0 to 5; what it means differs between tools.
+    pub orient: u8,
+}
+
+
+/// Whether `bytes` start with an Analyze 7.5 header, i.e. `sizeof_hdr` is
+/// 348 in either byte order and there is no NIfTI-1 magic.
+pub(crate) fn is_analyze(bytes: &[u8]) -> bool {
+    let Some(sizeof_hdr) = bytes.get(..4) else { return false };
+    let sizeof_hdr: [u8; 4] = sizeof_hdr.try_into().unwrap();
+    let size = HEADER_SIZE as i32;
+    (i32::from_le_bytes(sizeof_hdr) == size || i32::from_be_bytes(sizeof_hdr) == size)
+        && bytes.get(344..348).is_some_and(|m| m != b"n+1\0" && m != b"ni1\0")
+}
+
+
+impl AnalyzeHeader {
+    pub fn ndims(&self) -> usize {
+        self.dim[0] as usize
+    }
+
+    pub fn size(&self) -> Vec<u64> {
+        self.dim[1..=self.ndims()].iter().map(|&d| d as u64).collect()
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, AnalyzeError> {
+        if bytes.len() < HEADER_SIZE {
+            return Err(AnalyzeError::InvalidHeader(format!("header is {} bytes, expected {}", bytes.len(), HEADER_SIZE)));
+        }
+
+        // There is no magic, sizeof_hdr read in the wrong order gives the byte order away.
+        let sizeof_hdr: [u8; 4] = bytes[..4].try_into().unwrap();
+        let msb = match (i32::from_le_bytes(sizeof_hdr), i32::from_be_bytes(sizeof_hdr)) {
+            (348, _) => false,
+            (_, 348) => true,
+            (size, _) => {
+                return Err(AnalyzeError::InvalidHeader(format!("sizeof_hdr is {}, expected 348", size)));
+            }
+        };
+        if matches!(&bytes[344..348], b"n+1\0" | b"ni1\0") {
+            return Err(AnalyzeError::InvalidHeader("NIfTI-1 header, see load_nifti".into()));
+        }
+
+        let f = Fields { bytes, msb };
+        let header = AnalyzeHeader {
+            byte_order_msb: msb,
+            dim: std::array::from_fn(|i| f.i16(40 + 2 * i)),
+            datatype: f.i16(70),
+            bitpix: f.i16(72),
+            pixdim: std::array::from_fn(|i| f.f32(76 + 4 * i)),
+            vox_offset: f.f32(108),
+            descrip: f.text(148, 80),
+            aux_file: f.text(228, 24),
+            orient: bytes[252],
+        };
+
+        if !(1..=7).contains(&header.dim[0]) {
+            return Err(AnalyzeError::InvalidHeader(format!("dim[0] is {}, expected 1 to 7", header.dim[0])));
+        }
+        if header.dim[1..=header.ndims()].iter().any(|&d| d <= 0) {
+            return Err(AnalyzeError::InvalidHeader(format!("invalid dimensions {:?}", &header.dim[1..=header.ndims()])));
+        }
+        if !header.vox_offset.is_finite() || header.vox_offset < 0.0 {
+            return Err(AnalyzeError::InvalidHeader(format!("vox_offset is {}", header.vox_offset)));
+        }
+        Ok(header)
+    }
+}
@@ -0,0 +1,132 @@
+use crate::image::{Image, NdImage, AnyImage, Metadata};
+use crate::io::meta_image::image::bytes_to_vec;
+
+use super::error::AnalyzeError;
+use super::header::AnalyzeHeader;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use bytemuck::Pod;
+use num_traits::NumCast;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DataType {
+    I8, U8,
+    I16, U16,
+    I32, U32, F32,
+    F64,
+}
+
+impl DataType {
+    // Analyze 7.5 codes, and the 8-32 bit types SPM added (as NIfTI has them).
+    fn from_code(code: i16) -> Result<Self, AnalyzeError> {
+        use DataType::*;
+        match code {
+            2 => Ok(U8),
+            4 => Ok(I16),
+            8 => Ok(I32),
+            16 => Ok(F32),
+            64 => Ok(F64),
+            256 => Ok(I8),
+            512 => Ok(U16),
+            768 => Ok(U32),
+            _ => Err(AnalyzeError::UnsupportedDataType(code)),
+        }
+    }
+
+    fn bytes(self) -> usize {
+        use DataType::*;
+        match self {
+            U8 | I8 => 1,
+            U16 | I16 => 2,
+            U32 | I32 | F32 => 4,
+            F64 => 8,
+        }
+    }
+}
+
+
+// 3D images load as `Image<T>`, anything else as `NdImage<T>`, like NIfTI does.
+fn build_image<T: Pod + NumCast + Copy + 'static>(data: Vec<u8>, header: &AnalyzeHeader) -> Box<dyn AnyImage> {
+    let n = header.ndims();
+    let spacing = (1..=n).map(|a| match header.pixdim[a].abs() {
+        s if s.is_finite() && s > 0.0 => s,
+        _ => 1.0,
+    }).collect();
+    let mut direction = vec![0.0; n * n];
+    (0..n).for_each(|i| direction[i * n + i] = 1.0);
+
+    let mut metadata = Metadata::new();
+    for (key, value) in [("descrip", &header.descrip), ("aux_file", &header.aux_file)] {
+        if !value.is_empty() {
+            metadata.insert(key, value);
+        }
+    }
+    metadata.insert("orient", header.orient.to_string());
+
+    let image = NdImage {
+        voxels: bytes_to_vec::<T>(data, header.byte_order_msb),
+        size: header.size().iter().map(|&s| s as u32).collect(),
+        spacing,
+        origin: vec![0.0; n],
+        direction,
+        metadata,
+    };
+    if n != 3 {
+        return Box::new(image);
+    }
+    match Image::try_from(image) {
+        Ok(image) => Box::new(image),
+        Err(image) => Box::new(image),
+    }
+}
+
+
+fn read_file(path: &Path) -> Result<Vec<u8>, AnalyzeError> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+
+/// Load an Analyze 7.5 image from its .hdr/.img pair, `filename` can be
+/// either of the two. The byte order is detected from `sizeof_hdr`.
+///
+/// 3D images load as `Image<T>`, others as `NdImage<T>`, with the voxel
+/// size from `pixdim`. Analyze has no reliable orientation: the `orient`
+/// code is read differently by different tools and the origin is not
+/// stored, so the image is loaded with an identity direction and a zero
+/// origin. `orient` is kept in the metadata, check it before relying on
+/// the axes and convert such data to NIfTI where the orientation is known.
+pub fn load_analyze(filename: &str) -> Result<Box<dyn AnyImage>, AnalyzeError> {
+    let path = Path::new(filename);
+    let header = AnalyzeHeader::parse(&read_file(&path.with_extension("hdr"))?)?;
+    let dtype = DataType::from_code(header.datatype)?;
+    if header.bitpix != 0 && header.bitpix as usize != dtype.bytes() * 8 {
+        return Err(AnalyzeError::InvalidHeader(format!("bitpix {} for datatype {}", header.bitpix, header.datatype)));
+    }
+
+    let total_bytes = header.size().iter()
+        .try_fold(dtype.bytes() as u64, |bytes, &s| bytes.checked_mul(s))
+        .and_then(|bytes| usize::try_from(bytes).ok())
+        .ok_or_else(|| AnalyzeError::InvalidHeader(format!("size {:?} is too large", header.size())))?;
+    let offset = header.vox_offset as usize;
+    let data = read_file(&path.with_extension("img"))?;
+    let found = data.len().saturating_sub(offset);
+    if found < total_bytes {
+        return Err(AnalyzeError::Truncated { expected: total_bytes, found });
+    }
+    let data = data[offset..offset + total_bytes].to_vec();
+
+    Ok(match dtype {
+        DataType::U8 => build_image::<u8>(data, &header),
+        DataType::I8 => build_image::<i8>(data, &header),
+        DataType::U16 => build_image::<u16>(data, &header),
+        DataType::I16 => build_image::<i16>(data, &header),
+        DataType::U32 => build_image::<u32>(data, &header),
+        DataType::I32 => build_image::<i32>(data, &header),
+        DataType::F32 => build_image::<f32>(data, &header),
+        DataType::F64 => build_image::<f64>(data, &header),
+    })
+}
@@ -0,0 +1,6 @@
+pub mod error;
+pub mod header;
+pub mod image;
+
+pub use error::AnalyzeError;
+pub use image::load_analyze;
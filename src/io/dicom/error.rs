@@ -0,0 +1,49 @@
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum DicomError {
+    Missing(&'static str),  // Missing attributes that we need
+    Io(std::io::Error),     // Generic I/O error (file issue)
+    NotDicom(PathBuf),
+    InvalidData(String),    // Malformed data set
+    Unsupported(String),
+    Truncated { expected: usize, found: usize },
+    Decompression(std::io::Error),
+    NoSeries(PathBuf),
+    MultipleSeries(Vec<String>),
+    InconsistentSeries(String),
+}
+
+impl std::fmt::Display for DicomError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DicomError::Missing(attribute) => write!(f, "Missing DICOM attribute: {}", attribute),
+            DicomError::Io(e) => write!(f, "I/O error: {}", e),
+            DicomError::NotDicom(path) => write!(f, "Not a DICOM file: {}", path.display()),
+            DicomError::InvalidData(what) => write!(f, "Invalid DICOM data: {}", what),
+            DicomError::Unsupported(what) => write!(f, "Unsupported DICOM: {}", what),
+            DicomError::Truncated { expected, found } => {
+                write!(f, "Truncated DICOM data: expected {} bytes, found {}", expected, found)
+            }
+            DicomError::Decompression(e) => write!(f, "Failed to inflate deflated data set: {}", e),
+            DicomError::NoSeries(path) => write!(f, "No DICOM images found in {}", path.display()),
+            DicomError::MultipleSeries(uids) => {
+                write!(f, "Found {} series, pick one of: {}", uids.len(), uids.join(", "))
+            }
+            DicomError::InconsistentSeries(what) => write!(f, "Inconsistent DICOM series: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for DicomError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DicomError::Io(e) | DicomError::Decompression(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for DicomError {
+    fn from(e: std::io::Error) -> Self { DicomError::Io(e) }
+}
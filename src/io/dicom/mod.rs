@@ -0,0 +1,6 @@
+pub mod error;
+pub mod parser;
+pub mod series;
+
+pub use error::DicomError;
+pub use series::{load_dicom_series, read_dicom_series, DicomSeries};
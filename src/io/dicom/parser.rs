@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use std::io::Read;
+use flate2::read::DeflateDecoder;
+
+use super::error::DicomError;
+
+// (group, element) packed into one number, e.g. 0x7FE0_0010 for Pixel Data.
+pub(super) type Tag = u32;
+
+pub(super) const TRANSFER_SYNTAX_UID: Tag = 0x0002_0010;
+pub(super) const PIXEL_DATA: Tag = 0x7FE0_0010;
+const ITEM: Tag = 0xFFFE_E000;
+const ITEM_DELIMITATION: Tag = 0xFFFE_E00D;
+const SEQUENCE_DELIMITATION: Tag = 0xFFFE_E0DD;
+const UNDEFINED_LENGTH: u32 = 0xFFFF_FFFF;
+
+const IMPLICIT_VR_LITTLE_ENDIAN: &str = "1.2.840.10008.1.2";
+const EXPLICIT_VR_LITTLE_ENDIAN: &str = "1.2.840.10008.1.2.1";
+const DEFLATED_EXPLICIT_VR_LITTLE_ENDIAN: &str = "1.2.840.10008.1.2.1.99";
+const EXPLICIT_VR_BIG_ENDIAN: &str = "1.2.840.10008.1.2.2";
+
+
+// Top-level elements of a DICOM file. Sequences are skipped, we only need
+// plain attributes and the pixel data.
+#[derive(Debug, Default)]
+pub(super) struct DataSet {
+    pub big_endian: bool,
+    elements: HashMap<Tag, Vec<u8>>,
+}
+
+impl DataSet {
+    pub fn bytes(&self, tag: Tag) -> Option<&[u8]> {
+        self.elements.get(&tag).map(|v| v.as_slice())
+    }
+
+    /// Text value with padding (spaces, NUL) removed.
+    pub fn string(&self, tag: Tag) -> Option<String> {
+        let value = self.bytes(tag)?;
+        let text = String::from_utf8_lossy(value);
+        let text = text.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+        (!text.is_empty()).then(|| text.to_string())
+    }
+
+    /// Backslash separated decimal or integer strings (DS, IS).
+    pub fn numbers(&self, tag: Tag) -> Result<Option<Vec<f64>>, DicomError> {
+        let Some(text) = self.string(tag) else { return Ok(None) };
+        text.split('\\')
+            .map(|v| v.trim().parse::<f64>().map_err(|_| {
+                DicomError::InvalidData(format!("({:04X},{:04X}) is not numeric: '{}'", tag >> 16, tag & 0xFFFF, text))
+            }))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Some)
+    }
+
+    pub fn number(&self, tag: Tag) -> Result<Option<f64>, DicomError> {
+        Ok(self.numbers(tag)?.and_then(|v| v.first().copied()))
+    }
+
+    /// Binary unsigned short (US).
+    pub fn u16(&self, tag: Tag) -> Option<u16> {
+        let raw: [u8; 2] = self.bytes(tag)?.get(..2)?.try_into().ok()?;
+        Some(if self.big_endian { u16::from_be_bytes(raw) } else { u16::from_le_bytes(raw) })
+    }
+}
+
+
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    explicit_vr: bool,
+    big_endian: bool,
+}
+
+impl<'a> Cursor<'a> {
+    fn is_empty(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DicomError> {
+        let found = self.bytes.len() - self.pos;
+        if found < len {
+            return Err(DicomError::Truncated { expected: len, found });
+        }
+        self.pos += len;
+        Ok(&self.bytes[self.pos - len..self.pos])
+    }
+
+    fn u16(&mut self) -> Result<u16, DicomError> {
+        let raw: [u8; 2] = self.take(2)?.try_into().unwrap();
+        Ok(if self.big_endian { u16::from_be_bytes(raw) } else { u16::from_le_bytes(raw) })
+    }
+
+    fn u32(&mut self) -> Result<u32, DicomError> {
+        let raw: [u8; 4] = self.take(4)?.try_into().unwrap();
+        Ok(if self.big_endian { u32::from_be_bytes(raw) } else { u32::from_le_bytes(raw) })
+    }
+
+    fn tag(&mut self) -> Result<Tag, DicomError> {
+        Ok(((self.u16()? as u32) << 16) | self.u16()? as u32)
+    }
+
+    fn peek_tag(&mut self) -> Result<Tag, DicomError> {
+        let pos = self.pos;
+        let tag = self.tag();
+        self.pos = pos;
+        tag
+    }
+
+    // Next element, the value is `None` for sequences of undefined length (skipped).
+    fn element(&mut self) -> Result<(Tag, Option<&'a [u8]>), DicomError> {
+        let tag = self.tag()?;
+        let length = if self.explicit_vr {
+            let vr = self.take(2)?;
+            // These VRs have two reserved bytes and a 32 bit length.
+            if matches!(vr, b"OB" | b"OD" | b"OF" | b"OL" | b"OV" | b"OW" | b"SQ" | b"SV" | b"UC" | b"UN" | b"UR" | b"UT" | b"UV") {
+                self.take(2)?;
+                self.u32()?
+            } else {
+                self.u16()? as u32
+            }
+        } else {
+            self.u32()?
+        };
+
+        if length == UNDEFINED_LENGTH {
+            if tag == PIXEL_DATA {
+                return Err(DicomError::Unsupported("encapsulated (compressed) pixel data".into()));
+            }
+            self.skip_sequence()?;
+            return Ok((tag, None));
+        }
+        Ok((tag, Some(self.take(length as usize)?)))
+    }
+
+    // Skip items up to and including the sequence delimitation item.
+    fn skip_sequence(&mut self) -> Result<(), DicomError> {
+        loop {
+            let tag = self.tag()?;
+            let length = self.u32()?;
+            match tag {
+                SEQUENCE_DELIMITATION => return Ok(()),
+                ITEM if length == UNDEFINED_LENGTH => {
+                    while self.peek_tag()? != ITEM_DELIMITATION {
+                        self.element()?;
+                    }
+                    self.tag()?;
+                    self.u32()?;
+                }
+                ITEM => {
+                    self.take(length as usize)?;
+                }
+                _ => {
+                    return Err(DicomError::InvalidData(format!(
+                        "expected a sequence item, found ({:04X},{:04X})", tag >> 16, tag & 0xFFFF
+                    )));
+                }
+            }
+        }
+    }
+}
+
+
+// Read all elements until the end of the data or the first element beyond `stop`.
+fn read_elements(cursor: &mut Cursor, data_set: &mut DataSet, stop: Tag) -> Result<(), DicomError> {
+    while !cursor.is_empty() {
+        if cursor.peek_tag()? > stop {
+            break;
+        }
+        let (tag, value) = cursor.element()?;
+        if let Some(value) = value {
+            data_set.elements.insert(tag, value.to_vec());
+        }
+    }
+    Ok(())
+}
+
+
+/// Parse a DICOM Part 10 file (or a bare implicit VR little endian data
+/// set). `None` if `bytes` do not look like DICOM at all. Elements after
+/// `stop` are not read, pass `PIXEL_DATA` to read everything we use.
+pub(super) fn parse(bytes: &[u8], stop: Tag) -> Result<Option<DataSet>, DicomError> {
+    let mut data_set = DataSet::default();
+
+    let has_preamble = bytes.len() >= 132 && &bytes[128..132] == b"DICM";
+    let (transfer_syntax, start) = if has_preamble {
+        // File meta information is always explicit VR little endian.
+        let mut cursor = Cursor { bytes, pos: 132, explicit_vr: true, big_endian: false };
+        while !cursor.is_empty() && cursor.peek_tag()? >> 16 == 0x0002 {
+            let (tag, value) = cursor.element()?;
+            if let Some(value) = value {
+                data_set.elements.insert(tag, value.to_vec());
+            }
+        }
+        let syntax = data_set.string(TRANSFER_SYNTAX_UID).ok_or(DicomError::Missing("TransferSyntaxUID"))?;
+        (syntax, cursor.pos)
+    } else {
+        // Old ACR-NEMA style files start right away with a group 0008 element.
+        if bytes.len() < 8 || u16::from_le_bytes([bytes[0], bytes[1]]) != 0x0008 {
+            return Ok(None);
+        }
+        (IMPLICIT_VR_LITTLE_ENDIAN.to_string(), 0)
+    };
+
+    let inflated;
+    let mut cursor = match transfer_syntax.as_str() {
+        IMPLICIT_VR_LITTLE_ENDIAN => Cursor { bytes, pos: start, explicit_vr: false, big_endian: false },
+        EXPLICIT_VR_LITTLE_ENDIAN => Cursor { bytes, pos: start, explicit_vr: true, big_endian: false },
+        EXPLICIT_VR_BIG_ENDIAN => Cursor { bytes, pos: start, explicit_vr: true, big_endian: true },
+        DEFLATED_EXPLICIT_VR_LITTLE_ENDIAN => {
+            let mut buffer = Vec::new();
+            DeflateDecoder::new(&bytes[start..]).read_to_end(&mut buffer).map_err(DicomError::Decompression)?;
+            inflated = buffer;
+            Cursor { bytes: &inflated, pos: 0, explicit_vr: true, big_endian: false }
+        }
+        other => return Err(DicomError::Unsupported(format!("transfer syntax {}", other))),
+    };
+    data_set.big_endian = cursor.big_endian;
+    read_elements(&mut cursor, &mut data_set, stop)?;
+    Ok(Some(data_set))
+}
@@ -0,0 +1,385 @@
+use crate::image::{Image, AnyImage, Metadata};
+
+use super::error::DicomError;
+use super::parser::{DataSet, Tag, PIXEL_DATA, parse};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bytemuck::Pod;
+use num_traits::NumCast;
+
+const SLICE_THICKNESS: Tag = 0x0018_0050;
+const SPACING_BETWEEN_SLICES: Tag = 0x0018_0088;
+const SERIES_INSTANCE_UID: Tag = 0x0020_000E;
+const INSTANCE_NUMBER: Tag = 0x0020_0013;
+const IMAGE_POSITION_PATIENT: Tag = 0x0020_0032;
+const IMAGE_ORIENTATION_PATIENT: Tag = 0x0020_0037;
+const SAMPLES_PER_PIXEL: Tag = 0x0028_0002;
+const NUMBER_OF_FRAMES: Tag = 0x0028_0008;
+const ROWS: Tag = 0x0028_0010;
+const COLUMNS: Tag = 0x0028_0011;
+const PIXEL_SPACING: Tag = 0x0028_0030;
+const BITS_ALLOCATED: Tag = 0x0028_0100;
+const BITS_STORED: Tag = 0x0028_0101;
+const PIXEL_REPRESENTATION: Tag = 0x0028_0103;
+const RESCALE_INTERCEPT: Tag = 0x0028_1052;
+const RESCALE_SLOPE: Tag = 0x0028_1053;
+
+// How much (relative) a gap between slices may exceed the smallest gap.
+const SPACING_TOLERANCE: f64 = 0.01;
+
+// Attributes copied into the image metadata, from the first slice.
+const METADATA_TAGS: &[(Tag, &str)] = &[
+    (0x0008_0020, "StudyDate"),
+    (0x0008_0060, "Modality"),
+    (0x0008_103E, "SeriesDescription"),
+    (0x0010_0010, "PatientName"),
+    (0x0010_0020, "PatientID"),
+    (0x0020_000D, "StudyInstanceUID"),
+    (SERIES_INSTANCE_UID, "SeriesInstanceUID"),
+];
+
+
+// What we need to know about a file to sort it into its series.
+#[derive(Debug, Clone)]
+struct Slice {
+    path: PathBuf,
+    instance_number: Option<f64>,
+    position: Option<[f64; 3]>,
+    orientation: Option<[f64; 6]>,
+}
+
+
+/// The files of one DICOM series, in slice order.
+#[derive(Debug, Clone)]
+pub struct DicomSeries {
+    pub series_instance_uid: String,
+    slices: Vec<Slice>,
+}
+
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn fixed<const N: usize>(values: Option<Vec<f64>>, attribute: &str) -> Result<Option<[f64; N]>, DicomError> {
+    values.map(|v| v.try_into().map_err(|v: Vec<f64>| DicomError::InvalidData(
+        format!("{} has {} values, expected {}", attribute, v.len(), N)
+    ))).transpose()
+}
+
+// Slice normal from ImageOrientationPatient, identity orientation if it is missing.
+fn normal(orientation: Option<[f64; 6]>) -> [f64; 3] {
+    let o = orientation.unwrap_or([1.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+    cross([o[0], o[1], o[2]], [o[3], o[4], o[5]])
+}
+
+
+// Series UID and sorting attributes of an image file, `None` if it is not
+// DICOM or has no image.
+fn read_slice(path: PathBuf, bytes: &[u8]) -> Result<Option<(String, Slice)>, DicomError> {
+    // Everything we need for sorting comes before the pixel data.
+    let Some(data_set) = parse(bytes, RESCALE_SLOPE)? else { return Ok(None) };
+    if data_set.u16(ROWS).is_none() {
+        return Ok(None);
+    }
+
+    let uid = data_set.string(SERIES_INSTANCE_UID).unwrap_or_default();
+    let slice = Slice {
+        path,
+        instance_number: data_set.number(INSTANCE_NUMBER)?,
+        position: fixed(data_set.numbers(IMAGE_POSITION_PATIENT)?, "ImagePositionPatient")?,
+        orientation: fixed(data_set.numbers(IMAGE_ORIENTATION_PATIENT)?, "ImageOrientationPatient")?,
+    };
+    Ok(Some((uid, slice)))
+}
+
+
+/// Find all image series in `dir` (not recursing into subdirectories).
+///
+/// Files that can not be read, files that are not DICOM, DICOM without an
+/// image (e.g. DICOMDIR) and files that can not be parsed (e.g. a
+/// compressed transfer syntax or a malformed data set) are skipped, the
+/// others are still grouped. Slices
+/// are sorted by ImagePositionPatient along the slice normal, or by
+/// InstanceNumber if positions are missing.
+pub fn read_dicom_series(dir: &str) -> Result<Vec<DicomSeries>, DicomError> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    paths.sort();
+
+    let mut series: Vec<DicomSeries> = Vec::new();
+    for path in paths.into_iter().filter(|p| p.is_file()) {
+        let Ok(bytes) = fs::read(&path) else { continue };
+        let Ok(Some((uid, slice))) = read_slice(path, &bytes) else { continue };
+        match series.iter_mut().find(|s| s.series_instance_uid == uid) {
+            Some(s) => s.slices.push(slice),
+            None => series.push(DicomSeries { series_instance_uid: uid, slices: vec![slice] }),
+        }
+    }
+
+    for s in &mut series {
+        s.sort_slices();
+    }
+    Ok(series)
+}
+
+
+/// Load the only image series in `dir`, see `read_dicom_series`.
+pub fn load_dicom_series(dir: &str) -> Result<Box<dyn AnyImage>, DicomError> {
+    let mut series = read_dicom_series(dir)?;
+    match series.len() {
+        0 => Err(DicomError::NoSeries(PathBuf::from(dir))),
+        1 => series.remove(0).load(),
+        _ => Err(DicomError::MultipleSeries(series.into_iter().map(|s| s.series_instance_uid).collect())),
+    }
+}
+
+
+// Pixel format shared by all slices of a series.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PixelFormat {
+    rows: u16,
+    columns: u16,
+    bits_allocated: u16,
+    bits_stored: u16,
+    signed: bool,
+}
+
+impl PixelFormat {
+    fn read(data_set: &DataSet) -> Result<Self, DicomError> {
+        let samples = data_set.u16(SAMPLES_PER_PIXEL).unwrap_or(1);
+        if samples != 1 {
+            return Err(DicomError::Unsupported(format!("{} samples per pixel", samples)));
+        }
+        let frames = data_set.number(NUMBER_OF_FRAMES)?.unwrap_or(1.0);
+        if frames > 1.0 {
+            return Err(DicomError::Unsupported(format!("multi-frame image with {} frames", frames)));
+        }
+
+        let bits_allocated = data_set.u16(BITS_ALLOCATED).ok_or(DicomError::Missing("BitsAllocated"))?;
+        if !matches!(bits_allocated, 8 | 16 | 32) {
+            return Err(DicomError::Unsupported(format!("{} bits allocated", bits_allocated)));
+        }
+        Ok(PixelFormat {
+            rows: data_set.u16(ROWS).ok_or(DicomError::Missing("Rows"))?,
+            columns: data_set.u16(COLUMNS).ok_or(DicomError::Missing("Columns"))?,
+            bits_allocated,
+            bits_stored: data_set.u16(BITS_STORED).unwrap_or(bits_allocated).clamp(1, bits_allocated),
+            signed: data_set.u16(PIXEL_REPRESENTATION).unwrap_or(0) == 1,
+        })
+    }
+
+    fn pixels(&self) -> usize {
+        self.rows as usize * self.columns as usize
+    }
+
+    // Smallest and largest stored value.
+    fn range(&self) -> (f64, f64) {
+        let bits = self.bits_stored as i32;
+        if self.signed {
+            (-(2f64.powi(bits - 1)), 2f64.powi(bits - 1) - 1.0)
+        } else {
+            (0.0, 2f64.powi(bits) - 1.0)
+        }
+    }
+
+    // Stored values of one slice, masked to BitsStored and sign extended.
+    fn decode(&self, data_set: &DataSet) -> Result<Vec<i64>, DicomError> {
+        let pixel_data = data_set.bytes(PIXEL_DATA).ok_or(DicomError::Missing("PixelData"))?;
+        let bytes = self.bits_allocated as usize / 8;
+        let expected = self.pixels() * bytes;
+        if pixel_data.len() < expected {
+            return Err(DicomError::Truncated { expected, found: pixel_data.len() });
+        }
+
+        let stored = self.bits_stored as u32;
+        let mask = if stored >= 32 { u32::MAX } else { (1u32 << stored) - 1 };
+        Ok(pixel_data[..expected].chunks_exact(bytes).map(|raw| {
+            let value = match (bytes, data_set.big_endian) {
+                (1, _) => raw[0] as u32,
+                (2, false) => u16::from_le_bytes([raw[0], raw[1]]) as u32,
+                (2, true) => u16::from_be_bytes([raw[0], raw[1]]) as u32,
+                (_, false) => u32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]),
+                (_, true) => u32::from_be_bytes([raw[0], raw[1], raw[2], raw[3]]),
+            } & mask;
+            if self.signed && value >> (stored - 1) & 1 == 1 {
+                value as i64 - (1i64 << stored)
+            } else {
+                value as i64
+            }
+        }).collect())
+    }
+}
+
+
+// Output voxel type, see `DicomSeries::load`.
+#[derive(Debug, Clone, Copy)]
+enum VoxelType {
+    U8, I8, U16, I16, U32, I32, F32, F64,
+}
+
+fn voxel_type(format: &PixelFormat, rescale: &[(f64, f64)]) -> VoxelType {
+    if rescale.iter().all(|&r| r == (1.0, 0.0)) {
+        return match (format.bits_allocated, format.signed) {
+            (8, false) => VoxelType::U8,
+            (8, true) => VoxelType::I8,
+            (16, false) => VoxelType::U16,
+            (16, true) => VoxelType::I16,
+            (_, false) => VoxelType::U32,
+            (_, true) => VoxelType::I32,
+        };
+    }
+    let integral = rescale.iter().all(|(slope, intercept)| slope.fract() == 0.0 && intercept.fract() == 0.0);
+    if !integral {
+        return if format.bits_allocated <= 16 { VoxelType::F32 } else { VoxelType::F64 };
+    }
+
+    let (low, high) = format.range();
+    let (min, max) = rescale.iter().fold((f64::MAX, f64::MIN), |(min, max), (slope, intercept)| {
+        let (a, b) = (low * slope + intercept, high * slope + intercept);
+        (min.min(a.min(b)), max.max(a.max(b)))
+    });
+    if min >= i16::MIN as f64 && max <= i16::MAX as f64 {
+        VoxelType::I16
+    } else if min >= i32::MIN as f64 && max <= i32::MAX as f64 {
+        VoxelType::I32
+    } else {
+        VoxelType::F64
+    }
+}
+
+
+impl DicomSeries {
+    pub fn len(&self) -> usize {
+        self.slices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slices.is_empty()
+    }
+
+    /// Files of the series in slice order.
+    pub fn files(&self) -> impl Iterator<Item = &Path> + '_ {
+        self.slices.iter().map(|s| s.path.as_path())
+    }
+
+    fn sort_slices(&mut self) {
+        let n = normal(self.slices[0].orientation);
+        if self.slices.iter().all(|s| s.position.is_some()) {
+            self.slices.sort_by(|a, b| {
+                dot(a.position.unwrap(), n).total_cmp(&dot(b.position.unwrap(), n))
+            });
+        } else if self.slices.iter().all(|s| s.instance_number.is_some()) {
+            self.slices.sort_by(|a, b| a.instance_number.unwrap().total_cmp(&b.instance_number.unwrap()));
+        }
+    }
+
+    /// Read all slices into one 3D image.
+    ///
+    /// Voxels keep their stored type unless a slice has a RescaleSlope or
+    /// RescaleIntercept. Integer rescaling gives `i16` (or `i32` if the
+    /// rescaled range needs it), anything else `f32` (`f64` for 32 bit data).
+    /// The origin is the first slice's ImagePositionPatient and the
+    /// direction rows are the row, column and slice normal directions.
+    /// Slices must be evenly spaced along the normal (within 1%), a series
+    /// with a missing slice or uneven gaps is an `InconsistentSeries` error.
+    pub fn load(&self) -> Result<Box<dyn AnyImage>, DicomError> {
+        let data_sets = self.slices.iter().map(|slice| {
+            let bytes = fs::read(&slice.path)?;
+            parse(&bytes, PIXEL_DATA)?.ok_or_else(|| DicomError::NotDicom(slice.path.clone()))
+        }).collect::<Result<Vec<_>, _>>()?;
+
+        let format = PixelFormat::read(&data_sets[0])?;
+        let mut rescale = Vec::with_capacity(data_sets.len());
+        for (data_set, slice) in data_sets.iter().zip(&self.slices) {
+            if PixelFormat::read(data_set)? != format {
+                return Err(DicomError::InconsistentSeries(format!(
+                    "{} has a different size or pixel format than the first slice", slice.path.display()
+                )));
+            }
+            rescale.push((
+                data_set.number(RESCALE_SLOPE)?.unwrap_or(1.0),
+                data_set.number(RESCALE_INTERCEPT)?.unwrap_or(0.0),
+            ));
+        }
+
+        match voxel_type(&format, &rescale) {
+            VoxelType::U8 => self.build::<u8>(&data_sets, &format, &rescale),
+            VoxelType::I8 => self.build::<i8>(&data_sets, &format, &rescale),
+            VoxelType::U16 => self.build::<u16>(&data_sets, &format, &rescale),
+            VoxelType::I16 => self.build::<i16>(&data_sets, &format, &rescale),
+            VoxelType::U32 => self.build::<u32>(&data_sets, &format, &rescale),
+            VoxelType::I32 => self.build::<i32>(&data_sets, &format, &rescale),
+            VoxelType::F32 => self.build::<f32>(&data_sets, &format, &rescale),
+            VoxelType::F64 => self.build::<f64>(&data_sets, &format, &rescale),
+        }
+    }
+
+    fn build<T>(&self, data_sets: &[DataSet], format: &PixelFormat, rescale: &[(f64, f64)]) -> Result<Box<dyn AnyImage>, DicomError>
+    where
+        T: Pod + NumCast + Copy + 'static,
+    {
+        let mut voxels: Vec<T> = Vec::with_capacity(format.pixels() * data_sets.len());
+        for (data_set, &(slope, intercept)) in data_sets.iter().zip(rescale) {
+            for stored in format.decode(data_set)? {
+                voxels.push(NumCast::from(stored as f64 * slope + intercept).unwrap());
+            }
+        }
+
+        let first = &data_sets[0];
+        let o = self.slices[0].orientation.unwrap_or([1.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+        let n = normal(self.slices[0].orientation);
+        let origin = self.slices[0].position.unwrap_or([0.0; 3]);
+
+        // PixelSpacing is (between rows, between columns), i.e. (y, x).
+        let pixel_spacing = first.numbers(PIXEL_SPACING)?.unwrap_or_else(|| vec![1.0, 1.0]);
+        let pixel_spacing: [f64; 2] = fixed(Some(pixel_spacing), "PixelSpacing")?.unwrap();
+        let positions: Option<Vec<f64>> = self.slices.iter().map(|s| s.position.map(|p| dot(p, n))).collect();
+        let slice_spacing = match positions {
+            Some(positions) if positions.len() > 1 => {
+                let gaps: Vec<f64> = positions.windows(2).map(|p| p[1] - p[0]).collect();
+                let closest = gaps.iter().copied().fold(f64::INFINITY, f64::min);
+                if closest <= 0.0 {
+                    return Err(DicomError::InconsistentSeries("slices share the same position".into()));
+                }
+                // A missing slice or uneven gaps would put part of the volume in the wrong place.
+                for (gap, slices) in gaps.iter().zip(self.slices.windows(2)) {
+                    if gap - closest > SPACING_TOLERANCE * closest {
+                        return Err(DicomError::InconsistentSeries(format!(
+                            "{} and {} are {} apart, other slices {}",
+                            slices[0].path.display(), slices[1].path.display(), gap, closest,
+                        )));
+                    }
+                }
+                (positions[positions.len() - 1] - positions[0]) / gaps.len() as f64
+            }
+            _ => first.number(SPACING_BETWEEN_SLICES)?
+                .or(first.number(SLICE_THICKNESS)?)
+                .unwrap_or(1.0),
+        };
+
+        let mut metadata = Metadata::new();
+        for &(tag, keyword) in METADATA_TAGS {
+            if let Some(value) = first.string(tag) {
+                metadata.insert(keyword, value);
+            }
+        }
+
+        Ok(Box::new(Image {
+            voxels,
+            width: format.columns as u32,
+            height: format.rows as u32,
+            depth: self.len() as u32,
+            spacing: [pixel_spacing[1], pixel_spacing[0], slice_spacing],
+            origin,
+            direction: [o[0], o[1], o[2], o[3], o[4], o[5], n[0], n[1], n[2]],
+            metadata,
+        }))
+    }
+}
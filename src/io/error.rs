@@ -0,0 +1,106 @@
+use std::path::PathBuf;
+
+use super::analyze::AnalyzeError;
+use super::dicom::DicomError;
+use super::meta_image::MetaImageError;
+use super::nifti::NiftiError;
+use super::npy::NpyError;
+use super::nrrd::NrrdError;
+use super::png::PngError;
+use super::tiff::TiffError;
+use super::vtk::VtkError;
+
+/// Error of `load`/`save`, wrapping the error of the format that was used.
+#[derive(Debug)]
+pub enum ImageIoError {
+    Io(std::io::Error),     // Generic I/O error (file issue)
+    UnknownFormat(PathBuf), // No registered format recognizes the file
+    MetaImage(MetaImageError),
+    Nifti(NiftiError),
+    Analyze(AnalyzeError),
+    Nrrd(NrrdError),
+    Npy(NpyError),
+    Dicom(DicomError),
+    Tiff(TiffError),
+    Png(PngError),
+    Vtk(VtkError),
+    Other(Box<dyn std::error::Error + Send + Sync>), // Errors of plug-in formats
+}
+
+impl std::fmt::Display for ImageIoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageIoError::Io(e) => write!(f, "I/O error: {}", e),
+            ImageIoError::UnknownFormat(path) => write!(f, "Unknown image format: {}", path.display()),
+            ImageIoError::MetaImage(e) => e.fmt(f),
+            ImageIoError::Nifti(e) => e.fmt(f),
+            ImageIoError::Analyze(e) => e.fmt(f),
+            ImageIoError::Nrrd(e) => e.fmt(f),
+            ImageIoError::Npy(e) => e.fmt(f),
+            ImageIoError::Dicom(e) => e.fmt(f),
+            ImageIoError::Tiff(e) => e.fmt(f),
+            ImageIoError::Png(e) => e.fmt(f),
+            ImageIoError::Vtk(e) => e.fmt(f),
+            ImageIoError::Other(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ImageIoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImageIoError::Io(e) => Some(e),
+            ImageIoError::UnknownFormat(_) => None,
+            ImageIoError::MetaImage(e) => Some(e),
+            ImageIoError::Nifti(e) => Some(e),
+            ImageIoError::Analyze(e) => Some(e),
+            ImageIoError::Nrrd(e) => Some(e),
+            ImageIoError::Npy(e) => Some(e),
+            ImageIoError::Dicom(e) => Some(e),
+            ImageIoError::Tiff(e) => Some(e),
+            ImageIoError::Png(e) => Some(e),
+            ImageIoError::Vtk(e) => Some(e),
+            ImageIoError::Other(e) => Some(e.as_ref()),
+        }
+    }
+}
+
+impl From<std::io::Error> for ImageIoError {
+    fn from(e: std::io::Error) -> Self { ImageIoError::Io(e) }
+}
+
+impl From<MetaImageError> for ImageIoError {
+    fn from(e: MetaImageError) -> Self { ImageIoError::MetaImage(e) }
+}
+
+impl From<NiftiError> for ImageIoError {
+    fn from(e: NiftiError) -> Self { ImageIoError::Nifti(e) }
+}
+
+impl From<AnalyzeError> for ImageIoError {
+    fn from(e: AnalyzeError) -> Self { ImageIoError::Analyze(e) }
+}
+
+impl From<NrrdError> for ImageIoError {
+    fn from(e: NrrdError) -> Self { ImageIoError::Nrrd(e) }
+}
+
+impl From<NpyError> for ImageIoError {
+    fn from(e: NpyError) -> Self { ImageIoError::Npy(e) }
+}
+
+impl From<DicomError> for ImageIoError {
+    fn from(e: DicomError) -> Self { ImageIoError::Dicom(e) }
+}
+
+impl From<TiffError> for ImageIoError {
+    fn from(e: TiffError) -> Self { ImageIoError::Tiff(e) }
+}
+
+impl From<PngError> for ImageIoError {
+    fn from(e: PngError) -> Self { ImageIoError::Png(e) }
+}
+
+impl From<VtkError> for ImageIoError {
+    fn from(e: VtkError) -> Self { ImageIoError::Vtk(e) }
+}
@@ -0,0 +1,366 @@
+use crate::image::AnyImage;
+
+use super::analyze::header::is_analyze;
+use super::analyze::load_analyze;
+use super::dicom::{load_dicom_series, read_dicom_series, DicomError};
+use super::error::ImageIoError;
+use super::meta_image::{load_meta_image, save_meta_image};
+use super::nifti::{load_nifti, save_nifti};
+use super::npy::{load_npy, load_npz, save_npy, save_npz};
+use super::nrrd::{load_nrrd, save_nrrd, NrrdEncoding};
+use super::png::{load_png, save_png};
+use super::tiff::{load_tiff, save_tiff, TiffCompression};
+use super::vtk::{load_vtk, save_vtk, VtkEncoding};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, RwLock};
+use flate2::read::GzDecoder;
+
+// Bytes handed to `ImageFormat::matches_magic`.
+const MAGIC_BYTES: usize = 1024;
+
+/// A file format `load` and `save` can dispatch to.
+///
+/// Implement this for in-house formats and add them with `register_format`.
+pub trait ImageFormat: Send + Sync {
+    /// Short name for messages, e.g. "NIfTI".
+    fn name(&self) -> &str;
+
+    /// File name extensions without the leading dot, e.g. `["nii", "nii.gz"]`.
+    fn extensions(&self) -> &[&str];
+
+    /// Whether a file starting with `header` is in this format. `header`
+    /// holds the first (up to) 1024 bytes of the file.
+    fn matches_magic(&self, header: &[u8]) -> bool {
+        let _ = header;
+        false
+    }
+
+    /// Whether `load` accepts directories, e.g. a folder of DICOM slices.
+    fn loads_directories(&self) -> bool {
+        false
+    }
+
+    fn load(&self, path: &str) -> Result<Box<dyn AnyImage>, ImageIoError>;
+
+    fn save(&self, image: &dyn AnyImage, path: &str) -> Result<(), ImageIoError> {
+        let _ = image;
+        Err(ImageIoError::Io(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            format!("{} files can not be written, {}", self.name(), path),
+        )))
+    }
+}
+
+
+struct MetaImageFormat;
+
+impl ImageFormat for MetaImageFormat {
+    fn name(&self) -> &str {
+        "MetaImage"
+    }
+    fn extensions(&self) -> &[&str] {
+        &["mha", "mhd"]
+    }
+    fn matches_magic(&self, header: &[u8]) -> bool {
+        // Plain text "Key = Value" lines, MetaIO writes ObjectType or NDims first.
+        let text = String::from_utf8_lossy(&header[..header.len().min(64)]);
+        let key = text.split('=').next().unwrap_or_default().trim();
+        matches!(key, "ObjectType" | "NDims" | "Comment" | "ObjectSubType")
+    }
+    fn load(&self, path: &str) -> Result<Box<dyn AnyImage>, ImageIoError> {
+        Ok(load_meta_image(path)?)
+    }
+    fn save(&self, image: &dyn AnyImage, path: &str) -> Result<(), ImageIoError> {
+        Ok(save_meta_image(image, path, false)?)
+    }
+}
+
+
+struct NiftiFormat;
+
+impl ImageFormat for NiftiFormat {
+    fn name(&self) -> &str {
+        "NIfTI"
+    }
+    fn extensions(&self) -> &[&str] {
+        &["nii", "nii.gz"]
+    }
+    fn matches_magic(&self, header: &[u8]) -> bool {
+        // Look into gzipped files, the header is small enough.
+        let mut decompressed = Vec::new();
+        let header = if header.starts_with(&[0x1f, 0x8b]) {
+            let _ = GzDecoder::new(header).read_to_end(&mut decompressed);
+            &decompressed[..]
+        } else {
+            header
+        };
+        header.get(344..348).is_some_and(|m| m == b"n+1\0" || m == b"ni1\0")
+            || header.get(4..8).is_some_and(|m| m == b"n+2\0" || m == b"ni2\0")
+    }
+    fn load(&self, path: &str) -> Result<Box<dyn AnyImage>, ImageIoError> {
+        Ok(load_nifti(path)?)
+    }
+    fn save(&self, image: &dyn AnyImage, path: &str) -> Result<(), ImageIoError> {
+        Ok(save_nifti(image, path)?)
+    }
+}
+
+
+struct AnalyzeFormat;
+
+impl ImageFormat for AnalyzeFormat {
+    fn name(&self) -> &str {
+        "Analyze 7.5"
+    }
+    fn extensions(&self) -> &[&str] {
+        &["hdr", "img"]
+    }
+    fn matches_magic(&self, header: &[u8]) -> bool {
+        is_analyze(header)
+    }
+    fn load(&self, path: &str) -> Result<Box<dyn AnyImage>, ImageIoError> {
+        Ok(load_analyze(path)?)
+    }
+}
+
+
+struct NrrdFormat;
+
+impl ImageFormat for NrrdFormat {
+    fn name(&self) -> &str {
+        "NRRD"
+    }
+    fn extensions(&self) -> &[&str] {
+        &["nrrd", "nhdr"]
+    }
+    fn matches_magic(&self, header: &[u8]) -> bool {
+        header.starts_with(b"NRRD000")
+    }
+    fn load(&self, path: &str) -> Result<Box<dyn AnyImage>, ImageIoError> {
+        Ok(load_nrrd(path)?)
+    }
+    fn save(&self, image: &dyn AnyImage, path: &str) -> Result<(), ImageIoError> {
+        Ok(save_nrrd(image, path, NrrdEncoding::Raw)?)
+    }
+}
+
+
+struct NpyFormat;
+
+impl ImageFormat for NpyFormat {
+    fn name(&self) -> &str {
+        "NumPy"
+    }
+    fn extensions(&self) -> &[&str] {
+        &["npy"]
+    }
+    fn matches_magic(&self, header: &[u8]) -> bool {
+        header.starts_with(b"\x93NUMPY")
+    }
+    fn load(&self, path: &str) -> Result<Box<dyn AnyImage>, ImageIoError> {
+        Ok(load_npy(path)?)
+    }
+    fn save(&self, image: &dyn AnyImage, path: &str) -> Result<(), ImageIoError> {
+        Ok(save_npy(image, path)?)
+    }
+}
+
+
+// Plain zip archives, only the extension tells them apart.
+struct NpzFormat;
+
+impl ImageFormat for NpzFormat {
+    fn name(&self) -> &str {
+        "NumPy archive"
+    }
+    fn extensions(&self) -> &[&str] {
+        &["npz"]
+    }
+    fn load(&self, path: &str) -> Result<Box<dyn AnyImage>, ImageIoError> {
+        Ok(load_npz(path)?)
+    }
+    fn save(&self, image: &dyn AnyImage, path: &str) -> Result<(), ImageIoError> {
+        Ok(save_npz(image, path, false)?)
+    }
+}
+
+
+struct TiffFormat;
+
+impl ImageFormat for TiffFormat {
+    fn name(&self) -> &str {
+        "TIFF"
+    }
+    fn extensions(&self) -> &[&str] {
+        &["tif", "tiff"]
+    }
+    fn matches_magic(&self, header: &[u8]) -> bool {
+        // Classic TIFF (42) or BigTIFF (43), either byte order.
+        matches!(header.get(..4), Some(b"II*\0" | b"MM\0*" | b"II+\0" | b"MM\0+"))
+    }
+    fn load(&self, path: &str) -> Result<Box<dyn AnyImage>, ImageIoError> {
+        Ok(load_tiff(path)?)
+    }
+    fn save(&self, image: &dyn AnyImage, path: &str) -> Result<(), ImageIoError> {
+        Ok(save_tiff(image, path, TiffCompression::None)?)
+    }
+}
+
+
+struct PngFormat;
+
+impl ImageFormat for PngFormat {
+    fn name(&self) -> &str {
+        "PNG"
+    }
+    fn extensions(&self) -> &[&str] {
+        &["png"]
+    }
+    fn matches_magic(&self, header: &[u8]) -> bool {
+        header.starts_with(b"\x89PNG\r\n\x1a\n")
+    }
+    fn load(&self, path: &str) -> Result<Box<dyn AnyImage>, ImageIoError> {
+        Ok(load_png(path)?)
+    }
+    fn save(&self, image: &dyn AnyImage, path: &str) -> Result<(), ImageIoError> {
+        Ok(save_png(image, path)?)
+    }
+}
+
+
+struct VtkFormat;
+
+impl ImageFormat for VtkFormat {
+    fn name(&self) -> &str {
+        "VTK"
+    }
+    fn extensions(&self) -> &[&str] {
+        &["vtk", "vti"]
+    }
+    fn matches_magic(&self, header: &[u8]) -> bool {
+        let text = String::from_utf8_lossy(header);
+        text.starts_with("# vtk DataFile") || text.contains("<VTKFile")
+    }
+    fn load(&self, path: &str) -> Result<Box<dyn AnyImage>, ImageIoError> {
+        Ok(load_vtk(path)?)
+    }
+    fn save(&self, image: &dyn AnyImage, path: &str) -> Result<(), ImageIoError> {
+        Ok(save_vtk(image, path, VtkEncoding::Binary)?)
+    }
+}
+
+
+struct DicomFormat;
+
+impl ImageFormat for DicomFormat {
+    fn name(&self) -> &str {
+        "DICOM"
+    }
+    fn extensions(&self) -> &[&str] {
+        &["dcm"]
+    }
+    fn matches_magic(&self, header: &[u8]) -> bool {
+        header.get(128..132) == Some(b"DICM")
+    }
+    fn loads_directories(&self) -> bool {
+        true
+    }
+    /// A directory loads its only series, a single file the series it belongs to.
+    fn load(&self, path: &str) -> Result<Box<dyn AnyImage>, ImageIoError> {
+        if Path::new(path).is_dir() {
+            return Ok(load_dicom_series(path)?);
+        }
+        let file = Path::new(path).canonicalize()?;
+        let dir = file.parent().unwrap_or(Path::new("."));
+        let series = read_dicom_series(&dir.to_string_lossy())?;
+        let series = series.into_iter()
+            .find(|s| s.files().any(|f| f.canonicalize().is_ok_and(|f| f == file)))
+            .ok_or_else(|| DicomError::NoSeries(PathBuf::from(path)))?;
+        Ok(series.load()?)
+    }
+}
+
+
+fn registry() -> &'static RwLock<Vec<Arc<dyn ImageFormat>>> {
+    static REGISTRY: OnceLock<RwLock<Vec<Arc<dyn ImageFormat>>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(vec![
+        Arc::new(MetaImageFormat),
+        Arc::new(NiftiFormat),
+        Arc::new(AnalyzeFormat),
+        Arc::new(NrrdFormat),
+        Arc::new(NpyFormat),
+        Arc::new(NpzFormat),
+        Arc::new(TiffFormat),
+        Arc::new(PngFormat),
+        Arc::new(VtkFormat),
+        Arc::new(DicomFormat),
+    ]))
+}
+
+
+/// Make `format` available to `load` and `save`. Registered formats are
+/// tried before the built-in ones, the latest registration first.
+pub fn register_format(format: impl ImageFormat + 'static) {
+    registry().write().unwrap().insert(0, Arc::new(format));
+}
+
+
+/// All formats in the order `load` and `save` try them.
+pub fn formats() -> Vec<Arc<dyn ImageFormat>> {
+    registry().read().unwrap().clone()
+}
+
+
+// Length of the longest extension of `format` that `path` ends with.
+fn extension_match(format: &dyn ImageFormat, path: &str) -> Option<usize> {
+    let name = Path::new(path).file_name()?.to_string_lossy().to_lowercase();
+    format.extensions().iter()
+        .filter(|ext| name.ends_with(&format!(".{}", ext.to_lowercase())))
+        .map(|ext| ext.len())
+        .max()
+}
+
+
+fn by_extension(formats: &[Arc<dyn ImageFormat>], path: &str) -> Option<Arc<dyn ImageFormat>> {
+    formats.iter()
+        .filter_map(|f| extension_match(f.as_ref(), path).map(|len| (len, f)))
+        // Longest match wins, e.g. "nii.gz" over a plain "gz" format, then registration order.
+        .fold(None, |best: Option<(usize, &Arc<dyn ImageFormat>)>, (len, f)| match best {
+            Some((best_len, _)) if best_len >= len => best,
+            _ => Some((len, f)),
+        })
+        .map(|(_, f)| f.clone())
+}
+
+
+/// Load an image in any registered format.
+///
+/// The format is detected from the first bytes of the file and, if no
+/// format recognizes those, from the file name extension. Directories
+/// go to formats that load them, i.e. DICOM series.
+pub fn load(path: &str) -> Result<Box<dyn AnyImage>, ImageIoError> {
+    let formats = formats();
+    if Path::new(path).is_dir() {
+        let format = formats.iter().find(|f| f.loads_directories())
+            .ok_or_else(|| ImageIoError::UnknownFormat(PathBuf::from(path)))?;
+        return format.load(path);
+    }
+
+    let mut header = Vec::with_capacity(MAGIC_BYTES);
+    File::open(path)?.take(MAGIC_BYTES as u64).read_to_end(&mut header)?;
+    let format = formats.iter().find(|f| f.matches_magic(&header)).cloned()
+        .or_else(|| by_extension(&formats, path))
+        .ok_or_else(|| ImageIoError::UnknownFormat(PathBuf::from(path)))?;
+    format.load(path)
+}
+
+
+/// Save an image in the format matching the file name extension, with
+/// that format's default options.
+pub fn save(image: &dyn AnyImage, path: &str) -> Result<(), ImageIoError> {
+    let format = by_extension(&formats(), path)
+        .ok_or_else(|| ImageIoError::UnknownFormat(PathBuf::from(path)))?;
+    format.save(image, path)
+}
@@ -0,0 +1,65 @@
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum MetaImageError {
+    Missing(&'static str),  // Missing key-values that we need
+    Io(std::io::Error),     // Generic I/O error (file issue)
+    UnsupportedElementType(String),
+    Unsupported(String),
+    InvalidValue { key: String, value: String, line: usize },
+    ValueCount { key: String, expected: usize, found: usize, line: usize },
+    Truncated { expected: usize, found: usize },
+    MissingValues { expected: usize, found: usize },
+    DecompressedSize { expected: usize, found: usize },
+    Decompression(std::io::Error),
+    MissingDataFile(PathBuf),
+    InvalidRegion { start: Vec<u32>, size: Vec<u32>, dims: Vec<u32> },
+    TooLarge { dims: Vec<u32> },  // DimSize overflows the address space
+}
+
+impl std::fmt::Display for MetaImageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MetaImageError::Missing(key) => write!(f, "Missing header key: {}", key),
+            MetaImageError::Io(e) => write!(f, "I/O error: {}", e),
+            MetaImageError::UnsupportedElementType(e) => write!(f, "Unsupported element type: {}", e),
+            MetaImageError::Unsupported(what) => write!(f, "Unsupported MetaImage: {}", what),
+            MetaImageError::InvalidValue { key, value, line } => {
+                write!(f, "Invalid value for {} on line {}: '{}'", key, line, value)
+            }
+            MetaImageError::ValueCount { key, expected, found, line } => {
+                write!(f, "Expected {} values for {} on line {}, found {}", expected, key, line, found)
+            }
+            MetaImageError::Truncated { expected, found } => {
+                write!(f, "Truncated voxel data: expected {} bytes, found {}", expected, found)
+            }
+            MetaImageError::MissingValues { expected, found } => {
+                write!(f, "ASCII voxel data has {} values, expected {}", found, expected)
+            }
+            MetaImageError::DecompressedSize { expected, found } => {
+                write!(f, "Decompressed voxel data is {} bytes, DimSize and ElementType need {}", found, expected)
+            }
+            MetaImageError::Decompression(e) => write!(f, "Failed to decompress voxel data: {}", e),
+            MetaImageError::MissingDataFile(path) => {
+                write!(f, "Element data file not found: {}", path.display())
+            }
+            MetaImageError::InvalidRegion { start, size, dims } => {
+                write!(f, "Region starting at {:?} with size {:?} does not fit image of size {:?}", start, size, dims)
+            }
+            MetaImageError::TooLarge { dims } => write!(f, "Image of size {:?} is too large to load", dims),
+        }
+    }
+}
+
+impl std::error::Error for MetaImageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MetaImageError::Io(e) | MetaImageError::Decompression(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for MetaImageError {
+    fn from(e: std::io::Error) -> Self { MetaImageError::Io(e) }
+}
@@ -1,5 +1,10 @@
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::str::FromStr;
+
+use crate::image::Metadata;
+
+use super::error::MetaImageError;
 
 // Raw header for parsing
 #[derive(Debug, Default)]
@@ -9,93 +14,199 @@ struct RawHeader {
     binary_data: Option<bool>,
     binary_data_byte_order_msb: Option<bool>,
     compressed_data: Option<bool>,
-    transform_matrix: Option<[f64; 9]>,
-    offset: Option<[f64; 3]>,
-    center_of_rotation: Option<[f64; 3]>,
-    anatomical_orientation: Option<String>,
-    element_spacing: Option<[f64; 3]>,
-    dim_size: Option<[u32; 3]>,
+    compressed_data_size: Option<u64>,
+    header_size: Option<i64>,
+    transform_matrix: Option<Vec<f64>>,
+    offset: Option<Vec<f64>>,
+    element_spacing: Option<Vec<f64>>,
+    dim_size: Option<Vec<u32>>,
+    element_number_of_channels: Option<usize>,
     element_type: Option<String>,
     element_data_file: Option<String>,
+    element_data_files: Vec<String>,
+
+    // Everything else, e.g. AnatomicalOrientation or custom keys, in file order.
+    metadata: Metadata,
 
     // This one is not part of the Standard but we need it for .mha files.
     data_offset: Option<u64>,
+    data_line: usize,
 }
 
 // filled header for image bytes retrieval
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(super) struct Header {
+    pub binary_data: bool,
+    pub binary_data_byte_order_msb: bool,
     pub compressed_data: bool,
-    pub transform_matrix: [f64; 9],
-    pub offset: [f64; 3],
-    pub element_spacing: [f64; 3],
-    pub dim_size: [u32; 3],
+    pub compressed_data_size: Option<u64>,
+    // Bytes to skip in the data file, -1 means the data is at the end of the file.
+    pub header_size: Option<i64>,
+    pub n_dims: usize,
+    pub transform_matrix: Vec<f64>,
+    pub offset: Vec<f64>,
+    pub element_spacing: Vec<f64>,
+    pub dim_size: Vec<u32>,
+    pub element_number_of_channels: usize,
     pub element_type: String,
     pub element_data_file: String,
+    // Data files relative to the header, more than one for LIST or a pattern, empty for LOCAL.
+    pub element_data_files: Vec<String>,
     pub data_offset: u64,
+    // Line the voxel data starts on, counting the header lines for LOCAL data.
+    pub data_line: usize,
+    pub metadata: Metadata,
 }
 
-#[derive(Debug)]
-pub(super) enum HeaderError {
-    Missing(&'static str),  // Missing key-values that we need
-    Parse(std::io::Error),  // Generic Parsing error (file issue)
-    UnsupportedElementType(String),
+impl TryFrom<RawHeader> for Header {
+    type Error = MetaImageError;
+
+    fn try_from(r: RawHeader) -> Result<Self, Self::Error> {
+        Ok(Header {
+            binary_data: r.binary_data.unwrap_or(true),
+            // Little-endian unless stated otherwise.
+            binary_data_byte_order_msb: r.binary_data_byte_order_msb.unwrap_or(false),
+            compressed_data: r.compressed_data.ok_or(MetaImageError::Missing("CompressedData"))?,
+            compressed_data_size: r.compressed_data_size,
+            header_size: r.header_size,
+            n_dims: r.n_dims.ok_or(MetaImageError::Missing("NDims"))? as usize,
+            transform_matrix: r.transform_matrix.ok_or(MetaImageError::Missing("TransformMatrix"))?,
+            offset: r.offset.ok_or(MetaImageError::Missing("Offset"))?,
+            element_spacing: r.element_spacing.ok_or(MetaImageError::Missing("ElementSpacing"))?,
+            dim_size: r.dim_size.ok_or(MetaImageError::Missing("DimSize"))?,
+            element_number_of_channels: r.element_number_of_channels.unwrap_or(1),
+            element_type: r.element_type.ok_or(MetaImageError::Missing("ElementType"))?,
+            element_data_file: r.element_data_file.ok_or(MetaImageError::Missing("ElementDataFile"))?,
+            element_data_files: r.element_data_files,
+            data_offset: r.data_offset.ok_or(MetaImageError::Missing("Data Offset? weird!"))?,
+            data_line: r.data_line,
+            metadata: r.metadata,
+        })
+    }
 }
 
-impl std::fmt::Display for HeaderError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            HeaderError::Missing(key) => write!(f, "Missing header key: {}", key),
-            HeaderError::Parse(e) => write!(f, "Parse error: {}", e),
-            HeaderError::UnsupportedElementType(e) => write!(f, "Unsupported element type: {}", e),
-        }
+impl Header {
+    /// Number of values in the voxel data, all channels included.
+    pub fn value_count(&self) -> Result<usize, MetaImageError> {
+        self.dim_size.iter()
+            .try_fold(self.element_number_of_channels, |count, &n| count.checked_mul(n as usize))
+            .ok_or_else(|| MetaImageError::TooLarge { dims: self.dim_size.clone() })
+    }
+
+    /// Size of the voxel data in bytes, for elements of `element_bytes`.
+    pub fn data_bytes(&self, element_bytes: usize) -> Result<usize, MetaImageError> {
+        self.value_count()?
+            .checked_mul(element_bytes)
+            .ok_or_else(|| MetaImageError::TooLarge { dims: self.dim_size.clone() })
     }
 }
-impl std::error::Error for HeaderError {}
 
 
-impl From<std::io::Error> for HeaderError {
-    fn from(e: std::io::Error) -> Self { HeaderError::Parse(e) }
+fn parse_bool(key: &str, value: &str, line: usize) -> Result<bool, MetaImageError> {
+    match value.to_lowercase().as_str() {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(invalid_value(key, value, line)),
+    }
 }
 
-impl TryFrom<RawHeader> for Header {
-    type Error = HeaderError;
 
-    fn try_from(r: RawHeader) -> Result<Self, Self::Error> {
-        Ok(Header {
-            compressed_data: r.compressed_data.ok_or(HeaderError::Missing("CompressedData"))?,
-            transform_matrix: r.transform_matrix.ok_or(HeaderError::Missing("TransformMatrix"))?,
-            offset: r.offset.ok_or(HeaderError::Missing("Offset"))?,
-            element_spacing: r.element_spacing.ok_or(HeaderError::Missing("ElementSpacing"))?,
-            dim_size: r.dim_size.ok_or(HeaderError::Missing("DimSize"))?,
-            element_type: r.element_type.ok_or(HeaderError::Missing("ElementType"))?,
-            element_data_file: r.element_data_file.ok_or(HeaderError::Missing("ElementDataFile"))?,
-            data_offset: r.data_offset.ok_or(HeaderError::Missing("Data Offset? weird!"))?,
-        })
+fn parse_value<T: FromStr>(key: &str, value: &str, line: usize) -> Result<T, MetaImageError> {
+    value.parse().map_err(|_| invalid_value(key, value, line))
+}
+
+
+// Parse exactly `count` whitespace separated values, e.g. "DimSize = 50 50 50".
+fn parse_list<T: FromStr>(key: &str, value: &str, line: usize, count: usize) -> Result<Vec<T>, MetaImageError> {
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    if parts.len() != count {
+        return Err(MetaImageError::ValueCount {
+            key: key.to_string(),
+            expected: count,
+            found: parts.len(),
+            line,
+        });
     }
+    parts.into_iter().map(|part| parse_value(key, part, line)).collect()
 }
 
 
-fn parse_bool(value: &str) -> Option<bool> {
-    match value.to_lowercase().as_str() {
-        "true" => Some(true),
-        "false" => Some(false),
-        _ => None,
+fn is_list(value: &str) -> bool {
+    value.split_whitespace().next().is_some_and(|first| first.eq_ignore_ascii_case("LIST"))
+}
+
+
+// Either a single file name or a printf-style pattern such as "slice%03d.raw 1 120 1"
+// (pattern, first index, last index, optional step).
+fn parse_data_files(key: &str, value: &str, line: usize) -> Result<Vec<String>, MetaImageError> {
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    if parts.len() < 3 || parts.len() > 4 || !parts[0].contains('%') {
+        return Ok(vec![value.to_string()]);
+    }
+
+    let first: i64 = parse_value(key, parts[1], line)?;
+    let last: i64 = parse_value(key, parts[2], line)?;
+    let step: i64 = match parts.get(3) {
+        Some(step) => parse_value(key, step, line)?,
+        None => 1,
+    };
+    if step <= 0 || last < first {
+        return Err(invalid_value(key, value, line));
+    }
+
+    (first..=last)
+        .step_by(step as usize)
+        .map(|i| format_pattern(parts[0], i).ok_or_else(|| invalid_value(key, value, line)))
+        .collect()
+}
+
+
+// Substitute `index` for the single %d, %5d or %05d in `pattern`.
+pub(super) fn format_pattern(pattern: &str, index: i64) -> Option<String> {
+    let (prefix, rest) = pattern.split_once('%')?;
+    let end = rest.find('d')?;
+    let (spec, suffix) = (&rest[..end], &rest[end + 1..]);
+    if !spec.chars().all(|c| c.is_ascii_digit()) {
+        return None;
     }
+
+    let width: usize = if spec.is_empty() { 0 } else { spec.parse().ok()? };
+    let number = if spec.starts_with('0') {
+        format!("{:0width$}", index, width = width)
+    } else {
+        format!("{:width$}", index, width = width)
+    };
+    Some(format!("{}{}{}", prefix, number, suffix))
 }
 
 
-pub(super) fn parse_header(filename: &str) -> Result<Header, HeaderError> {
-    let mut raw = RawHeader::default();
+// NDims precedes every per-dimension key, we need it to validate their value counts.
+fn n_dims(raw: &RawHeader) -> Result<usize, MetaImageError> {
+    match raw.n_dims {
+        Some(n) if n > 0 => Ok(n as usize),
+        _ => Err(MetaImageError::Missing("NDims")),
+    }
+}
+
+
+fn invalid_value(key: &str, value: &str, line: usize) -> MetaImageError {
+    MetaImageError::InvalidValue { key: key.to_string(), value: value.to_string(), line }
+}
+
+
+pub(super) fn parse_header(filename: &str) -> Result<Header, MetaImageError> {
+    let mut raw = RawHeader { data_line: 1, ..RawHeader::default() };
     let mut data_offset: u64 = 0;
 
     let file = File::open(filename)?;
     let mut reader = BufReader::new(file);
     let mut line_buffer = String::new();
+    let mut line: usize = 0;
 
     loop {
         line_buffer.clear();
         let bytes_this_line = reader.read_line(&mut line_buffer)?;
+        line += 1;
 
         if let Some((key, value)) = line_buffer.trim().split_once('=') {
             let key = key.trim();
@@ -103,42 +214,31 @@ pub(super) fn parse_header(filename: &str) -> Result<Header, HeaderError> {
 
             match key {
                 "ObjectType" => raw.object_type = Some(value.to_string()),
-                "NDims" => raw.n_dims = value.parse().ok(),
-                "BinaryData" => raw.binary_data = parse_bool(value),
-                "BinaryDataByteOrderMSB" => raw.binary_data_byte_order_msb = parse_bool(value),
-                "CompressedData" => raw.compressed_data = parse_bool(value),
-                "TransformMatrix" => {
-                    let mut iter = value.split_whitespace().map(|s| s.parse::<f64>().unwrap());
-                    raw.transform_matrix = Some([
-                        iter.next().unwrap(), iter.next().unwrap(), iter.next().unwrap(),
-                        iter.next().unwrap(), iter.next().unwrap(), iter.next().unwrap(),
-                        iter.next().unwrap(), iter.next().unwrap(), iter.next().unwrap(),
-                    ]);
-                },
-                "Offset" => {
-                    let mut iter = value.split_whitespace().map(|s| s.parse::<f64>().unwrap());
-                    raw.offset = Some([
-                        iter.next().unwrap(), iter.next().unwrap(), iter.next().unwrap(),
-                    ]);
-                },
-                "CenterOfRotation" => {
-                    let mut iter = value.split_whitespace().map(|s| s.parse::<f64>().unwrap());
-                    raw.center_of_rotation = Some([
-                        iter.next().unwrap(), iter.next().unwrap(), iter.next().unwrap(),
-                    ]);
+                "NDims" => raw.n_dims = Some(parse_value(key, value, line)?),
+                "BinaryData" => raw.binary_data = Some(parse_bool(key, value, line)?),
+                "BinaryDataByteOrderMSB" | "ElementByteOrderMSB" => raw.binary_data_byte_order_msb = Some(parse_bool(key, value, line)?),
+                "CompressedData" => raw.compressed_data = Some(parse_bool(key, value, line)?),
+                "CompressedDataSize" => raw.compressed_data_size = Some(parse_value(key, value, line)?),
+                "HeaderSize" => {
+                    let header_size: i64 = parse_value(key, value, line)?;
+                    if header_size < -1 {
+                        return Err(invalid_value(key, value, line));
+                    }
+                    raw.header_size = Some(header_size);
                 },
-                "AnatomicalOrientation" => raw.anatomical_orientation = Some(value.to_string()),
-                "ElementSpacing" => {
-                    let mut iter = value.split_whitespace().map(|s| s.parse::<f64>().unwrap());
-                    raw.element_spacing = Some([
-                        iter.next().unwrap(), iter.next().unwrap(), iter.next().unwrap(),
-                    ]);
+                "TransformMatrix" => {
+                    let n = n_dims(&raw)?;
+                    raw.transform_matrix = Some(parse_list(key, value, line, n * n)?);
                 },
-                "DimSize" => {
-                    let mut iter = value.split_whitespace().map(|s| s.parse::<u32>().unwrap());
-                    raw.dim_size = Some([
-                        iter.next().unwrap(), iter.next().unwrap(), iter.next().unwrap(),
-                    ]);
+                "Offset" => raw.offset = Some(parse_list(key, value, line, n_dims(&raw)?)?),
+                "ElementSpacing" => raw.element_spacing = Some(parse_list(key, value, line, n_dims(&raw)?)?),
+                "DimSize" => raw.dim_size = Some(parse_list(key, value, line, n_dims(&raw)?)?),
+                "ElementNumberOfChannels" => {
+                    let channels: usize = parse_value(key, value, line)?;
+                    if channels == 0 {
+                        return Err(invalid_value(key, value, line));
+                    }
+                    raw.element_number_of_channels = Some(channels);
                 },
                 "ElementType" => raw.element_type = Some(value.to_string()),
                 "ElementDataFile" => {
@@ -148,11 +248,22 @@ pub(super) fn parse_header(filename: &str) -> Result<Header, HeaderError> {
                     if value.eq_ignore_ascii_case("LOCAL") {
                         // account for this header line’s length before breaking
                         data_offset += bytes_this_line as u64;
+                        raw.data_line = line + 1;
+                    } else if is_list(value) {
+                        // "LIST [2D]": one file name per remaining line.
+                        raw.element_data_files = reader
+                            .lines()
+                            .map(|l| l.map(|l| l.trim().to_string()))
+                            .filter(|l| !matches!(l, Ok(l) if l.is_empty()))
+                            .collect::<Result<_, _>>()?;
+                    } else {
+                        raw.element_data_files = parse_data_files(key, value, line)?;
                     }
                     break;
                 }
                 _ => {
-                    // Unknown key. Shouldn't happen?
+                    // Auxiliary or custom key, keep it so it can be written back.
+                    raw.metadata.insert(key, value);
                 }
             }
             data_offset += bytes_this_line as u64;
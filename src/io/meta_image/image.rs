@@ -1,18 +1,24 @@
-use crate::image::{Image, AnyImage};
+use crate::image::{Image, NdImage, VectorImage, AnyImage, Metadata};
+use crate::image::dyn_image::{first_pixel_type, ImageParts};
 
-use super::header::{HeaderError, parse_header};
+use super::error::MetaImageError;
+use super::header::{Header, format_pattern, parse_header};
 use std::fs::File;
-use std::io::{Read, Seek, SeekFrom, BufWriter, Write};
-use std::path::Path;
-use flate2::read::ZlibDecoder;
+use std::io::{BufRead, Read, Seek, SeekFrom, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use flate2::bufread::{DeflateDecoder, MultiGzDecoder, ZlibDecoder};
 use flate2::write::ZlibEncoder;
 use flate2::Compression;
 use std::any::TypeId;
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::str::FromStr;
 
-use bytemuck::{cast_slice, Pod};
+use bytemuck::Pod;
+use num_traits::NumCast;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum ElementType { // TODO: bool support?
+pub(super) enum ElementType {
     I8, U8,
     I16, U16,
     I32, U32, F32,
@@ -20,28 +26,35 @@ enum ElementType { // TODO: bool support?
 }
 
 impl std::str::FromStr for ElementType {
-    type Err = HeaderError;
+    type Err = MetaImageError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         use ElementType::*;
-        match s {
+        // MET_*_ARRAY types are stored like their scalar counterparts, the
+        // number of components comes from ElementNumberOfChannels.
+        match s.strip_suffix("_ARRAY").unwrap_or(s) {
+            // MetaIO stores bools as one byte per voxel, they load as u8 0/1.
+            "MET_BOOL" => Ok(U8),
             "MET_UCHAR"  => Ok(U8),
-            "MET_CHAR"   => Ok(I8),
+            "MET_CHAR" | "MET_ASCII_CHAR" => Ok(I8),
             "MET_USHORT" => Ok(U16),
             "MET_SHORT"  => Ok(I16),
             "MET_UINT" => Ok(U32),
             "MET_INT" => Ok(I32),
-            "MET_FLOAT"  => Ok(F32),
+            // MetaIO's long is 4 bytes wide on every platform.
+            "MET_ULONG" => Ok(U32),
+            "MET_LONG" => Ok(I32),
+            "MET_FLOAT" | "MET_FLOAT_MATRIX" => Ok(F32),
             "MET_ULONG_LONG" => Ok(U64),
             "MET_LONG_LONG" => Ok(I64),
             "MET_DOUBLE" => Ok(F64),
-            _ => Err(HeaderError::UnsupportedElementType(s.into())),
+            _ => Err(MetaImageError::UnsupportedElementType(s.into())),
         }
     }
 }
 
 impl ElementType {
-    fn bytes(self) -> usize {
+    pub(super) fn bytes(self) -> usize {
         use ElementType::*;
         match self {
             U8 | I8 => 1,
@@ -52,189 +65,268 @@ impl ElementType {
     }
 }
 
+// Element bytes need swapping when the file's byte order differs from ours.
+fn needs_swap(byte_order_msb: bool) -> bool {
+    byte_order_msb != cfg!(target_endian = "big")
+}
+
+
 // Read raw byte vector and covert to type T.
-fn bytes_to_vec<T: Pod>(raw: Vec<u8>) -> Result<Vec<T>, &'static str> {
-    if raw.len() % std::mem::size_of::<T>() != 0 {
-        return Err("byte count not divisible by element size");
+pub(crate) fn bytes_to_vec<T: Pod>(mut raw: Vec<u8>, byte_order_msb: bool) -> Vec<T> {
+    let size = std::mem::size_of::<T>();
+    if size > 1 && needs_swap(byte_order_msb) {
+        raw.chunks_exact_mut(size).for_each(|element| element.reverse());
+    }
+
+    // Unaligned reads, a Vec<u8> is not guaranteed to be aligned for T.
+    raw.chunks_exact(size)
+        .map(bytemuck::pod_read_unaligned)
+        .collect()
+}
+
+
+// Inverse of `bytes_to_vec`: voxels as bytes in the requested byte order.
+pub(crate) fn vec_to_bytes<T: Pod>(voxels: &[T], byte_order_msb: bool) -> Cow<'_, [u8]> {
+    let size = std::mem::size_of::<T>();
+    let bytes: &[u8] = bytemuck::cast_slice(voxels);
+    if size > 1 && needs_swap(byte_order_msb) {
+        let mut swapped = bytes.to_vec();
+        swapped.chunks_exact_mut(size).for_each(|element| element.reverse());
+        Cow::Owned(swapped)
+    } else {
+        Cow::Borrowed(bytes)
+    }
+}
+
+
+// Parse `BinaryData = False` voxel data, whitespace separated values.
+// `first_line` is the line the data starts on, for error messages; lines
+// of multi-file data are counted through all files.
+fn ascii_to_vec<T>(raw: &[u8], count: usize, first_line: usize) -> Result<Vec<T>, MetaImageError>
+where
+    T: NumCast + FromStr,
+{
+    let text = String::from_utf8_lossy(raw);
+    let voxels = text
+        .lines()
+        .enumerate()
+        .flat_map(|(i, text_line)| text_line.split_whitespace().map(move |token| (first_line + i, token)))
+        .take(count)
+        .map(|(line, token)| {
+            // Integer types may still be written as "1.0" by some tools.
+            token.parse::<T>().ok()
+                .or_else(|| token.parse::<f64>().ok().and_then(NumCast::from))
+                .ok_or_else(|| MetaImageError::InvalidValue {
+                    key: "ElementData".to_string(),
+                    value: token.to_string(),
+                    line,
+                })
+        })
+        .collect::<Result<Vec<T>, _>>()?;
+
+    if voxels.len() < count {
+        return Err(MetaImageError::MissingValues { expected: count, found: voxels.len() });
+    }
+    Ok(voxels)
+}
+
+
+fn build_image<T>(buffer: Vec<u8>, header: &Header) -> Result<Box<dyn AnyImage>, MetaImageError>
+where
+    T: Pod + NumCast + FromStr + Copy + 'static,
+{
+    let voxels = if header.binary_data {
+        bytes_to_vec(buffer, header.binary_data_byte_order_msb)
+    } else {
+        ascii_to_vec(&buffer, header.value_count()?, header.data_line)?
+    };
+
+    let image = NdImage::<T> {
+        voxels,
+        size: header.dim_size.clone(),
+        spacing: header.element_spacing.clone(),
+        origin: header.offset.clone(),
+        direction: header.transform_matrix.clone(),
+        metadata: header.metadata.clone(),
+    };
+
+    if header.element_number_of_channels > 1 {
+        return build_vector_image(image, header.element_number_of_channels as u32);
+    }
+
+    // Plain 3D volumes keep loading as `Image<T>`, anything else stays N-dimensional.
+    if header.n_dims != 3 {
+        return Ok(Box::new(image));
+    }
+    match Image::try_from(image) {
+        Ok(image) => Ok(Box::new(image)),
+        Err(image) => Ok(Box::new(image)),
     }
-    let slice_t: &[T] = cast_slice(&raw);
-    Ok(slice_t.to_vec())
 }
 
 
-pub fn load_meta_image(filename: &str) -> Box<dyn AnyImage> {
-    let header = parse_header(filename).expect("Invalid Meta Image");
+// `image.voxels` holds interleaved channels here, only its (padded) geometry is used.
+fn build_vector_image<T>(image: NdImage<T>, channels: u32) -> Result<Box<dyn AnyImage>, MetaImageError>
+where
+    T: Pod + NumCast + FromStr + Copy + 'static,
+{
+    let ndims = image.ndims();
+    let image = Image::try_from(image).map_err(|_| MetaImageError::Unsupported(
+        format!("{} channels in a {}D image, vector images are at most 3D", channels, ndims)
+    ))?;
+    Ok(Box::new(VectorImage {
+        voxels: image.voxels,
+        channels,
+        width: image.width,
+        height: image.height,
+        depth: image.depth,
+        spacing: image.spacing,
+        origin: image.origin,
+        direction: image.direction,
+        metadata: image.metadata,
+    }))
+}
 
-    let etype: ElementType = header.element_type.parse().expect("Unsupported Element type!");
-    let voxel_count: usize = header.dim_size.iter().map(|&v| v as usize).product();
-    let total_bytes = etype.bytes() * voxel_count;
-    let mut buffer = vec![0u8; total_bytes];
 
+// Resolve the files holding the voxel bytes and the offset at which they start.
+// `total_bytes` is the uncompressed size of all voxel data, used for HeaderSize = -1.
+fn data_locations(filename: &str, header: &Header, total_bytes: usize) -> Result<Vec<(PathBuf, u64)>, MetaImageError> {
     // EDF LOCAL implies next filepath bytes is data, otherwise data is
-    // found in a different file pointed to by EDF.
-    let data_filename: String;
-    let data_offset: u64;
-    if header.element_data_file == "LOCAL" {
-        data_offset = header.data_offset;
-        data_filename = filename.to_string();
+    // found in one or more different files pointed to by EDF.
+    let local = header.element_data_file.eq_ignore_ascii_case("LOCAL");
+    let data_paths = if local {
+        vec![PathBuf::from(filename)]
     } else {
-        data_offset = 0;
-        let base_path = Path::new(filename)
-            .parent()
-            .expect("Failed to get parent directory of filename");
-        data_filename = base_path.join(&header.element_data_file).to_str().unwrap().to_string();
+        let base_path = Path::new(filename).parent().unwrap_or(Path::new(""));
+        header.element_data_files.iter().map(|data_file| {
+            let data_path = base_path.join(data_file);
+            if !data_path.is_file() {
+                return Err(MetaImageError::MissingDataFile(data_path));
+            }
+            Ok(data_path)
+        }).collect::<Result<Vec<_>, _>>()?
+    };
+
+    let files = data_paths.len() as u64;
+    data_paths.into_iter().map(|data_path| {
+        // HeaderSize counts from the start of the data file, like MetaIO does.
+        let data_offset = match header.header_size {
+            Some(-1) => {
+                let data_bytes = if header.compressed_data {
+                    header.compressed_data_size.filter(|_| files == 1).ok_or_else(|| {
+                        MetaImageError::Unsupported("HeaderSize = -1 for compressed data needs CompressedDataSize".into())
+                    })?
+                } else {
+                    total_bytes as u64 / files
+                };
+                let file_bytes = data_path.metadata()?.len();
+                file_bytes.checked_sub(data_bytes).ok_or(MetaImageError::Truncated {
+                    expected: data_bytes as usize,
+                    found: file_bytes as usize,
+                })?
+            }
+            Some(size) if size > 0 => size as u64,
+            _ if local => header.data_offset,
+            _ => 0,
+        };
+        Ok((data_path, data_offset))
+    }).collect()
+}
+
+
+// Like `data_locations` for readers that need all voxel data in a single file.
+pub(super) fn data_location(filename: &str, header: &Header, total_bytes: usize) -> Result<(PathBuf, u64), MetaImageError> {
+    let mut locations = data_locations(filename, header, total_bytes)?;
+    if locations.len() != 1 {
+        return Err(MetaImageError::Unsupported("voxel data split over multiple files".into()));
     }
+    Ok(locations.remove(0))
+}
+
 
-    // Now just load the file and get bytes from the offset.
-    let mut f = File::open(&data_filename).expect("Failed to open MetaImage file");
-    f.seek(SeekFrom::Start(data_offset))
-        .expect("Failed to seek to data offset");
-
-    // If file is compressed we have to read _all_ bytes in file, otherwise we can do exact reads.
-    if header.compressed_data {
-        let mut compressed = Vec::new();
-        f.read_to_end(&mut compressed).expect("Failed to read compressed voxel data");
-        let mut decoder = ZlibDecoder::new(&compressed[..]);
-        let mut decompressed = Vec::with_capacity(total_bytes);
-        decoder
-            .read_to_end(&mut decompressed)
-            .expect("Failed to decompress voxel data");
-        buffer = decompressed;
+/// Decoder for compressed voxel data. MetaIO writes zlib streams, other
+/// writers gzip or raw deflate; the first bytes tell them apart.
+pub(super) fn decoder<'a, R: BufRead + 'a>(mut reader: R) -> std::io::Result<Box<dyn Read + 'a>> {
+    let (gzip, zlib) = match reader.fill_buf()? {
+        [0x1f, 0x8b, ..] => (true, false),
+        // Deflate method and the header check (a multiple of 31), as in RFC 1950.
+        [cmf, flg, ..] => (false, cmf & 0x0f == 8 && (*cmf as u16 * 256 + *flg as u16).is_multiple_of(31)),
+        _ => (false, false),
+    };
+    Ok(if gzip {
+        Box::new(MultiGzDecoder::new(reader))
+    } else if zlib {
+        Box::new(ZlibDecoder::new(reader))
     } else {
-        f.read_exact(&mut buffer).expect("Failed to read voxel data");
+        Box::new(DeflateDecoder::new(reader))
+    })
+}
+
+
+pub fn load_meta_image(filename: &str) -> Result<Box<dyn AnyImage>, MetaImageError> {
+    let header = parse_header(filename)?;
+
+    let etype: ElementType = header.element_type.parse()?;
+    let total_bytes = header.data_bytes(etype.bytes())?;
+
+    // Now just load the file(s) and get bytes from the offset. Multi-file data
+    // (LIST or a pattern) is the concatenation of all files.
+    let mut buffer = Vec::new();
+    let locations = data_locations(filename, &header, total_bytes)?;
+    // CompressedDataSize is the total, only usable for reading a single file.
+    let compressed_size = header.compressed_data_size.filter(|_| locations.len() == 1);
+    for (data_filename, data_offset) in locations {
+        let mut f = File::open(&data_filename)?;
+        f.seek(SeekFrom::Start(data_offset))?;
+
+        // If file is compressed we have to read _all_ bytes in file (or CompressedDataSize),
+        // otherwise we can do exact reads. ASCII data has no fixed size so it is read to the end as well.
+        if header.compressed_data {
+            let mut compressed = Vec::new();
+            f.take(compressed_size.unwrap_or(u64::MAX)).read_to_end(&mut compressed)?;
+            decoder(&compressed[..])?
+                .read_to_end(&mut buffer)
+                .map_err(MetaImageError::Decompression)?;
+        } else if header.binary_data {
+            let remaining = total_bytes.saturating_sub(buffer.len());
+            // Never more than the file holds, whatever DimSize claims.
+            let available = f.metadata()?.len().saturating_sub(data_offset);
+            buffer.reserve(remaining.min(available as usize));
+            f.take(remaining as u64).read_to_end(&mut buffer)?;
+        } else {
+            f.read_to_end(&mut buffer)?;
+            buffer.push(b'\n');
+        }
+    }
+
+    if !header.binary_data {
+        return build_any_image(etype, buffer, &header);
+    }
+    if header.compressed_data && buffer.len() != total_bytes {
+        return Err(MetaImageError::DecompressedSize { expected: total_bytes, found: buffer.len() });
+    }
+    if buffer.len() < total_bytes {
+        return Err(MetaImageError::Truncated { expected: total_bytes, found: buffer.len() });
     }
+    buffer.truncate(total_bytes);
 
+    build_any_image(etype, buffer, &header)
+}
+
+
+// Convert raw voxel bytes described by `header` into the matching image type.
+pub(super) fn build_any_image(etype: ElementType, buffer: Vec<u8>, header: &Header) -> Result<Box<dyn AnyImage>, MetaImageError> {
     match etype {
-        ElementType::U8 => {
-            let vox_vec: Vec<u8> = buffer; // already Vec<u8>
-            let image = Image::<u8> {
-                voxels: vox_vec,
-                width: header.dim_size[0],
-                height: header.dim_size[1],
-                depth: header.dim_size[2],
-                spacing: header.element_spacing,
-                origin: header.offset,
-                direction: header.transform_matrix,
-            };
-            Box::new(image)
-        }
-        ElementType::I8 => {
-            let vox_vec: Vec<i8> = buffer.iter().map(|&b| b as i8).collect();
-            let image = Image::<i8> {
-                voxels: vox_vec,
-                width: header.dim_size[0],
-                height: header.dim_size[1],
-                depth: header.dim_size[2],
-                spacing: header.element_spacing,
-                origin: header.offset,
-                direction: header.transform_matrix,
-            };
-            Box::new(image)
-        }
-        ElementType::I16 => {
-            let vox_vec: Vec<i16> = bytes_to_vec::<i16>(buffer).expect("Bad byte count");
-            let image = Image::<i16> {
-                voxels: vox_vec,
-                width: header.dim_size[0],
-                height: header.dim_size[1],
-                depth: header.dim_size[2],
-                spacing: header.element_spacing,
-                origin: header.offset,
-                direction: header.transform_matrix,
-            };
-            Box::new(image)
-        }
-        ElementType::U16 => {
-            let vox_vec: Vec<u16> = bytes_to_vec::<u16>(buffer).expect("Bad byte count");
-            let image = Image::<u16> {
-                voxels: vox_vec,
-                width: header.dim_size[0],
-                height: header.dim_size[1],
-                depth: header.dim_size[2],
-                spacing: header.element_spacing,
-                origin: header.offset,
-                direction: header.transform_matrix,
-            };
-            Box::new(image)
-        }
-        ElementType::I32 => {
-            let vox_vec: Vec<i32> = bytes_to_vec::<i32>(buffer).expect("Bad byte count");
-            let image = Image::<i32> {
-                voxels: vox_vec,
-                width: header.dim_size[0],
-                height: header.dim_size[1],
-                depth: header.dim_size[2],
-                spacing: header.element_spacing,
-                origin: header.offset,
-                direction: header.transform_matrix,
-            };
-            Box::new(image)
-        }
-        ElementType::U32 => {
-            let vox_vec: Vec<u32> = bytes_to_vec::<u32>(buffer).expect("Bad byte count");
-            let image = Image::<u32> {
-                voxels: vox_vec,
-                width: header.dim_size[0],
-                height: header.dim_size[1],
-                depth: header.dim_size[2],
-                spacing: header.element_spacing,
-                origin: header.offset,
-                direction: header.transform_matrix,
-            };
-            Box::new(image)
-        }
-                ElementType::F32 => {
-            let vox_vec: Vec<f32> = bytes_to_vec::<f32>(buffer).expect("Bad byte count");
-            let image = Image::<f32> {
-                voxels: vox_vec,
-                width: header.dim_size[0],
-                height: header.dim_size[1],
-                depth: header.dim_size[2],
-                spacing: header.element_spacing,
-                origin: header.offset,
-                direction: header.transform_matrix,
-            };
-            Box::new(image)
-        }
-        ElementType::I64 => {
-            let vox_vec: Vec<i64> = bytes_to_vec::<i64>(buffer).expect("Bad byte count");
-            let image = Image::<i64> {
-                voxels: vox_vec,
-                width: header.dim_size[0],
-                height: header.dim_size[1],
-                depth: header.dim_size[2],
-                spacing: header.element_spacing,
-                origin: header.offset,
-                direction: header.transform_matrix,
-            };
-            Box::new(image)
-        }
-        ElementType::U64 => {
-            let vox_vec: Vec<u64> = bytes_to_vec::<u64>(buffer).expect("Bad byte count");
-            let image = Image::<u64> {
-                voxels: vox_vec,
-                width: header.dim_size[0],
-                height: header.dim_size[1],
-                depth: header.dim_size[2],
-                spacing: header.element_spacing,
-                origin: header.offset,
-                direction: header.transform_matrix,
-            };
-            Box::new(image)
-        }
-        ElementType::F64 => {
-            let vox_vec: Vec<f64> = bytes_to_vec::<f64>(buffer).expect("Bad byte count");
-            let image = Image::<f64> {
-                voxels: vox_vec,
-                width: header.dim_size[0],
-                height: header.dim_size[1],
-                depth: header.dim_size[2],
-                spacing: header.element_spacing,
-                origin: header.offset,
-                direction: header.transform_matrix,
-            };
-            Box::new(image)
-        }
+        ElementType::U8 => build_image::<u8>(buffer, header),
+        ElementType::I8 => build_image::<i8>(buffer, header),
+        ElementType::I16 => build_image::<i16>(buffer, header),
+        ElementType::U16 => build_image::<u16>(buffer, header),
+        ElementType::I32 => build_image::<i32>(buffer, header),
+        ElementType::U32 => build_image::<u32>(buffer, header),
+        ElementType::F32 => build_image::<f32>(buffer, header),
+        ElementType::I64 => build_image::<i64>(buffer, header),
+        ElementType::U64 => build_image::<u64>(buffer, header),
+        ElementType::F64 => build_image::<f64>(buffer, header),
     }
 }
 
@@ -267,73 +359,227 @@ fn element_type_str<T: 'static>() -> &'static str {
 }
 
 
-pub fn save_image<T>(img: Image<T>, file_path: &str, compress: bool) -> std::io::Result<()>
+/// Options for writing MetaImage files, `Default` gives an uncompressed little-endian file.
+#[derive(Debug, Clone, Copy)]
+pub struct SaveOptions {
+    /// Zlib compress the voxel data (.zraw for .mhd files).
+    pub compress: bool,
+    /// zlib level from 0 (stored, fastest) to 9 (smallest), 6 by default.
+    pub compression_level: u32,
+    /// Write voxel data big-endian (`BinaryDataByteOrderMSB = True`).
+    pub byte_order_msb: bool,
+    /// Write voxel data as whitespace separated text (`BinaryData = False`).
+    pub ascii: bool,
+    /// Write one data file per slice along the last axis, referenced from the
+    /// header with a file name pattern. Only for .mhd files.
+    pub split_slices: bool,
+}
+
+impl Default for SaveOptions {
+    fn default() -> Self {
+        SaveOptions {
+            compress: false,
+            compression_level: 6,
+            byte_order_msb: false,
+            ascii: false,
+            split_slices: false,
+        }
+    }
+}
+
+
+// Everything the header writer needs to know about an image besides its voxels.
+struct ImageInfo {
+    size: Vec<u32>,
+    spacing: Vec<f64>,
+    origin: Vec<f64>,
+    direction: Vec<f64>,
+    channels: u32,
+    metadata: Metadata,
+}
+
+
+// Keys written from the image itself, never copied over from its metadata.
+const RESERVED_KEYS: &[&str] = &[
+    "ObjectType", "NDims", "BinaryData", "BinaryDataByteOrderMSB", "ElementByteOrderMSB",
+    "CompressedData", "CompressedDataSize", "HeaderSize", "TransformMatrix", "Offset", "ElementSpacing", "DimSize",
+    "ElementNumberOfChannels", "ElementType", "ElementDataFile",
+];
+
+
+fn join<T: ToString>(values: &[T]) -> String {
+    values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+
+// Voxels as text, one row along the first axis per line.
+fn vec_to_ascii<T: Display>(voxels: &[T], row_length: usize) -> Vec<u8> {
+    let mut text = String::new();
+    for row in voxels.chunks(row_length.max(1)) {
+        text.push_str(&join(row));
+        text.push('\n');
+    }
+    text.into_bytes()
+}
+
+
+fn encode_voxels<'a, T>(voxels: &'a [T], info: &ImageInfo, options: &SaveOptions) -> Cow<'a, [u8]>
+where
+    T: Pod + Display,
+{
+    if options.ascii {
+        Cow::Owned(vec_to_ascii(voxels, info.size[0] as usize * info.channels as usize))
+    } else {
+        vec_to_bytes(voxels, options.byte_order_msb)
+    }
+}
+
+
+pub fn save_image<T>(img: &Image<T>, file_path: &str, options: &SaveOptions) -> std::io::Result<()>
+where
+    T: Pod + Display + 'static,
+{
+    let info = ImageInfo {
+        size: vec![img.width, img.height, img.depth],
+        spacing: img.spacing.to_vec(),
+        origin: img.origin.to_vec(),
+        direction: img.direction.to_vec(),
+        channels: 1,
+        metadata: img.metadata.clone(),
+    };
+    write_meta_image(&img.voxels, &info, file_path, options)
+}
+
+
+pub fn save_nd_image<T>(img: &NdImage<T>, file_path: &str, options: &SaveOptions) -> std::io::Result<()>
 where
-    T: Pod + 'static,
+    T: Pod + Display + 'static,
 {
+    let info = ImageInfo {
+        size: img.size.clone(),
+        spacing: img.spacing.clone(),
+        origin: img.origin.clone(),
+        direction: img.direction.clone(),
+        channels: 1,
+        metadata: img.metadata.clone(),
+    };
+    write_meta_image(&img.voxels, &info, file_path, options)
+}
+
+
+pub fn save_vector_image<T>(img: &VectorImage<T>, file_path: &str, options: &SaveOptions) -> std::io::Result<()>
+where
+    T: Pod + Display + 'static,
+{
+    let info = ImageInfo {
+        size: vec![img.width, img.height, img.depth],
+        spacing: img.spacing.to_vec(),
+        origin: img.origin.to_vec(),
+        direction: img.direction.to_vec(),
+        channels: img.channels,
+        metadata: img.metadata.clone(),
+    };
+    write_meta_image(&img.voxels, &info, file_path, options)
+}
+
+
+fn write_meta_image<T>(voxels: &[T], info: &ImageInfo, file_path: &str, options: &SaveOptions) -> std::io::Result<()>
+where
+    T: Pod + Display + 'static,
+{
+    let compress = options.compress;
     let path = Path::new(file_path);
     let filename = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
     let ext = path.extension().and_then(|s| s.to_str()).unwrap_or_default();
 
     if ext != "mha" && ext != "mhd" {
-        panic!("Unsupported file extension: {}. Only 'mha' and 'mhd' are allowed.", ext);
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Unsupported file extension: {}. Only 'mha' and 'mhd' are allowed.", ext),
+        ));
+    }
+    if ext == "mha" && options.split_slices {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "split_slices needs a .mhd header, .mha files keep their data inline",
+        ));
+    }
+    if options.compression_level > 9 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("compression_level is {}, expected 0 to 9", options.compression_level),
+        ));
     }
 
     // choose LOCAL for .mha or generate either .zraw/.raw for .mhd
-    let (element_data_file, raw_path) = if ext == "mha" {
-        ("LOCAL".to_string(), file_path.to_string())
+    let raw_ext = if compress { "zraw" } else { "raw" };
+    let slices = *info.size.last().unwrap_or(&1) as usize;
+    let (element_data_file, raw_paths) = if ext == "mha" {
+        ("LOCAL".to_string(), vec![])
+    } else if options.split_slices {
+        // e.g. "image_%03d.raw 0 119 1"
+        let width = (slices.saturating_sub(1).to_string().len()).max(3);
+        let pattern = format!("{}_%0{}d.{}", filename, width, raw_ext);
+        let raw_paths = (0..slices)
+            .map(|i| path.with_file_name(format_pattern(&pattern, i as i64).unwrap()))
+            .collect();
+        (format!("{} 0 {} 1", pattern, slices.saturating_sub(1)), raw_paths)
     } else {
-        let raw_filename = if compress {
-            format!("{}.zraw", filename)
-        } else {
-            format!("{}.raw", filename)
-        };
-        let raw_path = path.with_file_name(&raw_filename).to_str().unwrap().to_string();
-        (raw_filename, raw_path)
+        let raw_filename = format!("{}.{}", filename, raw_ext);
+        let raw_path = path.with_file_name(&raw_filename);
+        (raw_filename, vec![raw_path])
+    };
+
+    // Encode (and compress) up front, the header needs the compressed size.
+    let payloads: Vec<Cow<[u8]>> = if raw_paths.is_empty() {
+        vec![encode_voxels(voxels, info, options)]
+    } else {
+        let chunk = voxels.len() / raw_paths.len();
+        voxels.chunks(chunk.max(1)).map(|voxels| encode_voxels(voxels, info, options)).collect()
+    };
+    let payloads = if compress {
+        payloads.iter().map(|bytes| {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(options.compression_level));
+            encoder.write_all(bytes)?;
+            Ok(Cow::Owned(encoder.finish()?))
+        }).collect::<std::io::Result<Vec<_>>>()?
+    } else {
+        payloads
     };
 
     let mut header = BufWriter::new(File::create(file_path)?);
     writeln!(header, "ObjectType = Image")?;
-    writeln!(header, "NDims = 3")?;
-    writeln!(header, "BinaryData = True")?;
-    writeln!(header, "BinaryDataByteOrderMSB = False")?;
+    writeln!(header, "NDims = {}", info.size.len())?;
+    writeln!(header, "BinaryData = {}", if options.ascii { "False" } else { "True" })?;
+    writeln!(header, "BinaryDataByteOrderMSB = {}", if options.byte_order_msb && !options.ascii { "True" } else { "False" })?;
     writeln!(header, "CompressedData = {}", if compress { "True" } else { "False" })?;
-    writeln!(header, "TransformMatrix = {}", img.direction.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" "))?;
-    writeln!(header, "Offset = {}", img.origin.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" "))?;
-    writeln!(header, "ElementSpacing = {}", img.spacing.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" "))?;
-    writeln!(header, "DimSize = {} {} {}", img.width, img.height, img.depth)?;
+    if compress {
+        writeln!(header, "CompressedDataSize = {}", payloads.iter().map(|p| p.len()).sum::<usize>())?;
+    }
+    writeln!(header, "TransformMatrix = {}", join(&info.direction))?;
+    writeln!(header, "Offset = {}", join(&info.origin))?;
+    writeln!(header, "ElementSpacing = {}", join(&info.spacing))?;
+    writeln!(header, "DimSize = {}", join(&info.size))?;
+    for (key, value) in info.metadata.iter().filter(|(key, _)| !RESERVED_KEYS.contains(key)) {
+        writeln!(header, "{} = {}", key, value)?;
+    }
+    if info.channels > 1 {
+        writeln!(header, "ElementNumberOfChannels = {}", info.channels)?;
+    }
     writeln!(header, "ElementType = {}", element_type_str::<T>())?;
     writeln!(header, "ElementDataFile = {}", element_data_file)?;
     header.flush()?;
 
-    let slice: &[T] = img.voxels.as_slice();
-    let bytes: &[u8] = unsafe {
-        std::slice::from_raw_parts(
-            slice.as_ptr() as *const u8,
-            slice.len() * std::mem::size_of::<T>(),
-        )
-    };
-
     if ext == "mha" {
         // inline into .mha
-        if compress {
-            let mut encoder = ZlibEncoder::new(header.get_mut(), Compression::default());
-            encoder.write_all(bytes)?;
-            encoder.finish()?;
-        } else {
-            header.get_mut().write_all(bytes)?;
-        }
+        header.write_all(&payloads[0])?;
         header.flush()?;
         return Ok(());
     }
 
-    // write out .zraw/.raw
-    let mut raw = BufWriter::new(File::create(raw_path)?);
-    if compress {
-        let mut encoder = ZlibEncoder::new(raw, Compression::default());
-        encoder.write_all(bytes)?;
-        encoder.finish()?;
-    } else {
+    // write out .zraw/.raw, one per slice when splitting
+    for (raw_path, bytes) in raw_paths.iter().zip(&payloads) {
+        let mut raw = BufWriter::new(File::create(raw_path)?);
         raw.write_all(bytes)?;
         raw.flush()?;
     }
@@ -341,30 +587,32 @@ where
 }
 
 
+fn try_save<T>(img: &dyn AnyImage, file_path: &str, options: &SaveOptions) -> Option<std::io::Result<()>>
+where
+    T: Pod + Display + 'static,
+{
+    let parts = ImageParts::<T>::of(img)?;
+    let info = ImageInfo {
+        size: parts.size,
+        spacing: parts.spacing.to_vec(),
+        origin: parts.origin.to_vec(),
+        direction: parts.direction.to_vec(),
+        channels: parts.channels,
+        metadata: parts.metadata.clone(),
+    };
+    Some(write_meta_image(parts.voxels, &info, file_path, options))
+}
+
+
 pub fn save_meta_image(img: &dyn AnyImage, file_path: &str, compress: bool) -> std::io::Result<()> {
-    if let Some(i) = img.as_any().downcast_ref::<Image<u8>>() {
-        return save_image(i.clone(), file_path, compress);
-    } else if let Some(i) = img.as_any().downcast_ref::<Image<i8>>() {
-        return save_image(i.clone(), file_path, compress);
-    } else if let Some(i) = img.as_any().downcast_ref::<Image<u16>>() {
-        return save_image(i.clone(), file_path, compress);
-    } else if let Some(i) = img.as_any().downcast_ref::<Image<i16>>() {
-        return save_image(i.clone(), file_path, compress);
-    } else if let Some(i) = img.as_any().downcast_ref::<Image<u32>>() {
-        return save_image(i.clone(), file_path, compress);
-    } else if let Some(i) = img.as_any().downcast_ref::<Image<i32>>() {
-        return save_image(i.clone(), file_path, compress);
-    } else if let Some(i) = img.as_any().downcast_ref::<Image<f32>>() {
-        return save_image(i.clone(), file_path, compress);
-    } else if let Some(i) = img.as_any().downcast_ref::<Image<u64>>() {
-        return save_image(i.clone(), file_path, compress);
-    } else if let Some(i) = img.as_any().downcast_ref::<Image<i64>>() {
-        return save_image(i.clone(), file_path, compress);
-    } else if let Some(i) = img.as_any().downcast_ref::<Image<f64>>() {
-        return save_image(i.clone(), file_path, compress);
-    }
-    Err(std::io::Error::new(
-        std::io::ErrorKind::InvalidInput,
-        "Unsupported pixel type for save_meta_image",
-    ))
+    save_meta_image_with_options(img, file_path, &SaveOptions { compress, ..Default::default() })
+}
+
+
+pub fn save_meta_image_with_options(img: &dyn AnyImage, file_path: &str, options: &SaveOptions) -> std::io::Result<()> {
+    first_pixel_type!(try_save(img, file_path, options))
+        .unwrap_or_else(|| Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Unsupported pixel type for save_meta_image",
+        )))
 }
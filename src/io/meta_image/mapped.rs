@@ -0,0 +1,79 @@
+use crate::image::{AnyImage, MappedImage};
+use crate::image::nd_image::{direction3, padded3};
+
+use super::error::MetaImageError;
+use super::header::{Header, parse_header};
+use super::image::{ElementType, data_location};
+use std::fs::File;
+use std::marker::PhantomData;
+use bytemuck::Pod;
+use memmap2::Mmap;
+use num_traits::NumCast;
+
+
+fn build_mapped<T>(mmap: Mmap, data_offset: usize, header: &Header) -> Box<dyn AnyImage>
+where
+    T: Pod + NumCast + Copy + 'static,
+{
+    let size = |axis: usize| header.dim_size.get(axis).copied().unwrap_or(1);
+    Box::new(MappedImage::<T> {
+        mmap,
+        data_offset,
+        byte_order_msb: header.binary_data_byte_order_msb,
+        width: size(0),
+        height: size(1),
+        depth: size(2),
+        spacing: padded3(&header.element_spacing, 1.0),
+        origin: padded3(&header.offset, 0.0),
+        direction: direction3(&header.transform_matrix, header.n_dims),
+        metadata: header.metadata.clone(),
+        _voxel: PhantomData,
+    })
+}
+
+
+/// Memory-map an uncompressed scalar MetaImage of at most three dimensions
+/// instead of reading it, the result is a `MappedImage<T>`.
+///
+/// Like any memory map, the file must not be changed by other processes
+/// while the image is alive.
+pub fn map_meta_image(filename: &str) -> Result<Box<dyn AnyImage>, MetaImageError> {
+    let header = parse_header(filename)?;
+    let etype: ElementType = header.element_type.parse()?;
+
+    if header.compressed_data || !header.binary_data {
+        return Err(MetaImageError::Unsupported("only uncompressed binary data can be memory-mapped".into()));
+    }
+    if header.element_number_of_channels > 1 || header.n_dims > 3 {
+        return Err(MetaImageError::Unsupported(
+            "only scalar images of at most three dimensions can be memory-mapped".into()
+        ));
+    }
+
+    let total_bytes = header.data_bytes(etype.bytes())?;
+
+    let (data_filename, data_offset) = data_location(filename, &header, total_bytes)?;
+    let file = File::open(&data_filename)?;
+    // Safety: the mapping is read-only, see the note on modifying the file above.
+    let mmap = unsafe { Mmap::map(&file)? };
+
+    let data_offset = data_offset as usize;
+    let available = mmap.len().saturating_sub(data_offset);
+    if available < total_bytes {
+        return Err(MetaImageError::Truncated { expected: total_bytes, found: available });
+    }
+
+    let image = match etype {
+        ElementType::U8 => build_mapped::<u8>(mmap, data_offset, &header),
+        ElementType::I8 => build_mapped::<i8>(mmap, data_offset, &header),
+        ElementType::I16 => build_mapped::<i16>(mmap, data_offset, &header),
+        ElementType::U16 => build_mapped::<u16>(mmap, data_offset, &header),
+        ElementType::I32 => build_mapped::<i32>(mmap, data_offset, &header),
+        ElementType::U32 => build_mapped::<u32>(mmap, data_offset, &header),
+        ElementType::F32 => build_mapped::<f32>(mmap, data_offset, &header),
+        ElementType::I64 => build_mapped::<i64>(mmap, data_offset, &header),
+        ElementType::U64 => build_mapped::<u64>(mmap, data_offset, &header),
+        ElementType::F64 => build_mapped::<f64>(mmap, data_offset, &header),
+    };
+    Ok(image)
+}
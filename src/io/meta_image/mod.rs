@@ -1,4 +1,10 @@
+pub mod error;
 pub mod header;
 pub mod image;
+pub mod mapped;
+pub mod reader;
 
-pub use image::{load_meta_image, save_meta_image};
\ No newline at end of file
+pub use error::MetaImageError;
+pub use image::{load_meta_image, save_meta_image, save_meta_image_with_options, SaveOptions};
+pub use mapped::map_meta_image;
+pub use reader::{MetaImageReader, Slices};
@@ -0,0 +1,207 @@
+use crate::image::AnyImage;
+
+use super::error::MetaImageError;
+use super::header::{Header, parse_header};
+use super::image::{ElementType, build_any_image, data_location, decoder};
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+/// Reads parts of a MetaImage without loading the full voxel buffer.
+///
+/// Uncompressed data (.raw or LOCAL) is read by seeking straight to the
+/// requested rows, compressed data (.zraw, zlib, gzip or raw deflate) is
+/// decompressed as a stream and skipped up to the requested rows.
+pub struct MetaImageReader {
+    header: Header,
+    etype: ElementType,
+    data_path: PathBuf,
+    data_offset: u64,
+}
+
+// Source of voxel bytes. Compressed data can only be read front to back.
+enum DataStream {
+    Raw { file: File, data_offset: u64 },
+    Compressed { decoder: Box<dyn Read>, position: u64 },
+}
+
+impl DataStream {
+    // Fill `buf` with the voxel bytes starting at `offset`.
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<(), MetaImageError> {
+        match self {
+            DataStream::Raw { file, data_offset } => {
+                file.seek(SeekFrom::Start(*data_offset + offset))?;
+                let found = read_full(file, buf)?;
+                check_length(offset, buf.len(), found)
+            }
+            DataStream::Compressed { decoder, position } => {
+                debug_assert!(offset >= *position, "compressed data is read front to back");
+                let skip = offset - *position;
+                let skipped = io::copy(&mut decoder.by_ref().take(skip), &mut io::sink())
+                    .map_err(MetaImageError::Decompression)?;
+                if skipped < skip {
+                    return check_length(*position, skip as usize, skipped as usize);
+                }
+                let found = read_full(decoder, buf).map_err(MetaImageError::Decompression)?;
+                *position = offset + found as u64;
+                check_length(offset, buf.len(), found)
+            }
+        }
+    }
+}
+
+// Like `read_exact`, but reports how many bytes were read before EOF.
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+fn check_length(offset: u64, expected: usize, found: usize) -> Result<(), MetaImageError> {
+    if found < expected {
+        return Err(MetaImageError::Truncated {
+            expected: offset as usize + expected,
+            found: offset as usize + found,
+        });
+    }
+    Ok(())
+}
+
+impl MetaImageReader {
+    /// Parse the header of `filename`, no voxel data is read yet.
+    pub fn open(filename: &str) -> Result<Self, MetaImageError> {
+        let header = parse_header(filename)?;
+        let etype: ElementType = header.element_type.parse()?;
+        if !header.binary_data {
+            return Err(MetaImageError::Unsupported("region reads need binary voxel data".into()));
+        }
+        let total_bytes = header.data_bytes(etype.bytes())?;
+        let (data_path, data_offset) = data_location(filename, &header, total_bytes)?;
+        Ok(MetaImageReader { header, etype, data_path, data_offset })
+    }
+
+    pub fn ndims(&self) -> usize {
+        self.header.n_dims
+    }
+
+    /// Size of the full image along every dimension.
+    pub fn size(&self) -> &[u32] {
+        &self.header.dim_size
+    }
+
+    fn stream(&self) -> Result<DataStream, MetaImageError> {
+        let mut file = File::open(&self.data_path)?;
+        if self.header.compressed_data {
+            file.seek(SeekFrom::Start(self.data_offset))?;
+            let compressed_size = self.header.compressed_data_size.unwrap_or(u64::MAX);
+            let decoder = decoder(BufReader::new(file).take(compressed_size))?;
+            return Ok(DataStream::Compressed { decoder, position: 0 });
+        }
+        Ok(DataStream::Raw { file, data_offset: self.data_offset })
+    }
+
+    /// Read the block of `size` voxels starting at index `start`, one value per
+    /// dimension each. The block comes back as the same image type
+    /// `load_meta_image` would produce, with its origin moved to `start`.
+    pub fn read_region(&self, start: &[u32], size: &[u32]) -> Result<Box<dyn AnyImage>, MetaImageError> {
+        let mut stream = self.stream()?;
+        self.read_region_from(&mut stream, start, size)
+    }
+
+    /// Iterate through the image one slice at a time along its last axis,
+    /// i.e. z-slices for a 3D volume. Every slice keeps all dimensions with
+    /// size 1 along the last one.
+    pub fn slices(&self) -> Result<Slices<'_>, MetaImageError> {
+        Ok(Slices { reader: self, stream: self.stream()?, index: 0 })
+    }
+
+    fn read_region_from(&self, stream: &mut DataStream, start: &[u32], size: &[u32]) -> Result<Box<dyn AnyImage>, MetaImageError> {
+        let header = &self.header;
+        let dims = &header.dim_size;
+        let n = dims.len();
+
+        let fits = start.len() == n && size.len() == n
+            && (0..n).all(|i| size[i] > 0 && start[i] as u64 + size[i] as u64 <= dims[i] as u64);
+        if !fits {
+            return Err(MetaImageError::InvalidRegion {
+                start: start.to_vec(),
+                size: size.to_vec(),
+                dims: dims.clone(),
+            });
+        }
+
+        // Byte strides of each dimension in the file.
+        let pixel_bytes = (self.etype.bytes() * header.element_number_of_channels) as u64;
+        let mut strides = vec![pixel_bytes; n];
+        for i in 1..n {
+            strides[i] = strides[i - 1] * dims[i - 1] as u64;
+        }
+
+        // Rows along the first axis are contiguous, read those one at a time.
+        let row_bytes = size[0] as usize * pixel_bytes as usize;
+        let rows: usize = size[1..].iter().map(|&s| s as usize).product();
+        let mut buffer = vec![0u8; row_bytes * rows];
+        let mut index = vec![0u32; n];
+        for row in buffer.chunks_exact_mut(row_bytes) {
+            let offset: u64 = (0..n).map(|i| (start[i] + index[i]) as u64 * strides[i]).sum();
+            stream.read_at(offset, row)?;
+
+            // Advance to the next row, axis 1 fastest.
+            for i in 1..n {
+                index[i] += 1;
+                if index[i] < size[i] {
+                    break;
+                }
+                index[i] = 0;
+            }
+        }
+
+        // Move the origin to the first voxel of the region.
+        let mut region = header.clone();
+        for (axis, &s) in start.iter().enumerate() {
+            let shift = s as f64 * header.element_spacing[axis];
+            for (j, origin) in region.offset.iter_mut().enumerate() {
+                *origin += shift * header.transform_matrix[axis * n + j];
+            }
+        }
+        region.dim_size = size.to_vec();
+
+        build_any_image(self.etype, buffer, &region)
+    }
+}
+
+/// Iterator over the slices of a MetaImage, see `MetaImageReader::slices`.
+pub struct Slices<'a> {
+    reader: &'a MetaImageReader,
+    stream: DataStream,
+    index: u32,
+}
+
+impl Iterator for Slices<'_> {
+    type Item = Result<Box<dyn AnyImage>, MetaImageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let dims = self.reader.size();
+        let last = dims.len() - 1;
+        if self.index >= dims[last] {
+            return None;
+        }
+
+        let mut start = vec![0; dims.len()];
+        start[last] = self.index;
+        let mut size = dims.to_vec();
+        size[last] = 1;
+
+        let slice = self.reader.read_region_from(&mut self.stream, &start, &size);
+        // Stop after the first error, the stream position is unknown from there.
+        self.index = if slice.is_ok() { self.index + 1 } else { dims[last] };
+        Some(slice)
+    }
+}
@@ -1,3 +1,15 @@
+pub mod analyze;
+pub mod dicom;
+pub mod error;
+pub mod format;
 pub mod meta_image;
+pub mod nifti;
+pub mod npy;
+pub mod nrrd;
+pub mod png;
+pub mod tiff;
+pub mod vtk;
 
+pub use error::ImageIoError;
+pub use format::{formats, load, register_format, save, ImageFormat};
 pub use meta_image::image::{load_meta_image};
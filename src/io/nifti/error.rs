@@ -0,0 +1,35 @@
+#[derive(Debug)]
+pub enum NiftiError {
+    Io(std::io::Error),     // Generic I/O error (file issue)
+    InvalidHeader(String),  // Not a NIfTI-1/2 header, or inconsistent fields
+    UnsupportedDataType(i16),
+    Unsupported(String),
+    Truncated { expected: usize, found: usize },
+}
+
+impl std::fmt::Display for NiftiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NiftiError::Io(e) => write!(f, "I/O error: {}", e),
+            NiftiError::InvalidHeader(what) => write!(f, "Invalid NIfTI header: {}", what),
+            NiftiError::UnsupportedDataType(code) => write!(f, "Unsupported NIfTI datatype: {}", code),
+            NiftiError::Unsupported(what) => write!(f, "Unsupported NIfTI image: {}", what),
+            NiftiError::Truncated { expected, found } => {
+                write!(f, "Truncated voxel data: expected {} bytes, found {}", expected, found)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NiftiError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NiftiError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for NiftiError {
+    fn from(e: std::io::Error) -> Self { NiftiError::Io(e) }
+}
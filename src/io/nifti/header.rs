@@ -0,0 +1,356 @@
+use super::error::NiftiError;
+
+/// NIfTI header layout, NIfTI-2 widens most fields to 64 bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NiftiVersion {
+    #[default]
+    Nifti1,
+    Nifti2,
+}
+
+// Both header versions parsed into the wider NIfTI-2 field types.
+#[derive(Debug, Clone)]
+pub(super) struct NiftiHeader {
+    pub version: NiftiVersion,
+    pub byte_order_msb: bool,
+    // "n+1"/"n+2": voxel data follows the header in the same file, otherwise in a separate .img.
+    pub single_file: bool,
+    pub dim: [i64; 8],
+    pub datatype: i16,
+    pub bitpix: i16,
+    pub pixdim: [f64; 8],
+    pub vox_offset: u64,
+    pub scl_slope: f64,
+    pub scl_inter: f64,
+    pub toffset: f64,
+    pub xyzt_units: i32,
+    pub intent_code: i32,
+    pub intent_name: String,
+    pub descrip: String,
+    pub aux_file: String,
+    pub qform_code: i32,
+    pub sform_code: i32,
+    pub quatern: [f64; 3],
+    pub qoffset: [f64; 3],
+    pub srow: [[f64; 4]; 3],
+}
+
+const NIFTI1_SIZE: usize = 348;
+const NIFTI2_SIZE: usize = 540;
+const NIFTI2_MAGIC: &[u8; 8] = b"n+2\0\r\n\x1a\n";
+
+
+// Fixed-width fields at byte offsets, in the file's byte order. Also
+// used for Analyze 7.5 headers, which NIfTI-1 extends.
+pub(crate) struct Fields<'a> {
+    pub bytes: &'a [u8],
+    pub msb: bool,
+}
+
+impl Fields<'_> {
+    fn raw<const N: usize>(&self, at: usize) -> [u8; N] {
+        let mut raw: [u8; N] = self.bytes[at..at + N].try_into().unwrap();
+        if self.msb != cfg!(target_endian = "big") {
+            raw.reverse();
+        }
+        raw
+    }
+
+    pub fn i16(&self, at: usize) -> i16 { i16::from_ne_bytes(self.raw(at)) }
+    pub fn i32(&self, at: usize) -> i32 { i32::from_ne_bytes(self.raw(at)) }
+    pub fn i64(&self, at: usize) -> i64 { i64::from_ne_bytes(self.raw(at)) }
+    pub fn f32(&self, at: usize) -> f64 { f32::from_ne_bytes(self.raw(at)) as f64 }
+    pub fn f64(&self, at: usize) -> f64 { f64::from_ne_bytes(self.raw(at)) }
+
+    // NUL terminated (or padded) text field.
+    pub fn text(&self, at: usize, len: usize) -> String {
+        let field = &self.bytes[at..at + len];
+        let end = field.iter().position(|&b| b == 0).unwrap_or(len);
+        String::from_utf8_lossy(&field[..end]).trim().to_string()
+    }
+}
+
+
+// Little-endian header writer, all files are written that way.
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn put(&mut self, at: usize, bytes: &[u8]) {
+        self.0[at..at + bytes.len()].copy_from_slice(bytes);
+    }
+
+    // Text fields keep at least one NUL at the end.
+    fn text(&mut self, at: usize, len: usize, text: &str) {
+        let bytes = text.as_bytes();
+        self.put(at, &bytes[..bytes.len().min(len - 1)]);
+    }
+}
+
+
+impl NiftiHeader {
+    pub fn ndims(&self) -> usize {
+        self.dim[0] as usize
+    }
+
+    pub fn size(&self) -> Vec<u64> {
+        self.dim[1..=self.ndims()].iter().map(|&d| d as u64).collect()
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, NiftiError> {
+        if bytes.len() < 4 {
+            return Err(NiftiError::InvalidHeader("file too short".into()));
+        }
+
+        // sizeof_hdr tells the version and, read in the wrong order, the byte order.
+        let sizeof_hdr: [u8; 4] = bytes[..4].try_into().unwrap();
+        let (version, msb) = match (i32::from_le_bytes(sizeof_hdr), i32::from_be_bytes(sizeof_hdr)) {
+            (348, _) => (NiftiVersion::Nifti1, false),
+            (_, 348) => (NiftiVersion::Nifti1, true),
+            (540, _) => (NiftiVersion::Nifti2, false),
+            (_, 540) => (NiftiVersion::Nifti2, true),
+            (size, _) => {
+                return Err(NiftiError::InvalidHeader(format!("sizeof_hdr is {}, expected 348 or 540", size)));
+            }
+        };
+        let size = match version {
+            NiftiVersion::Nifti1 => NIFTI1_SIZE,
+            NiftiVersion::Nifti2 => NIFTI2_SIZE,
+        };
+        if bytes.len() < size {
+            return Err(NiftiError::InvalidHeader(format!("header is {} bytes, expected {}", bytes.len(), size)));
+        }
+
+        let f = Fields { bytes, msb };
+        let header = match version {
+            NiftiVersion::Nifti1 => Self::parse_nifti1(&f)?,
+            NiftiVersion::Nifti2 => Self::parse_nifti2(&f)?,
+        };
+
+        if !(1..=7).contains(&header.dim[0]) {
+            return Err(NiftiError::InvalidHeader(format!("dim[0] is {}, expected 1 to 7", header.dim[0])));
+        }
+        if header.size().contains(&0) || header.dim[1..=header.ndims()].iter().any(|&d| d < 0) {
+            return Err(NiftiError::InvalidHeader(format!("invalid dimensions {:?}", header.size())));
+        }
+        Ok(header)
+    }
+
+    fn parse_nifti1(f: &Fields) -> Result<Self, NiftiError> {
+        let single_file = match &f.bytes[344..348] {
+            b"n+1\0" => true,
+            b"ni1\0" => false,
+            magic => return Err(NiftiError::InvalidHeader(format!("unknown magic {:?}", magic))),
+        };
+        let srow = |at: usize| [f.f32(at), f.f32(at + 4), f.f32(at + 8), f.f32(at + 12)];
+        Ok(NiftiHeader {
+            version: NiftiVersion::Nifti1,
+            byte_order_msb: f.msb,
+            single_file,
+            dim: std::array::from_fn(|i| f.i16(40 + 2 * i) as i64),
+            datatype: f.i16(70),
+            bitpix: f.i16(72),
+            pixdim: std::array::from_fn(|i| f.f32(76 + 4 * i)),
+            vox_offset: f.f32(108).max(0.0) as u64,
+            scl_slope: f.f32(112),
+            scl_inter: f.f32(116),
+            toffset: f.f32(136),
+            xyzt_units: f.bytes[123] as i32,
+            intent_code: f.i16(68) as i32,
+            intent_name: f.text(328, 16),
+            descrip: f.text(148, 80),
+            aux_file: f.text(228, 24),
+            qform_code: f.i16(252) as i32,
+            sform_code: f.i16(254) as i32,
+            quatern: [f.f32(256), f.f32(260), f.f32(264)],
+            qoffset: [f.f32(268), f.f32(272), f.f32(276)],
+            srow: [srow(280), srow(296), srow(312)],
+        })
+    }
+
+    fn parse_nifti2(f: &Fields) -> Result<Self, NiftiError> {
+        let single_file = match &f.bytes[4..12] {
+            magic if magic == NIFTI2_MAGIC => true,
+            magic if &magic[..4] == b"ni2\0" => false,
+            magic => return Err(NiftiError::InvalidHeader(format!("unknown magic {:?}", magic))),
+        };
+        let srow = |at: usize| [f.f64(at), f.f64(at + 8), f.f64(at + 16), f.f64(at + 24)];
+        Ok(NiftiHeader {
+            version: NiftiVersion::Nifti2,
+            byte_order_msb: f.msb,
+            single_file,
+            dim: std::array::from_fn(|i| f.i64(16 + 8 * i)),
+            datatype: f.i16(12),
+            bitpix: f.i16(14),
+            pixdim: std::array::from_fn(|i| f.f64(104 + 8 * i)),
+            vox_offset: f.i64(168).max(0) as u64,
+            scl_slope: f.f64(176),
+            scl_inter: f.f64(184),
+            toffset: f.f64(216),
+            xyzt_units: f.i32(500),
+            intent_code: f.i32(504),
+            intent_name: f.text(508, 16),
+            descrip: f.text(240, 80),
+            aux_file: f.text(320, 24),
+            qform_code: f.i32(344),
+            sform_code: f.i32(348),
+            quatern: [f.f64(352), f.f64(360), f.f64(368)],
+            qoffset: [f.f64(376), f.f64(384), f.f64(392)],
+            srow: [srow(400), srow(432), srow(464)],
+        })
+    }
+
+    /// Header bytes for a single .nii file, including the 4 byte extension
+    /// flag; `vox_offset` must point right behind it.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self.version {
+            NiftiVersion::Nifti1 => self.nifti1_bytes(),
+            NiftiVersion::Nifti2 => self.nifti2_bytes(),
+        }
+    }
+
+    fn nifti1_bytes(&self) -> Vec<u8> {
+        let mut w = Writer(vec![0; NIFTI1_SIZE + 4]);
+        w.put(0, &(NIFTI1_SIZE as i32).to_le_bytes());
+        for (i, &d) in self.dim.iter().enumerate() {
+            w.put(40 + 2 * i, &(d as i16).to_le_bytes());
+        }
+        w.put(68, &(self.intent_code as i16).to_le_bytes());
+        w.put(70, &self.datatype.to_le_bytes());
+        w.put(72, &self.bitpix.to_le_bytes());
+        for (i, &p) in self.pixdim.iter().enumerate() {
+            w.put(76 + 4 * i, &(p as f32).to_le_bytes());
+        }
+        w.put(108, &(self.vox_offset as f32).to_le_bytes());
+        w.put(112, &(self.scl_slope as f32).to_le_bytes());
+        w.put(116, &(self.scl_inter as f32).to_le_bytes());
+        w.put(123, &[self.xyzt_units as u8]);
+        w.put(136, &(self.toffset as f32).to_le_bytes());
+        w.text(148, 80, &self.descrip);
+        w.text(228, 24, &self.aux_file);
+        w.put(252, &(self.qform_code as i16).to_le_bytes());
+        w.put(254, &(self.sform_code as i16).to_le_bytes());
+        for (i, &q) in self.quatern.iter().chain(&self.qoffset).enumerate() {
+            w.put(256 + 4 * i, &(q as f32).to_le_bytes());
+        }
+        for (i, &s) in self.srow.iter().flatten().enumerate() {
+            w.put(280 + 4 * i, &(s as f32).to_le_bytes());
+        }
+        w.text(328, 16, &self.intent_name);
+        w.put(344, b"n+1\0");
+        w.0
+    }
+
+    fn nifti2_bytes(&self) -> Vec<u8> {
+        let mut w = Writer(vec![0; NIFTI2_SIZE + 4]);
+        w.put(0, &(NIFTI2_SIZE as i32).to_le_bytes());
+        w.put(4, NIFTI2_MAGIC);
+        w.put(12, &self.datatype.to_le_bytes());
+        w.put(14, &self.bitpix.to_le_bytes());
+        for (i, &d) in self.dim.iter().enumerate() {
+            w.put(16 + 8 * i, &d.to_le_bytes());
+        }
+        for (i, &p) in self.pixdim.iter().enumerate() {
+            w.put(104 + 8 * i, &p.to_le_bytes());
+        }
+        w.put(168, &(self.vox_offset as i64).to_le_bytes());
+        w.put(176, &self.scl_slope.to_le_bytes());
+        w.put(184, &self.scl_inter.to_le_bytes());
+        w.put(216, &self.toffset.to_le_bytes());
+        w.text(240, 80, &self.descrip);
+        w.text(320, 24, &self.aux_file);
+        w.put(344, &self.qform_code.to_le_bytes());
+        w.put(348, &self.sform_code.to_le_bytes());
+        for (i, &q) in self.quatern.iter().chain(&self.qoffset).enumerate() {
+            w.put(352 + 8 * i, &q.to_le_bytes());
+        }
+        for (i, &s) in self.srow.iter().flatten().enumerate() {
+            w.put(400 + 8 * i, &s.to_le_bytes());
+        }
+        w.put(500, &self.xyzt_units.to_le_bytes());
+        w.put(504, &self.intent_code.to_le_bytes());
+        w.text(508, 16, &self.intent_name);
+        w.0
+    }
+
+    /// Voxel index to RAS world coordinates, rows are x, y and z.
+    ///
+    /// Uses the sform when `sform_code > 0`, else the qform when
+    /// `qform_code > 0`, else only scales by `pixdim` (NIfTI "method 1").
+    pub fn affine(&self) -> [[f64; 4]; 3] {
+        let [dx, dy, dz] = [self.pixdim[1], self.pixdim[2], self.pixdim[3]];
+        if self.sform_code > 0 {
+            return self.srow;
+        }
+        if self.qform_code <= 0 {
+            return [[dx, 0.0, 0.0, 0.0], [0.0, dy, 0.0, 0.0], [0.0, 0.0, dz, 0.0]];
+        }
+
+        let [b, c, d] = self.quatern;
+        let a = (1.0 - (b * b + c * c + d * d)).max(0.0).sqrt();
+        let rotation = [
+            [a * a + b * b - c * c - d * d, 2.0 * (b * c - a * d), 2.0 * (b * d + a * c)],
+            [2.0 * (b * c + a * d), a * a + c * c - b * b - d * d, 2.0 * (c * d - a * b)],
+            [2.0 * (b * d - a * c), 2.0 * (c * d + a * b), a * a + d * d - c * c - b * b],
+        ];
+        // pixdim[0] holds qfac, the handedness of the voxel axes.
+        let qfac = if self.pixdim[0] < 0.0 { -1.0 } else { 1.0 };
+        let scale = [dx, dy, qfac * dz];
+        std::array::from_fn(|row| [
+            rotation[row][0] * scale[0],
+            rotation[row][1] * scale[1],
+            rotation[row][2] * scale[2],
+            self.qoffset[row],
+        ])
+    }
+
+    /// Store `affine` as sform and as its closest qform, with
+    /// `pixdim[1..=3]` set to the column lengths.
+    pub fn set_affine(&mut self, affine: [[f64; 4]; 3]) {
+        self.srow = affine;
+        self.qoffset = [affine[0][3], affine[1][3], affine[2][3]];
+
+        // Unit column vectors make up the rotation, a negative determinant goes into qfac.
+        let mut r = [[0.0; 3]; 3];
+        for col in 0..3 {
+            let length = (0..3).map(|row| affine[row][col].powi(2)).sum::<f64>().sqrt();
+            self.pixdim[col + 1] = length;
+            for row in 0..3 {
+                r[row][col] = if length > 0.0 { affine[row][col] / length } else if row == col { 1.0 } else { 0.0 };
+            }
+        }
+        let det = r[0][0] * (r[1][1] * r[2][2] - r[1][2] * r[2][1])
+            - r[0][1] * (r[1][0] * r[2][2] - r[1][2] * r[2][0])
+            + r[0][2] * (r[1][0] * r[2][1] - r[1][1] * r[2][0]);
+        self.pixdim[0] = if det < 0.0 { -1.0 } else { 1.0 };
+        if det < 0.0 {
+            (0..3).for_each(|row| r[row][2] = -r[row][2]);
+        }
+        self.quatern = quaternion(&r);
+    }
+}
+
+
+// (b, c, d) of the unit quaternion for rotation `r`, as in nifti_mat44_to_quatern.
+fn quaternion(r: &[[f64; 3]; 3]) -> [f64; 3] {
+    let trace = r[0][0] + r[1][1] + r[2][2] + 1.0;
+    let (a, b, c, d) = if trace > 0.5 {
+        let a = 0.5 * trace.sqrt();
+        (a, 0.25 * (r[2][1] - r[1][2]) / a, 0.25 * (r[0][2] - r[2][0]) / a, 0.25 * (r[1][0] - r[0][1]) / a)
+    } else {
+        let xd = 1.0 + r[0][0] - (r[1][1] + r[2][2]);
+        let yd = 1.0 + r[1][1] - (r[0][0] + r[2][2]);
+        let zd = 1.0 + r[2][2] - (r[0][0] + r[1][1]);
+        if xd > 1.0 {
+            let b = 0.5 * xd.sqrt();
+            (0.25 * (r[2][1] - r[1][2]) / b, b, 0.25 * (r[0][1] + r[1][0]) / b, 0.25 * (r[0][2] + r[2][0]) / b)
+        } else if yd > 1.0 {
+            let c = 0.5 * yd.sqrt();
+            (0.25 * (r[0][2] - r[2][0]) / c, 0.25 * (r[0][1] + r[1][0]) / c, c, 0.25 * (r[1][2] + r[2][1]) / c)
+        } else {
+            let d = 0.5 * zd.sqrt();
+            (0.25 * (r[1][0] - r[0][1]) / d, 0.25 * (r[0][2] + r[2][0]) / d, 0.25 * (r[1][2] + r[2][1]) / d, d)
+        }
+    };
+    // a >= 0 is implied by the format, flip the whole quaternion otherwise.
+    if a < 0.0 { [-b, -c, -d] } else { [b, c, d] }
+}
@@ -0,0 +1,395 @@
+use crate::image::{Image, NdImage, VectorImage, AnyImage, Metadata};
+use crate::image::dyn_image::{first_pixel_type, ImageParts};
+use crate::image::nd_image::{direction3, padded3};
+use crate::io::meta_image::image::{bytes_to_vec, vec_to_bytes};
+
+use super::error::NiftiError;
+use super::header::{NiftiHeader, NiftiVersion};
+use std::any::TypeId;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use bytemuck::Pod;
+use num_traits::NumCast;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DataType {
+    I8, U8,
+    I16, U16,
+    I32, U32, F32,
+    I64, U64, F64,
+    Rgb24, Rgba32,
+}
+
+impl DataType {
+    fn from_code(code: i16) -> Result<Self, NiftiError> {
+        use DataType::*;
+        match code {
+            2 => Ok(U8),
+            4 => Ok(I16),
+            8 => Ok(I32),
+            16 => Ok(F32),
+            64 => Ok(F64),
+            128 => Ok(Rgb24),
+            256 => Ok(I8),
+            512 => Ok(U16),
+            768 => Ok(U32),
+            1024 => Ok(I64),
+            1280 => Ok(U64),
+            2304 => Ok(Rgba32),
+            _ => Err(NiftiError::UnsupportedDataType(code)),
+        }
+    }
+
+    fn code(self) -> i16 {
+        use DataType::*;
+        match self {
+            U8 => 2, I16 => 4, I32 => 8, F32 => 16, F64 => 64, Rgb24 => 128,
+            I8 => 256, U16 => 512, U32 => 768, I64 => 1024, U64 => 1280, Rgba32 => 2304,
+        }
+    }
+
+    fn bytes(self) -> usize {
+        use DataType::*;
+        match self {
+            U8 | I8 => 1,
+            U16 | I16 => 2,
+            Rgb24 => 3,
+            U32 | I32 | F32 | Rgba32 => 4,
+            U64 | I64 | F64 => 8,
+        }
+    }
+
+    fn of<T: 'static>() -> Option<Self> {
+        use DataType::*;
+        let tid = TypeId::of::<T>();
+        [
+            (TypeId::of::<u8>(), U8), (TypeId::of::<i8>(), I8),
+            (TypeId::of::<u16>(), U16), (TypeId::of::<i16>(), I16),
+            (TypeId::of::<u32>(), U32), (TypeId::of::<i32>(), I32), (TypeId::of::<f32>(), F32),
+            (TypeId::of::<u64>(), U64), (TypeId::of::<i64>(), I64), (TypeId::of::<f64>(), F64),
+        ].into_iter().find(|(t, _)| *t == tid).map(|(_, d)| d)
+    }
+}
+
+
+// Whole file, gunzipped if it starts with the gzip magic (.nii.gz, .hdr.gz).
+fn read_file(path: &Path) -> Result<Vec<u8>, NiftiError> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+    if bytes.starts_with(&[0x1f, 0x8b]) {
+        let mut decompressed = Vec::new();
+        MultiGzDecoder::new(&bytes[..]).read_to_end(&mut decompressed)?;
+        return Ok(decompressed);
+    }
+    Ok(bytes)
+}
+
+
+// The .img (or .img.gz) next to a .hdr header.
+fn image_file(path: &Path) -> PathBuf {
+    let name = path.file_name().and_then(|s| s.to_str()).unwrap_or_default();
+    let image_name = match name.strip_suffix(".gz") {
+        Some(name) => format!("{}.img.gz", Path::new(name).file_stem().and_then(|s| s.to_str()).unwrap_or_default()),
+        None => format!("{}.img", path.file_stem().and_then(|s| s.to_str()).unwrap_or_default()),
+    };
+    path.with_file_name(image_name)
+}
+
+
+// Geometry from the header in LPS, the convention of `Image<T>` (and ITK).
+// NIfTI world coordinates are RAS, so x and y change sign.
+fn nd_image<T>(voxels: Vec<T>, header: &NiftiHeader) -> NdImage<T> {
+    let n = header.ndims();
+    let affine = header.affine();
+    let lps = [-1.0, -1.0, 1.0];
+
+    let mut spacing3 = [1.0; 3];
+    let mut direction3 = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+    for axis in 0..3 {
+        let column: [f64; 3] = std::array::from_fn(|row| lps[row] * affine[row][axis]);
+        let length = column.iter().map(|c| c * c).sum::<f64>().sqrt();
+        if length > 0.0 {
+            spacing3[axis] = length;
+            for (j, c) in column.iter().enumerate() {
+                direction3[axis * 3 + j] = c / length;
+            }
+        }
+    }
+    let origin3: [f64; 3] = std::array::from_fn(|row| lps[row] * affine[row][3]);
+
+    // Non-spatial axes (time, ...) get pixdim as spacing and no rotation.
+    let spacing = (0..n).map(|a| match a {
+        0..=2 => spacing3[a],
+        _ if header.pixdim[a + 1] > 0.0 => header.pixdim[a + 1],
+        _ => 1.0,
+    }).collect();
+    let origin = (0..n).map(|a| match a {
+        0..=2 => origin3[a],
+        3 => header.toffset,
+        _ => 0.0,
+    }).collect();
+    let direction = (0..n * n).map(|i| {
+        let (row, col) = (i / n, i % n);
+        if row < 3 && col < 3 { direction3[row * 3 + col] } else if row == col { 1.0 } else { 0.0 }
+    }).collect();
+
+    let mut metadata = Metadata::new();
+    for (key, value) in [("descrip", &header.descrip), ("aux_file", &header.aux_file), ("intent_name", &header.intent_name)] {
+        if !value.is_empty() {
+            metadata.insert(key, value);
+        }
+    }
+    for (key, code) in [("intent_code", header.intent_code), ("qform_code", header.qform_code), ("sform_code", header.sform_code)] {
+        if code != 0 {
+            metadata.insert(key, code.to_string());
+        }
+    }
+
+    NdImage {
+        voxels,
+        size: header.size().iter().map(|&s| s as u32).collect(),
+        spacing,
+        origin,
+        direction,
+        metadata,
+    }
+}
+
+
+// 3D images load as `Image<T>`, anything else as `NdImage<T>`, like MetaImages do.
+fn boxed<T: Pod + NumCast + Copy + 'static>(image: NdImage<T>) -> Box<dyn AnyImage> {
+    if image.ndims() != 3 {
+        return Box::new(image);
+    }
+    match Image::try_from(image) {
+        Ok(image) => Box::new(image),
+        Err(image) => Box::new(image),
+    }
+}
+
+
+// Stored values, or `value * scl_slope + scl_inter` as float when the header scales.
+fn build_image<T: Pod + NumCast + Copy + 'static>(data: Vec<u8>, header: &NiftiHeader) -> Box<dyn AnyImage> {
+    let voxels: Vec<T> = bytes_to_vec(data, header.byte_order_msb);
+    let (slope, inter) = (header.scl_slope, header.scl_inter);
+    let scaled = slope != 0.0 && slope.is_finite() && inter.is_finite() && (slope, inter) != (1.0, 0.0);
+    if !scaled {
+        return boxed(nd_image(voxels, header));
+    }
+
+    // f32 keeps 8 and 16 bit data exact, wider types need f64.
+    let as_f64 = voxels.iter().map(|&v| NumCast::from(v).unwrap_or(f64::NAN) * slope + inter);
+    if std::mem::size_of::<T>() <= 2 || TypeId::of::<T>() == TypeId::of::<f32>() {
+        boxed(nd_image(as_f64.map(|v| v as f32).collect(), header))
+    } else {
+        boxed(nd_image(as_f64.collect(), header))
+    }
+}
+
+
+fn build_rgb_image(data: Vec<u8>, header: &NiftiHeader, channels: u32) -> Result<Box<dyn AnyImage>, NiftiError> {
+    let ndims = header.ndims();
+    let image = Image::try_from(nd_image(data, header)).map_err(|_| NiftiError::Unsupported(
+        format!("RGB data in a {}D image, vector images are at most 3D", ndims)
+    ))?;
+    Ok(Box::new(VectorImage {
+        voxels: image.voxels,
+        channels,
+        width: image.width,
+        height: image.height,
+        depth: image.depth,
+        spacing: image.spacing,
+        origin: image.origin,
+        direction: image.direction,
+        metadata: image.metadata,
+    }))
+}
+
+
+/// Load a NIfTI-1 or NIfTI-2 image from a .nii, .nii.gz or .hdr/.img pair.
+///
+/// Voxel indices map to physical space through the sform, or the qform if
+/// there is no sform, converted from NIfTI's RAS to LPS coordinates.
+/// Images with `scl_slope`/`scl_inter` load scaled as `f32`, or `f64` for
+/// 32 and 64 bit integer and `f64` data. RGB data loads as `VectorImage<u8>`.
+pub fn load_nifti(filename: &str) -> Result<Box<dyn AnyImage>, NiftiError> {
+    let path = Path::new(filename);
+    let bytes = read_file(path)?;
+    let header = NiftiHeader::parse(&bytes)?;
+    let dtype = DataType::from_code(header.datatype)?;
+
+    if header.size().iter().any(|&s| s > u32::MAX as u64) {
+        return Err(NiftiError::Unsupported(format!("size {:?} does not fit u32", header.size())));
+    }
+    let total_bytes = header.size().iter()
+        .try_fold(dtype.bytes() as u64, |bytes, &s| bytes.checked_mul(s))
+        .and_then(|bytes| usize::try_from(bytes).ok())
+        .ok_or_else(|| NiftiError::InvalidHeader(format!("size {:?} is too large", header.size())))?;
+
+    let (data, offset) = if header.single_file {
+        (bytes, header.vox_offset as usize)
+    } else {
+        (read_file(&image_file(path))?, header.vox_offset as usize)
+    };
+    let found = data.len().saturating_sub(offset);
+    if found < total_bytes {
+        return Err(NiftiError::Truncated { expected: total_bytes, found });
+    }
+    let data = data[offset..offset + total_bytes].to_vec();
+
+    Ok(match dtype {
+        DataType::U8 => build_image::<u8>(data, &header),
+        DataType::I8 => build_image::<i8>(data, &header),
+        DataType::U16 => build_image::<u16>(data, &header),
+        DataType::I16 => build_image::<i16>(data, &header),
+        DataType::U32 => build_image::<u32>(data, &header),
+        DataType::I32 => build_image::<i32>(data, &header),
+        DataType::F32 => build_image::<f32>(data, &header),
+        DataType::U64 => build_image::<u64>(data, &header),
+        DataType::I64 => build_image::<i64>(data, &header),
+        DataType::F64 => build_image::<f64>(data, &header),
+        DataType::Rgb24 => build_rgb_image(data, &header, 3)?,
+        DataType::Rgba32 => build_rgb_image(data, &header, 4)?,
+    })
+}
+
+
+// Everything the header writer needs to know about an image besides its voxels.
+struct ImageInfo<'a> {
+    size: Vec<u32>,
+    spacing: &'a [f64],
+    origin: &'a [f64],
+    direction: &'a [f64],
+    metadata: &'a Metadata,
+}
+
+
+fn write_nifti(data: &[u8], dtype: DataType, info: &ImageInfo, file_path: &str, version: Option<NiftiVersion>) -> Result<(), NiftiError> {
+    let gzip = file_path.ends_with(".nii.gz");
+    if !gzip && !file_path.ends_with(".nii") {
+        return Err(NiftiError::Unsupported(format!("{} is not a .nii or .nii.gz file name", file_path)));
+    }
+
+    let n = info.size.len();
+    if !(1..=7).contains(&n) {
+        return Err(NiftiError::Unsupported(format!("{} dimensions, NIfTI stores 1 to 7", n)));
+    }
+    let fits_nifti1 = info.size.iter().all(|&s| s <= i16::MAX as u32);
+    let version = version.unwrap_or(if fits_nifti1 { NiftiVersion::Nifti1 } else { NiftiVersion::Nifti2 });
+    if version == NiftiVersion::Nifti1 && !fits_nifti1 {
+        return Err(NiftiError::Unsupported(format!("size {:?} is too large for NIfTI-1", info.size)));
+    }
+
+    let mut dim = [1i64; 8];
+    dim[0] = n as i64;
+    let mut pixdim = [1.0; 8];
+    for a in 0..n {
+        dim[a + 1] = info.size[a] as i64;
+        pixdim[a + 1] = info.spacing[a];
+    }
+
+    // Codes and text fields come back from the metadata of loaded images.
+    let text = |key: &str| info.metadata.get(key).unwrap_or_default().to_string();
+    let code = |key: &str, default: i32| {
+        info.metadata.get(key).and_then(|v| v.parse().ok()).unwrap_or(default)
+    };
+    let mut header = NiftiHeader {
+        version,
+        byte_order_msb: false,
+        single_file: true,
+        dim,
+        datatype: dtype.code(),
+        bitpix: (dtype.bytes() * 8) as i16,
+        pixdim,
+        vox_offset: 0,
+        scl_slope: 1.0,
+        scl_inter: 0.0,
+        toffset: info.origin.get(3).copied().unwrap_or(0.0),
+        // mm, plus seconds for time series
+        xyzt_units: if n > 3 { 2 | 8 } else { 2 },
+        intent_code: code("intent_code", 0),
+        intent_name: text("intent_name"),
+        descrip: text("descrip"),
+        aux_file: text("aux_file"),
+        qform_code: code("qform_code", 1).max(1),
+        sform_code: code("sform_code", 1).max(1),
+        quatern: [0.0; 3],
+        qoffset: [0.0; 3],
+        srow: [[0.0; 4]; 3],
+    };
+
+    // LPS back to RAS, columns of the affine are the axes scaled by spacing.
+    let lps = [-1.0, -1.0, 1.0];
+    let spacing = padded3(info.spacing, 1.0);
+    let origin = padded3(info.origin, 0.0);
+    let direction = direction3(info.direction, n);
+    header.set_affine(std::array::from_fn(|row| [
+        lps[row] * direction[row] * spacing[0],
+        lps[row] * direction[3 + row] * spacing[1],
+        lps[row] * direction[6 + row] * spacing[2],
+        lps[row] * origin[row],
+    ]));
+    header.vox_offset = header.to_bytes().len() as u64;
+
+    let file = BufWriter::new(File::create(file_path)?);
+    if gzip {
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        encoder.write_all(&header.to_bytes())?;
+        encoder.write_all(data)?;
+        encoder.finish()?.flush()?;
+    } else {
+        let mut file = file;
+        file.write_all(&header.to_bytes())?;
+        file.write_all(data)?;
+        file.flush()?;
+    }
+    Ok(())
+}
+
+
+fn try_save<T: Pod + 'static>(img: &dyn AnyImage, file_path: &str, version: Option<NiftiVersion>) -> Option<Result<(), NiftiError>> {
+    let dtype = DataType::of::<T>()?;
+    let parts = ImageParts::<T>::of(img)?;
+    let info = ImageInfo {
+        size: parts.size,
+        spacing: parts.spacing,
+        origin: parts.origin,
+        direction: parts.direction,
+        metadata: parts.metadata,
+    };
+    if parts.channels == 1 {
+        return Some(write_nifti(&vec_to_bytes(parts.voxels, false), dtype, &info, file_path, version));
+    }
+    let rgb = match (dtype, parts.channels) {
+        (DataType::U8, 3) => DataType::Rgb24,
+        (DataType::U8, 4) => DataType::Rgba32,
+        _ => return Some(Err(NiftiError::Unsupported(
+            "only RGB and RGBA vector images of u8 are supported".into()
+        ))),
+    };
+    Some(write_nifti(bytemuck::cast_slice(parts.voxels), rgb, &info, file_path, version))
+}
+
+
+/// Save as a single .nii (or gzipped .nii.gz) file. NIfTI-1 unless the
+/// image is too large for it, then NIfTI-2.
+pub fn save_nifti(img: &dyn AnyImage, file_path: &str) -> Result<(), NiftiError> {
+    save_nifti_impl(img, file_path, None)
+}
+
+
+/// Like `save_nifti`, writing the requested header version.
+pub fn save_nifti_with_version(img: &dyn AnyImage, file_path: &str, version: NiftiVersion) -> Result<(), NiftiError> {
+    save_nifti_impl(img, file_path, Some(version))
+}
+
+
+fn save_nifti_impl(img: &dyn AnyImage, file_path: &str, version: Option<NiftiVersion>) -> Result<(), NiftiError> {
+    first_pixel_type!(try_save(img, file_path, version))
+        .unwrap_or_else(|| Err(NiftiError::Unsupported("unsupported pixel type".into())))
+}
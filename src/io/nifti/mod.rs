@@ -0,0 +1,7 @@
+pub mod error;
+pub mod header;
+pub mod image;
+
+pub use error::NiftiError;
+pub use header::NiftiVersion;
+pub use image::{load_nifti, save_nifti, save_nifti_with_version};
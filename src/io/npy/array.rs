@@ -0,0 +1,190 @@
+use std::io::Write;
+
+use super::error::NpyError;
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ElementType {
+    I8, U8,
+    I16, U16,
+    I32, U32, F32,
+    I64, U64, F64,
+}
+
+impl ElementType {
+    pub(super) fn bytes(self) -> usize {
+        use ElementType::*;
+        match self {
+            U8 | I8 => 1,
+            U16 | I16 => 2,
+            U32 | I32 | F32 => 4,
+            U64 | I64 | F64 => 8,
+        }
+    }
+
+    // Kind and size part of a dtype string, e.g. "f4".
+    pub(super) fn code(self) -> &'static str {
+        use ElementType::*;
+        match self {
+            I8 => "i1", U8 => "u1",
+            I16 => "i2", U16 => "u2",
+            I32 => "i4", U32 => "u4", F32 => "f4",
+            I64 => "i8", U64 => "u8", F64 => "f8",
+        }
+    }
+}
+
+
+// Element type and byte order of a dtype string such as "<f4" or "|u1".
+fn parse_descr(descr: &str) -> Result<(ElementType, bool), NpyError> {
+    use ElementType::*;
+    let unsupported = || NpyError::UnsupportedDType(descr.to_string());
+    let (order, code) = descr.split_at_checked(1).ok_or_else(unsupported)?;
+    let msb = match order {
+        "<" | "|" => false,
+        ">" => true,
+        "=" => cfg!(target_endian = "big"),
+        _ => return Err(unsupported()),
+    };
+    let etype = match code {
+        // NumPy bools are one byte per element, they load as u8 0/1.
+        "b1" | "u1" => U8,
+        "i1" => I8,
+        "i2" => I16,
+        "u2" => U16,
+        "i4" => I32,
+        "u4" => U32,
+        "f4" => F32,
+        "i8" => I64,
+        "u8" => U64,
+        "f8" => F64,
+        _ => return Err(unsupported()),
+    };
+    Ok((etype, msb))
+}
+
+
+// A parsed .npy file, the data is still in the file's byte order but always
+// in C order.
+#[derive(Debug)]
+pub(super) struct Array {
+    pub etype: ElementType,
+    pub byte_order_msb: bool,
+    pub shape: Vec<usize>,
+    pub data: Vec<u8>,
+}
+
+
+// Value following `'key':` in the header dict, up to the next top-level comma.
+fn dict_value<'a>(header: &'a str, key: &str) -> Result<&'a str, NpyError> {
+    let missing = || NpyError::InvalidHeader(format!("no '{}' in {}", key, header));
+    let start = header.find(&format!("'{}'", key)).ok_or_else(missing)? + key.len() + 2;
+    let rest = header[start..].trim_start().strip_prefix(':').ok_or_else(missing)?.trim_start();
+    let end = if rest.starts_with('(') {
+        rest.find(')').map(|i| i + 1)
+    } else {
+        rest.find([',', '}'])
+    };
+    Ok(rest[..end.unwrap_or(rest.len())].trim())
+}
+
+
+pub(super) fn parse_npy(mut bytes: Vec<u8>) -> Result<Array, NpyError> {
+    if !bytes.starts_with(MAGIC) || bytes.len() < 10 {
+        return Err(NpyError::InvalidHeader("missing \\x93NUMPY magic".into()));
+    }
+    // Version 1.0 has a 2 byte header length, 2.0 and 3.0 a 4 byte one.
+    let (header_start, header_len) = match bytes[6] {
+        1 => (10, u16::from_le_bytes([bytes[8], bytes[9]]) as usize),
+        2 | 3 if bytes.len() >= 12 => (12, u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize),
+        version => return Err(NpyError::InvalidHeader(format!("unknown version {}", version))),
+    };
+    let data_start = header_start + header_len;
+    if bytes.len() < data_start {
+        return Err(NpyError::Truncated { expected: data_start, found: bytes.len() });
+    }
+    let header = String::from_utf8_lossy(&bytes[header_start..data_start]).to_string();
+
+    let descr = dict_value(&header, "descr")?;
+    let descr = descr.strip_prefix('\'').and_then(|d| d.strip_suffix('\''))
+        .ok_or_else(|| NpyError::UnsupportedDType(descr.to_string()))?;
+    let (etype, byte_order_msb) = parse_descr(descr)?;
+    let fortran_order = match dict_value(&header, "fortran_order")? {
+        "True" => true,
+        "False" => false,
+        other => return Err(NpyError::InvalidHeader(format!("fortran_order is {}", other))),
+    };
+    let shape = dict_value(&header, "shape")?;
+    let shape = shape.strip_prefix('(').and_then(|s| s.strip_suffix(')'))
+        .ok_or_else(|| NpyError::InvalidHeader(format!("shape is {}", shape)))?
+        .split(',')
+        .map(|s| s.trim().trim_end_matches('L'))
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<usize>().map_err(|_| NpyError::InvalidHeader(format!("shape is {}", shape))))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let expected = shape.iter()
+        .try_fold(etype.bytes(), |bytes, &s| bytes.checked_mul(s))
+        .ok_or_else(|| NpyError::InvalidHeader(format!("shape {:?} is too large", shape)))?;
+    let found = bytes.len() - data_start;
+    if found < expected {
+        return Err(NpyError::Truncated { expected, found });
+    }
+    bytes.truncate(data_start + expected);
+    bytes.drain(..data_start);
+    let data = if fortran_order { fortran_to_c(&bytes, &shape, etype.bytes()) } else { bytes };
+    Ok(Array { etype, byte_order_msb, shape, data })
+}
+
+
+// Reorder Fortran order data (first axis fastest) to C order (last axis
+// fastest), so `np.asfortranarray(a)` loads the same as `a`.
+fn fortran_to_c(data: &[u8], shape: &[usize], element_bytes: usize) -> Vec<u8> {
+    let mut strides = vec![element_bytes; shape.len()];
+    for axis in 1..shape.len() {
+        strides[axis] = strides[axis - 1] * shape[axis - 1];
+    }
+    let mut out = Vec::with_capacity(data.len());
+    let mut index = vec![0; shape.len()];
+    for _ in 0..data.len() / element_bytes {
+        let offset: usize = index.iter().zip(&strides).map(|(i, s)| i * s).sum();
+        out.extend_from_slice(&data[offset..offset + element_bytes]);
+        for axis in (0..shape.len()).rev() {
+            index[axis] += 1;
+            if index[axis] < shape[axis] {
+                break;
+            }
+            index[axis] = 0;
+        }
+    }
+    out
+}
+
+
+/// Write a little-endian, C order .npy array.
+pub(super) fn write_npy<W: Write>(writer: &mut W, etype: ElementType, shape: &[usize], data: &[u8]) -> std::io::Result<()> {
+    let order = if etype.bytes() == 1 { '|' } else { '<' };
+    let shape = match shape {
+        [single] => format!("({},)", single),
+        _ => format!("({})", shape.iter().map(|s| s.to_string()).collect::<Vec<_>>().join(", ")),
+    };
+    let mut header = format!("{{'descr': '{}{}', 'fortran_order': False, 'shape': {}, }}", order, etype.code(), shape);
+
+    // Pad with spaces and a newline so the data starts 64 byte aligned.
+    let version: u8 = if header.len() + 11 > u16::MAX as usize { 2 } else { 1 };
+    let prefix = if version == 1 { 10 } else { 12 };
+    let padding = (64 - (prefix + header.len() + 1) % 64) % 64;
+    header.push_str(&" ".repeat(padding));
+    header.push('\n');
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[version, 0])?;
+    if version == 1 {
+        writer.write_all(&(header.len() as u16).to_le_bytes())?;
+    } else {
+        writer.write_all(&(header.len() as u32).to_le_bytes())?;
+    }
+    writer.write_all(header.as_bytes())?;
+    writer.write_all(data)
+}
@@ -0,0 +1,48 @@
+#[derive(Debug)]
+pub enum NpyError {
+    Io(std::io::Error),     // Generic I/O error (file issue)
+    InvalidHeader(String),  // Not a .npy file, or a header we can not parse
+    UnsupportedDType(String),
+    Unsupported(String),
+    Truncated { expected: usize, found: usize },
+    MissingArray(String),   // .npz archive without the voxel array
+    InvalidGeometry { name: String, expected: usize, found: usize },
+    Zip(zip::result::ZipError),
+}
+
+impl std::fmt::Display for NpyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NpyError::Io(e) => write!(f, "I/O error: {}", e),
+            NpyError::InvalidHeader(what) => write!(f, "Invalid .npy header: {}", what),
+            NpyError::UnsupportedDType(descr) => write!(f, "Unsupported dtype: {}", descr),
+            NpyError::Unsupported(what) => write!(f, "Unsupported array: {}", what),
+            NpyError::Truncated { expected, found } => {
+                write!(f, "Truncated array data: expected {} bytes, found {}", expected, found)
+            }
+            NpyError::MissingArray(name) => write!(f, "Archive has no array named '{}'", name),
+            NpyError::InvalidGeometry { name, expected, found } => {
+                write!(f, "Array '{}' has {} values, the image needs {}", name, found, expected)
+            }
+            NpyError::Zip(e) => write!(f, "Invalid .npz archive: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for NpyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NpyError::Io(e) => Some(e),
+            NpyError::Zip(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for NpyError {
+    fn from(e: std::io::Error) -> Self { NpyError::Io(e) }
+}
+
+impl From<zip::result::ZipError> for NpyError {
+    fn from(e: zip::result::ZipError) -> Self { NpyError::Zip(e) }
+}
@@ -0,0 +1,308 @@
+use crate::image::{Image, NdImage, VectorImage, AnyImage, Metadata};
+use crate::image::dyn_image::{first_pixel_type, ImageParts};
+use crate::io::meta_image::image::{bytes_to_vec, vec_to_bytes};
+
+use super::array::{Array, ElementType, parse_npy, write_npy};
+use super::error::NpyError;
+use std::any::TypeId;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use bytemuck::Pod;
+use num_traits::NumCast;
+
+// Array names in .npz files written by `save_npz`, as in `np.savez(path, image=..., spacing=...)`.
+const IMAGE: &str = "image";
+const SPACING: &str = "spacing";
+const ORIGIN: &str = "origin";
+const DIRECTION: &str = "direction";
+
+
+// Geometry arrays of an .npz file, `None` for arrays that are not in the archive.
+#[derive(Debug, Default)]
+struct Geometry {
+    spacing: Option<Vec<f64>>,
+    origin: Option<Vec<f64>>,
+    direction: Option<Vec<f64>>,
+}
+
+impl Geometry {
+    // Number of spatial dimensions the arrays describe.
+    fn ndims(&self) -> Option<usize> {
+        self.spacing.as_ref().or(self.origin.as_ref()).map(|v| v.len())
+            .or_else(|| self.direction.as_ref().map(|d| (d.len() as f64).sqrt().round() as usize))
+    }
+}
+
+
+// Image size with the fastest varying axis first, i.e. the shape reversed.
+fn size_of(array: &Array) -> Vec<usize> {
+    array.shape.iter().rev().copied().collect()
+}
+
+
+fn check_length(name: &str, values: &[f64], expected: usize) -> Result<(), NpyError> {
+    if values.len() != expected {
+        return Err(NpyError::InvalidGeometry { name: name.to_string(), expected, found: values.len() });
+    }
+    Ok(())
+}
+
+
+fn build_image<T>(array: Array, geometry: &Geometry) -> Result<Box<dyn AnyImage>, NpyError>
+where
+    T: Pod + NumCast + Copy + 'static,
+{
+    let mut size = size_of(&array);
+    let voxels: Vec<T> = bytes_to_vec(array.data, array.byte_order_msb);
+    if size.is_empty() {
+        return Err(NpyError::Unsupported("0-dimensional array".into()));
+    }
+
+    // One axis more than the geometry describes holds the channels, stored fastest.
+    let channels = match geometry.ndims() {
+        Some(n) if size.len() == n + 1 => size.remove(0),
+        _ => 1,
+    };
+    let n = size.len();
+
+    let mut direction = vec![0.0; n * n];
+    (0..n).for_each(|i| direction[i * n + i] = 1.0);
+    if let Some(d) = &geometry.direction {
+        check_length(DIRECTION, d, n * n)?;
+        direction.clone_from(d);
+    }
+    let spacing = geometry.spacing.clone().unwrap_or_else(|| vec![1.0; n]);
+    check_length(SPACING, &spacing, n)?;
+    let origin = geometry.origin.clone().unwrap_or_else(|| vec![0.0; n]);
+    check_length(ORIGIN, &origin, n)?;
+
+    let image = NdImage { voxels, size: size.iter().map(|&s| s as u32).collect(), spacing, origin, direction, metadata: Metadata::default() };
+
+    if channels > 1 {
+        let image = Image::try_from(image).map_err(|_| NpyError::Unsupported(
+            format!("{} channels in a {}D image, vector images are at most 3D", channels, n)
+        ))?;
+        return Ok(Box::new(VectorImage {
+            voxels: image.voxels,
+            channels: channels as u32,
+            width: image.width,
+            height: image.height,
+            depth: image.depth,
+            spacing: image.spacing,
+            origin: image.origin,
+            direction: image.direction,
+            metadata: image.metadata,
+        }));
+    }
+
+    // 3D arrays load as `Image<T>`, anything else stays N-dimensional.
+    if n != 3 {
+        return Ok(Box::new(image));
+    }
+    match Image::try_from(image) {
+        Ok(image) => Ok(Box::new(image)),
+        Err(image) => Ok(Box::new(image)),
+    }
+}
+
+
+fn to_image(array: Array, geometry: &Geometry) -> Result<Box<dyn AnyImage>, NpyError> {
+    match array.etype {
+        ElementType::U8 => build_image::<u8>(array, geometry),
+        ElementType::I8 => build_image::<i8>(array, geometry),
+        ElementType::U16 => build_image::<u16>(array, geometry),
+        ElementType::I16 => build_image::<i16>(array, geometry),
+        ElementType::U32 => build_image::<u32>(array, geometry),
+        ElementType::I32 => build_image::<i32>(array, geometry),
+        ElementType::F32 => build_image::<f32>(array, geometry),
+        ElementType::U64 => build_image::<u64>(array, geometry),
+        ElementType::I64 => build_image::<i64>(array, geometry),
+        ElementType::F64 => build_image::<f64>(array, geometry),
+    }
+}
+
+
+fn cast_f64<T: Pod + NumCast>(array: Array) -> Vec<f64> {
+    bytes_to_vec::<T>(array.data, array.byte_order_msb).into_iter()
+        .map(|v| NumCast::from(v).unwrap_or(f64::NAN))
+        .collect()
+}
+
+
+// Values of a geometry array as f64, a direction matrix in C order.
+fn to_f64(array: Array) -> Vec<f64> {
+    match array.etype {
+        ElementType::U8 => cast_f64::<u8>(array),
+        ElementType::I8 => cast_f64::<i8>(array),
+        ElementType::U16 => cast_f64::<u16>(array),
+        ElementType::I16 => cast_f64::<i16>(array),
+        ElementType::U32 => cast_f64::<u32>(array),
+        ElementType::I32 => cast_f64::<i32>(array),
+        ElementType::F32 => cast_f64::<f32>(array),
+        ElementType::U64 => cast_f64::<u64>(array),
+        ElementType::I64 => cast_f64::<i64>(array),
+        ElementType::F64 => cast_f64::<f64>(array),
+    }
+}
+
+
+/// Load a NumPy .npy array.
+///
+/// NumPy arrays index slowest axis first, so a C order array of shape
+/// `(z, y, x)` loads with `width = x`, as `SimpleITK.GetArrayFromImage`
+/// lays it out. Fortran order arrays are reordered to C order first, so
+/// `np.asfortranarray(a)` loads the same as `a`. 3D arrays load as
+/// `Image<T>`, others as `NdImage<T>`, booleans as `u8`.
+/// .npy files have no geometry, spacing is 1 and the origin 0.
+pub fn load_npy(filename: &str) -> Result<Box<dyn AnyImage>, NpyError> {
+    let mut bytes = Vec::new();
+    BufReader::new(File::open(filename)?).read_to_end(&mut bytes)?;
+    to_image(parse_npy(bytes)?, &Geometry::default())
+}
+
+
+/// Load a NumPy .npz archive with the voxels in an `image` array (or the
+/// archive's only array) and optional `spacing`, `origin` and `direction`
+/// arrays. `spacing` and `origin` are in x, y, z order, the rows of
+/// `direction` are the axis directions as in `Image::direction`.
+///
+/// If the voxel array has one dimension more than the geometry, its
+/// fastest axis holds the channels of a `VectorImage`.
+pub fn load_npz(filename: &str) -> Result<Box<dyn AnyImage>, NpyError> {
+    let mut archive = ZipArchive::new(BufReader::new(File::open(filename)?))?;
+
+    let mut read_array = |name: &str| -> Result<Option<Array>, NpyError> {
+        let mut entry = match archive.by_name(&format!("{}.npy", name)) {
+            Ok(entry) => entry,
+            Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut bytes = Vec::new();
+        entry.read_to_end(&mut bytes)?;
+        parse_npy(bytes).map(Some)
+    };
+
+    let geometry = Geometry {
+        spacing: read_array(SPACING)?.map(to_f64),
+        origin: read_array(ORIGIN)?.map(to_f64),
+        direction: read_array(DIRECTION)?.map(to_f64),
+    };
+
+    let image = match read_array(IMAGE)? {
+        Some(array) => array,
+        None => {
+            // e.g. `np.savez(path, volume)`, stored as arr_0.
+            let others: Vec<String> = archive.file_names()
+                .filter_map(|name| name.strip_suffix(".npy"))
+                .filter(|name| ![SPACING, ORIGIN, DIRECTION].contains(name))
+                .map(String::from)
+                .collect();
+            match others.as_slice() {
+                [name] => {
+                    let mut entry = archive.by_name(&format!("{}.npy", name))?;
+                    let mut bytes = Vec::new();
+                    entry.read_to_end(&mut bytes)?;
+                    parse_npy(bytes)?
+                }
+                _ => return Err(NpyError::MissingArray(IMAGE.to_string())),
+            }
+        }
+    };
+    to_image(image, &geometry)
+}
+
+
+fn element_type<T: 'static>() -> Option<ElementType> {
+    let tid = TypeId::of::<T>();
+    [
+        (TypeId::of::<u8>(), ElementType::U8), (TypeId::of::<i8>(), ElementType::I8),
+        (TypeId::of::<u16>(), ElementType::U16), (TypeId::of::<i16>(), ElementType::I16),
+        (TypeId::of::<u32>(), ElementType::U32), (TypeId::of::<i32>(), ElementType::I32),
+        (TypeId::of::<f32>(), ElementType::F32),
+        (TypeId::of::<u64>(), ElementType::U64), (TypeId::of::<i64>(), ElementType::I64),
+        (TypeId::of::<f64>(), ElementType::F64),
+    ].into_iter().find(|(t, _)| *t == tid).map(|(_, etype)| etype)
+}
+
+
+// An image flattened into what the .npy and .npz writers need.
+struct Encoded<'a> {
+    etype: ElementType,
+    shape: Vec<usize>,
+    data: Vec<u8>,
+    spacing: &'a [f64],
+    origin: &'a [f64],
+    direction: &'a [f64],
+}
+
+
+fn encode<'a, T: Pod + 'static>(voxels: &[T], size: &[u32], channels: u32, spacing: &'a [f64], origin: &'a [f64], direction: &'a [f64]) -> Encoded<'a> {
+    // C order, slowest axis first and the channels last.
+    let mut shape: Vec<usize> = size.iter().rev().map(|&s| s as usize).collect();
+    if channels > 1 {
+        shape.push(channels as usize);
+    }
+    Encoded {
+        etype: element_type::<T>().unwrap(),
+        shape,
+        data: vec_to_bytes(voxels, false).into_owned(),
+        spacing,
+        origin,
+        direction,
+    }
+}
+
+
+fn try_encode<T: Pod + 'static>(img: &dyn AnyImage) -> Option<Encoded<'_>> {
+    element_type::<T>()?;
+    let parts = ImageParts::<T>::of(img)?;
+    Some(encode(parts.voxels, &parts.size, parts.channels, parts.spacing, parts.origin, parts.direction))
+}
+
+
+fn encode_image(img: &dyn AnyImage) -> Result<Encoded<'_>, NpyError> {
+    first_pixel_type!(try_encode(img))
+        .ok_or_else(|| NpyError::UnsupportedDType("unsupported pixel type".into()))
+}
+
+
+/// Save as a little-endian, C order .npy array of shape `(z, y, x)`, or
+/// `(z, y, x, channels)` for a `VectorImage`. The geometry is not stored,
+/// use `save_npz` to keep it.
+pub fn save_npy(img: &dyn AnyImage, file_path: &str) -> Result<(), NpyError> {
+    let encoded = encode_image(img)?;
+    let mut file = BufWriter::new(File::create(file_path)?);
+    write_npy(&mut file, encoded.etype, &encoded.shape, &encoded.data)?;
+    file.flush()?;
+    Ok(())
+}
+
+
+/// Save as an .npz archive with `image`, `spacing`, `origin` and
+/// `direction` arrays, readable with `np.load`. `compress` deflates the
+/// arrays like `np.savez_compressed`.
+pub fn save_npz(img: &dyn AnyImage, file_path: &str, compress: bool) -> Result<(), NpyError> {
+    let encoded = encode_image(img)?;
+    let n = encoded.spacing.len();
+
+    let method = if compress { CompressionMethod::Deflated } else { CompressionMethod::Stored };
+    let mut zip = ZipWriter::new(BufWriter::new(File::create(file_path)?));
+    let arrays = [
+        (IMAGE, encoded.shape.clone(), encoded.etype, encoded.data),
+        (SPACING, vec![n], ElementType::F64, vec_to_bytes(encoded.spacing, false).into_owned()),
+        (ORIGIN, vec![n], ElementType::F64, vec_to_bytes(encoded.origin, false).into_owned()),
+        (DIRECTION, vec![n, n], ElementType::F64, vec_to_bytes(encoded.direction, false).into_owned()),
+    ];
+    for (name, shape, etype, data) in arrays {
+        let options = SimpleFileOptions::default()
+            .compression_method(method)
+            .large_file(data.len() as u64 >= u32::MAX as u64);
+        zip.start_file(format!("{}.npy", name), options)?;
+        write_npy(&mut zip, etype, &shape, &data)?;
+    }
+    zip.finish()?.flush()?;
+    Ok(())
+}
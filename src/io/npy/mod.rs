@@ -0,0 +1,6 @@
+pub mod array;
+pub mod error;
+pub mod image;
+
+pub use error::NpyError;
+pub use image::{load_npy, load_npz, save_npy, save_npz};
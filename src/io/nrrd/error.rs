@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum NrrdError {
+    Missing(&'static str),  // Missing fields that we need
+    Io(std::io::Error),     // Generic I/O error (file issue)
+    InvalidHeader(String),  // Not a NRRD file
+    InvalidValue { field: String, value: String, line: usize },
+    UnsupportedType(String),
+    Unsupported(String),
+    Truncated { expected: usize, found: usize },
+    MissingValues { expected: usize, found: usize },
+    Decompression(std::io::Error),
+    MissingDataFile(PathBuf),
+}
+
+impl std::fmt::Display for NrrdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NrrdError::Missing(field) => write!(f, "Missing NRRD field: {}", field),
+            NrrdError::Io(e) => write!(f, "I/O error: {}", e),
+            NrrdError::InvalidHeader(what) => write!(f, "Invalid NRRD header: {}", what),
+            NrrdError::InvalidValue { field, value, line } => {
+                write!(f, "Invalid value for {} on line {}: '{}'", field, line, value)
+            }
+            NrrdError::UnsupportedType(t) => write!(f, "Unsupported NRRD type: {}", t),
+            NrrdError::Unsupported(what) => write!(f, "Unsupported NRRD: {}", what),
+            NrrdError::Truncated { expected, found } => {
+                write!(f, "Truncated voxel data: expected {} bytes, found {}", expected, found)
+            }
+            NrrdError::MissingValues { expected, found } => {
+                write!(f, "ASCII voxel data has {} values, expected {}", found, expected)
+            }
+            NrrdError::Decompression(e) => write!(f, "Failed to decompress voxel data: {}", e),
+            NrrdError::MissingDataFile(path) => write!(f, "Data file not found: {}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for NrrdError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NrrdError::Io(e) | NrrdError::Decompression(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for NrrdError {
+    fn from(e: std::io::Error) -> Self { NrrdError::Io(e) }
+}
@@ -0,0 +1,292 @@
+use std::io::BufRead;
+use std::str::FromStr;
+
+use crate::image::Metadata;
+
+use super::error::NrrdError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ElementType {
+    I8, U8,
+    I16, U16,
+    I32, U32, F32,
+    I64, U64, F64,
+}
+
+impl FromStr for ElementType {
+    type Err = NrrdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use ElementType::*;
+        match s {
+            "signed char" | "int8" | "int8_t" => Ok(I8),
+            "uchar" | "unsigned char" | "uint8" | "uint8_t" => Ok(U8),
+            "short" | "short int" | "signed short" | "signed short int" | "int16" | "int16_t" => Ok(I16),
+            "ushort" | "unsigned short" | "unsigned short int" | "uint16" | "uint16_t" => Ok(U16),
+            "int" | "signed int" | "int32" | "int32_t" => Ok(I32),
+            "uint" | "unsigned int" | "uint32" | "uint32_t" => Ok(U32),
+            "longlong" | "long long" | "long long int" | "signed long long" | "signed long long int"
+            | "int64" | "int64_t" => Ok(I64),
+            "ulonglong" | "unsigned long long" | "unsigned long long int" | "uint64" | "uint64_t" => Ok(U64),
+            "float" => Ok(F32),
+            "double" => Ok(F64),
+            _ => Err(NrrdError::UnsupportedType(s.into())),
+        }
+    }
+}
+
+impl ElementType {
+    pub(super) fn bytes(self) -> usize {
+        use ElementType::*;
+        match self {
+            U8 | I8 => 1,
+            U16 | I16 => 2,
+            U32 | I32 | F32 => 4,
+            U64 | I64 | F64 => 8,
+        }
+    }
+}
+
+
+/// How the voxel data is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NrrdEncoding {
+    #[default]
+    Raw,
+    Gzip,
+    /// Whitespace separated text.
+    Ascii,
+}
+
+impl FromStr for NrrdEncoding {
+    type Err = NrrdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "raw" => Ok(NrrdEncoding::Raw),
+            "gzip" | "gz" => Ok(NrrdEncoding::Gzip),
+            "ascii" | "text" | "txt" => Ok(NrrdEncoding::Ascii),
+            _ => Err(NrrdError::Unsupported(format!("{} encoding", s))),
+        }
+    }
+}
+
+
+// Parsed header, per-axis fields hold `dimension` values.
+#[derive(Debug, Clone)]
+pub(super) struct Header {
+    pub element_type: ElementType,
+    pub dimension: usize,
+    pub sizes: Vec<u32>,
+    pub encoding: NrrdEncoding,
+    pub byte_order_msb: bool,
+    // Per space axis factor taking coordinates to LPS, empty without a `space`.
+    pub space_to_lps: Vec<f64>,
+    // `None` for non-spatial axes, e.g. vector components or time.
+    pub space_directions: Option<Vec<Option<Vec<f64>>>>,
+    pub space_origin: Option<Vec<f64>>,
+    pub spacings: Option<Vec<f64>>,
+    pub kinds: Option<Vec<String>>,
+    pub data_file: Option<String>,
+    pub line_skip: usize,
+    pub byte_skip: i64,
+    // Line the data starts on, before `line skip`: after the header for attached data, 1 in a data file.
+    pub data_line: usize,
+    // key:=value pairs, in file order.
+    pub metadata: Metadata,
+}
+
+
+fn invalid_value(field: &str, value: &str, line: usize) -> NrrdError {
+    NrrdError::InvalidValue { field: field.to_string(), value: value.to_string(), line }
+}
+
+
+fn parse_value<T: FromStr>(field: &str, value: &str, line: usize) -> Result<T, NrrdError> {
+    value.parse().map_err(|_| invalid_value(field, value, line))
+}
+
+
+// Exactly `count` whitespace separated values, one per axis.
+fn parse_list<T: FromStr>(field: &str, value: &str, line: usize, count: usize) -> Result<Vec<T>, NrrdError> {
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    if parts.len() != count {
+        return Err(invalid_value(field, value, line));
+    }
+    parts.into_iter().map(|part| parse_value(field, part, line)).collect()
+}
+
+
+// "(1,0,0)" or "( 1, 0, 0 )".
+fn parse_vector(field: &str, value: &str, line: usize) -> Result<Vec<f64>, NrrdError> {
+    let inner = value.trim().strip_prefix('(').and_then(|v| v.strip_suffix(')'))
+        .ok_or_else(|| invalid_value(field, value, line))?;
+    inner.split(',').map(|c| parse_value(field, c.trim(), line)).collect()
+}
+
+
+// "(1,0,0) none (0,0,2)", `none` marks axes without a direction.
+fn parse_vectors(field: &str, value: &str, line: usize, count: usize) -> Result<Vec<Option<Vec<f64>>>, NrrdError> {
+    let mut vectors = Vec::new();
+    let mut rest = value.trim();
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("none") {
+            vectors.push(None);
+            rest = after.trim_start();
+        } else {
+            let end = rest.find(')').ok_or_else(|| invalid_value(field, value, line))?;
+            vectors.push(Some(parse_vector(field, &rest[..=end], line)?));
+            rest = rest[end + 1..].trim_start();
+        }
+    }
+    if vectors.len() != count {
+        return Err(invalid_value(field, value, line));
+    }
+    Ok(vectors)
+}
+
+
+// Sign of each space axis relative to LPS, the convention of `Image<T>`.
+fn space_to_lps(field: &str, value: &str, line: usize) -> Result<Vec<f64>, NrrdError> {
+    let lps = match value.to_lowercase().as_str() {
+        "left-posterior-superior" | "lps" => vec![1.0, 1.0, 1.0],
+        "right-anterior-superior" | "ras" => vec![-1.0, -1.0, 1.0],
+        "left-anterior-superior" | "las" => vec![1.0, -1.0, 1.0],
+        "left-posterior-superior-time" | "lpst" => vec![1.0, 1.0, 1.0, 1.0],
+        "right-anterior-superior-time" | "rast" => vec![-1.0, -1.0, 1.0, 1.0],
+        "left-anterior-superior-time" | "last" => vec![1.0, -1.0, 1.0, 1.0],
+        // No anatomical meaning, keep coordinates as they are.
+        "scanner-xyz" | "3d-right-handed" | "3d-left-handed" => vec![1.0; 3],
+        "scanner-xyz-time" | "3d-right-handed-time" | "3d-left-handed-time" => vec![1.0; 4],
+        _ => return Err(invalid_value(field, value, line)),
+    };
+    Ok(lps)
+}
+
+
+// The dimension precedes every per-axis field, we need it to validate their value counts.
+fn require_dimension(dimension: Option<usize>) -> Result<usize, NrrdError> {
+    dimension.ok_or(NrrdError::Missing("dimension"))
+}
+
+
+pub(super) fn parse_header<R: BufRead>(reader: &mut R) -> Result<Header, NrrdError> {
+    let mut line_buffer = String::new();
+    reader.read_line(&mut line_buffer)?;
+    if !line_buffer.starts_with("NRRD000") {
+        return Err(NrrdError::InvalidHeader("missing NRRD000X magic".into()));
+    }
+
+    let mut element_type = None;
+    let mut dimension = None;
+    let mut sizes = None;
+    let mut encoding = None;
+    let mut endian = None;
+    let mut space_lps = Vec::new();
+    let mut space_directions = None;
+    let mut space_origin = None;
+    let mut spacings = None;
+    let mut kinds = None;
+    let mut data_file = None;
+    let mut line_skip = 0;
+    let mut byte_skip = 0;
+    let mut metadata = Metadata::new();
+
+    let mut line = 1;
+    loop {
+        line_buffer.clear();
+        reader.read_line(&mut line_buffer)?;
+        line += 1;
+
+        let text = line_buffer.trim_end_matches(['\r', '\n']);
+        if text.is_empty() {
+            break; // blank line (or EOF) ends the header
+        }
+        if text.starts_with('#') {
+            continue;
+        }
+
+        if let Some((key, value)) = text.split_once(":=") {
+            metadata.insert(key, value.replace("\\n", "\n"));
+            continue;
+        }
+        let (field, value) = text.split_once(": ")
+            .or_else(|| text.split_once(':'))
+            .ok_or_else(|| NrrdError::InvalidHeader(format!("line {} is not a field: '{}'", line, text)))?;
+        let field = field.to_lowercase();
+        let value = value.trim();
+
+        match field.as_str() {
+            "type" => element_type = Some(value.parse::<ElementType>()?),
+            "dimension" => dimension = Some(parse_value::<usize>(&field, value, line)?),
+            "sizes" => sizes = Some(parse_list(&field, value, line, require_dimension(dimension)?)?),
+            "encoding" => encoding = Some(value.parse::<NrrdEncoding>()?),
+            "endian" => endian = Some(match value {
+                "little" => false,
+                "big" => true,
+                _ => return Err(invalid_value(&field, value, line)),
+            }),
+            "space" => space_lps = space_to_lps(&field, value, line)?,
+            "space dimension" => space_lps = vec![1.0; parse_value::<usize>(&field, value, line)?],
+            "space directions" => {
+                space_directions = Some(parse_vectors(&field, value, line, require_dimension(dimension)?)?);
+            }
+            "space origin" => space_origin = Some(parse_vector(&field, value, line)?),
+            "spacings" => {
+                // "nan" for axes without a spacing.
+                spacings = Some(parse_list(&field, value, line, require_dimension(dimension)?)?);
+            }
+            "kinds" => kinds = Some(parse_list(&field, value, line, require_dimension(dimension)?)?),
+            "data file" | "datafile" => {
+                let parts: Vec<&str> = value.split_whitespace().collect();
+                if value.starts_with("LIST") || (parts.len() > 2 && parts[0].contains('%')) {
+                    return Err(NrrdError::Unsupported("data split over multiple files".into()));
+                }
+                data_file = Some(value.to_string());
+            }
+            "line skip" | "lineskip" => line_skip = parse_value(&field, value, line)?,
+            "byte skip" | "byteskip" => {
+                byte_skip = parse_value(&field, value, line)?;
+                if byte_skip < -1 {
+                    return Err(invalid_value(&field, value, line));
+                }
+            }
+            // Fields we have no place for, e.g. content, thicknesses or units.
+            _ => {}
+        }
+    }
+
+    let element_type = element_type.ok_or(NrrdError::Missing("type"))?;
+    let encoding = encoding.ok_or(NrrdError::Missing("encoding"))?;
+    // Single bytes and text have no byte order.
+    let byte_order_msb = match endian {
+        Some(msb) => msb,
+        None if element_type.bytes() == 1 || encoding == NrrdEncoding::Ascii => false,
+        None => return Err(NrrdError::Missing("endian")),
+    };
+    for vector in space_directions.iter().flatten().flatten().chain(&space_origin) {
+        if vector.len() != space_lps.len() {
+            return Err(NrrdError::InvalidHeader(format!(
+                "vector {:?} does not match the space dimension {}", vector, space_lps.len()
+            )));
+        }
+    }
+
+    Ok(Header {
+        element_type,
+        dimension: require_dimension(dimension)?,
+        sizes: sizes.ok_or(NrrdError::Missing("sizes"))?,
+        encoding,
+        byte_order_msb,
+        space_to_lps: space_lps,
+        space_directions,
+        space_origin,
+        spacings,
+        kinds,
+        data_line: if data_file.is_some() { 1 } else { line + 1 },
+        data_file,
+        line_skip,
+        byte_skip,
+        metadata,
+    })
+}
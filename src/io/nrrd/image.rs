@@ -0,0 +1,397 @@
+use crate::image::{Image, NdImage, VectorImage, AnyImage, Metadata};
+use crate::image::dyn_image::{first_pixel_type, ImageParts};
+use crate::io::meta_image::image::{bytes_to_vec, vec_to_bytes};
+
+use super::error::NrrdError;
+use super::header::{ElementType, Header, NrrdEncoding, parse_header};
+use std::any::TypeId;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::fmt::Display;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use bytemuck::Pod;
+use num_traits::NumCast;
+
+
+// Kinds of axes that hold the components of a voxel rather than a position.
+fn is_component_axis(header: &Header) -> bool {
+    if header.dimension < 2 {
+        return false;
+    }
+    let kind = header.kinds.as_ref().map(|kinds| kinds[0].as_str());
+    match kind {
+        Some("domain" | "space" | "time") => false,
+        Some("???" | "none") | None => {
+            matches!(&header.space_directions, Some(directions) if directions[0].is_none())
+        }
+        Some(_) => true,
+    }
+}
+
+
+// Geometry of the (non-component) axes, converted to LPS.
+fn nd_image<T>(voxels: Vec<T>, header: &Header, axes: &[usize]) -> NdImage<T> {
+    let n = axes.len();
+    let lps = &header.space_to_lps;
+
+    let mut spacing = vec![1.0; n];
+    let mut direction = vec![0.0; n * n];
+    for (i, &axis) in axes.iter().enumerate() {
+        direction[i * n + i] = 1.0;
+        let vector = header.space_directions.as_ref().and_then(|d| d[axis].as_ref());
+        if let Some(vector) = vector {
+            let vector: Vec<f64> = vector.iter().zip(lps).map(|(v, s)| v * s).collect();
+            let length = vector.iter().map(|v| v * v).sum::<f64>().sqrt();
+            if length > 0.0 {
+                spacing[i] = length;
+                // Space and image dimension usually agree, pad or drop components otherwise.
+                for j in 0..n {
+                    direction[i * n + j] = vector.get(j).map_or(0.0, |v| v / length);
+                }
+            }
+        } else if let Some(s) = header.spacings.as_ref().map(|s| s[axis]).filter(|s| s.is_finite() && *s > 0.0) {
+            spacing[i] = s;
+        }
+    }
+
+    let origin = (0..n).map(|j| {
+        header.space_origin.as_ref().and_then(|o| o.get(j)).map_or(0.0, |o| o * lps[j])
+    }).collect();
+
+    NdImage {
+        voxels,
+        size: axes.iter().map(|&axis| header.sizes[axis]).collect(),
+        spacing,
+        origin,
+        direction,
+        metadata: header.metadata.clone(),
+    }
+}
+
+
+// Parse `encoding: ascii` voxel data, whitespace separated values.
+// `first_line` is the line the data starts on, for error messages.
+fn ascii_to_vec<T: NumCast + FromStr>(raw: &[u8], count: usize, first_line: usize) -> Result<Vec<T>, NrrdError> {
+    let text = String::from_utf8_lossy(raw);
+    let voxels = text
+        .lines()
+        .enumerate()
+        .flat_map(|(i, text_line)| text_line.split_whitespace().map(move |token| (first_line + i, token)))
+        .take(count)
+        .map(|(line, token)| {
+            token.parse::<T>().ok()
+                .or_else(|| token.parse::<f64>().ok().and_then(NumCast::from))
+                .ok_or_else(|| NrrdError::InvalidValue {
+                    field: "data".to_string(),
+                    value: token.to_string(),
+                    line,
+                })
+        })
+        .collect::<Result<Vec<T>, _>>()?;
+
+    if voxels.len() < count {
+        return Err(NrrdError::MissingValues { expected: count, found: voxels.len() });
+    }
+    Ok(voxels)
+}
+
+
+// Number of values in the data, an error if it overflows.
+fn value_count(header: &Header) -> Result<usize, NrrdError> {
+    header.sizes.iter()
+        .try_fold(1usize, |count, &s| count.checked_mul(s as usize))
+        .ok_or_else(|| NrrdError::InvalidHeader(format!("sizes {:?} are too large", header.sizes)))
+}
+
+
+fn build_image<T>(data: Vec<u8>, header: &Header) -> Result<Box<dyn AnyImage>, NrrdError>
+where
+    T: Pod + NumCast + FromStr + Copy + 'static,
+{
+    let count = value_count(header)?;
+    let voxels: Vec<T> = match header.encoding {
+        NrrdEncoding::Ascii => ascii_to_vec(&data, count, header.data_line + header.line_skip)?,
+        _ => bytes_to_vec(data, header.byte_order_msb),
+    };
+
+    // Components vary fastest, so a leading component axis is interleaved like `VectorImage`.
+    let components = is_component_axis(header);
+    let channels = if components { header.sizes[0] } else { 1 };
+    let axes: Vec<usize> = (components as usize..header.dimension).collect();
+    let image = nd_image(voxels, header, &axes);
+
+    if channels > 1 {
+        let ndims = image.ndims();
+        let image = Image::try_from(image).map_err(|_| NrrdError::Unsupported(
+            format!("{} components in a {}D image, vector images are at most 3D", channels, ndims)
+        ))?;
+        return Ok(Box::new(VectorImage {
+            voxels: image.voxels,
+            channels,
+            width: image.width,
+            height: image.height,
+            depth: image.depth,
+            spacing: image.spacing,
+            origin: image.origin,
+            direction: image.direction,
+            metadata: image.metadata,
+        }));
+    }
+
+    // 3D volumes load as `Image<T>`, anything else stays N-dimensional.
+    if image.ndims() != 3 {
+        return Ok(Box::new(image));
+    }
+    match Image::try_from(image) {
+        Ok(image) => Ok(Box::new(image)),
+        Err(image) => Ok(Box::new(image)),
+    }
+}
+
+
+// Drop `lines` lines from the front of `data`.
+fn skip_lines(data: &[u8], lines: usize) -> &[u8] {
+    let mut rest = data;
+    for _ in 0..lines {
+        match rest.iter().position(|&b| b == b'\n') {
+            Some(end) => rest = &rest[end + 1..],
+            None => return &[],
+        }
+    }
+    rest
+}
+
+
+/// Load a .nrrd file, or a detached .nhdr header and its data file.
+///
+/// `space directions` and `space origin` are converted to LPS. A leading
+/// axis of kind `vector`, `RGB-color` etc. (or without a space direction)
+/// holds the components of a `VectorImage`.
+pub fn load_nrrd(filename: &str) -> Result<Box<dyn AnyImage>, NrrdError> {
+    let mut reader = BufReader::new(File::open(filename)?);
+    let header = parse_header(&mut reader)?;
+    if header.sizes.contains(&0) {
+        return Err(NrrdError::InvalidHeader(format!("invalid sizes {:?}", header.sizes)));
+    }
+
+    // Attached data follows the header, detached data lives next to it.
+    let mut raw = Vec::new();
+    match &header.data_file {
+        None => {
+            reader.read_to_end(&mut raw)?;
+        }
+        Some(data_file) => {
+            let base_path = Path::new(filename).parent().unwrap_or(Path::new(""));
+            let data_path = base_path.join(data_file);
+            if !data_path.is_file() {
+                return Err(NrrdError::MissingDataFile(data_path));
+            }
+            File::open(data_path)?.read_to_end(&mut raw)?;
+        }
+    }
+    let raw = skip_lines(&raw, header.line_skip);
+
+    let total_bytes = value_count(&header)?
+        .checked_mul(header.element_type.bytes())
+        .ok_or_else(|| NrrdError::InvalidHeader(format!("sizes {:?} are too large", header.sizes)))?;
+    let data = match header.encoding {
+        NrrdEncoding::Ascii => raw.to_vec(),
+        NrrdEncoding::Raw | NrrdEncoding::Gzip => {
+            let decompressed;
+            let bytes = if header.encoding == NrrdEncoding::Gzip {
+                let mut buffer = Vec::new();
+                MultiGzDecoder::new(raw).read_to_end(&mut buffer).map_err(NrrdError::Decompression)?;
+                decompressed = buffer;
+                &decompressed[..]
+            } else {
+                raw
+            };
+            // byte skip -1 means the data sits at the end of the file.
+            let start = match header.byte_skip {
+                -1 => bytes.len().saturating_sub(total_bytes),
+                skip => skip as usize,
+            };
+            let found = bytes.len().saturating_sub(start);
+            if found < total_bytes {
+                return Err(NrrdError::Truncated { expected: total_bytes, found });
+            }
+            bytes[start..start + total_bytes].to_vec()
+        }
+    };
+
+    match header.element_type {
+        ElementType::U8 => build_image::<u8>(data, &header),
+        ElementType::I8 => build_image::<i8>(data, &header),
+        ElementType::U16 => build_image::<u16>(data, &header),
+        ElementType::I16 => build_image::<i16>(data, &header),
+        ElementType::U32 => build_image::<u32>(data, &header),
+        ElementType::I32 => build_image::<i32>(data, &header),
+        ElementType::F32 => build_image::<f32>(data, &header),
+        ElementType::U64 => build_image::<u64>(data, &header),
+        ElementType::I64 => build_image::<i64>(data, &header),
+        ElementType::F64 => build_image::<f64>(data, &header),
+    }
+}
+
+
+fn type_str<T: 'static>() -> Option<&'static str> {
+    let tid = TypeId::of::<T>();
+    [
+        (TypeId::of::<u8>(), "uint8"), (TypeId::of::<i8>(), "int8"),
+        (TypeId::of::<u16>(), "uint16"), (TypeId::of::<i16>(), "int16"),
+        (TypeId::of::<u32>(), "uint32"), (TypeId::of::<i32>(), "int32"), (TypeId::of::<f32>(), "float"),
+        (TypeId::of::<u64>(), "uint64"), (TypeId::of::<i64>(), "int64"), (TypeId::of::<f64>(), "double"),
+    ].into_iter().find(|(t, _)| *t == tid).map(|(_, name)| name)
+}
+
+
+// Everything the header writer needs to know about an image besides its voxels.
+struct ImageInfo<'a> {
+    size: Vec<u32>,
+    spacing: &'a [f64],
+    origin: &'a [f64],
+    direction: &'a [f64],
+    channels: u32,
+    metadata: &'a Metadata,
+}
+
+
+fn vector(values: impl Iterator<Item = f64>) -> String {
+    format!("({})", values.map(|v| v.to_string()).collect::<Vec<_>>().join(","))
+}
+
+
+fn write_nrrd<T>(voxels: &[T], info: &ImageInfo, file_path: &str, encoding: NrrdEncoding) -> Result<(), NrrdError>
+where
+    T: Pod + Display + 'static,
+{
+    let path = Path::new(file_path);
+    let ext = path.extension().and_then(|s| s.to_str()).unwrap_or_default();
+    if ext != "nrrd" && ext != "nhdr" {
+        return Err(NrrdError::Unsupported(format!("{} is not a .nrrd or .nhdr file name", file_path)));
+    }
+    let type_name = type_str::<T>().ok_or_else(|| NrrdError::UnsupportedType(std::any::type_name::<T>().into()))?;
+
+    let n = info.size.len();
+    let components = info.channels > 1;
+    let mut sizes = info.size.clone();
+    if components {
+        sizes.insert(0, info.channels);
+    }
+
+    let mut header = Vec::new();
+    writeln!(header, "NRRD0004")?;
+    writeln!(header, "# Complete NRRD file format specification at:")?;
+    writeln!(header, "# http://teem.sourceforge.net/nrrd/format.html")?;
+    writeln!(header, "type: {}", type_name)?;
+    writeln!(header, "dimension: {}", sizes.len())?;
+    if n == 3 {
+        writeln!(header, "space: left-posterior-superior")?;
+    } else {
+        writeln!(header, "space dimension: {}", n)?;
+    }
+    writeln!(header, "sizes: {}", sizes.iter().map(|s| s.to_string()).collect::<Vec<_>>().join(" "))?;
+
+    // Axis vectors scaled by spacing, the rows of `direction`.
+    let mut directions: Vec<String> = (0..n)
+        .map(|a| vector((0..n).map(|j| info.direction[a * n + j] * info.spacing[a])))
+        .collect();
+    let mut kinds = vec!["domain"; n];
+    if components {
+        directions.insert(0, "none".into());
+        kinds.insert(0, "vector");
+    }
+    writeln!(header, "space directions: {}", directions.join(" "))?;
+    writeln!(header, "kinds: {}", kinds.join(" "))?;
+    if std::mem::size_of::<T>() > 1 && encoding != NrrdEncoding::Ascii {
+        writeln!(header, "endian: little")?;
+    }
+    let encoding_name = match encoding {
+        NrrdEncoding::Raw => "raw",
+        NrrdEncoding::Gzip => "gzip",
+        NrrdEncoding::Ascii => "ascii",
+    };
+    writeln!(header, "encoding: {}", encoding_name)?;
+    writeln!(header, "space origin: {}", vector(info.origin.iter().copied()))?;
+    for (key, value) in info.metadata.iter() {
+        writeln!(header, "{}:={}", key, value.replace('\n', "\\n"))?;
+    }
+
+    // .nhdr headers point at a data file next to them.
+    let data_path = if ext == "nhdr" {
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+        let data_ext = match encoding {
+            NrrdEncoding::Raw => "raw",
+            NrrdEncoding::Gzip => "raw.gz",
+            NrrdEncoding::Ascii => "txt",
+        };
+        let data_filename = format!("{}.{}", stem, data_ext);
+        writeln!(header, "data file: {}", data_filename)?;
+        Some(path.with_file_name(data_filename))
+    } else {
+        None
+    };
+    writeln!(header)?;
+
+    let data = match encoding {
+        NrrdEncoding::Raw => vec_to_bytes(voxels, false).into_owned(),
+        NrrdEncoding::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&vec_to_bytes(voxels, false))?;
+            encoder.finish()?
+        }
+        NrrdEncoding::Ascii => {
+            // One row along the first axis per line.
+            let row_length = (info.size.first().copied().unwrap_or(1) * info.channels).max(1) as usize;
+            let mut text = String::new();
+            for row in voxels.chunks(row_length) {
+                text.push_str(&row.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" "));
+                text.push('\n');
+            }
+            text.into_bytes()
+        }
+    };
+
+    let mut file = BufWriter::new(File::create(file_path)?);
+    file.write_all(&header)?;
+    match data_path {
+        Some(data_path) => {
+            let mut data_file = BufWriter::new(File::create(data_path)?);
+            data_file.write_all(&data)?;
+            data_file.flush()?;
+        }
+        None => file.write_all(&data)?,
+    }
+    file.flush()?;
+    Ok(())
+}
+
+
+fn try_save<T>(img: &dyn AnyImage, file_path: &str, encoding: NrrdEncoding) -> Option<Result<(), NrrdError>>
+where
+    T: Pod + Display + 'static,
+{
+    let parts = ImageParts::<T>::of(img)?;
+    let info = ImageInfo {
+        size: parts.size,
+        spacing: parts.spacing,
+        origin: parts.origin,
+        direction: parts.direction,
+        channels: parts.channels,
+        metadata: parts.metadata,
+    };
+    Some(write_nrrd(parts.voxels, &info, file_path, encoding))
+}
+
+
+/// Save as .nrrd, or as a detached .nhdr header plus data file
+/// (.raw, .raw.gz or .txt depending on `encoding`).
+pub fn save_nrrd(img: &dyn AnyImage, file_path: &str, encoding: NrrdEncoding) -> Result<(), NrrdError> {
+    first_pixel_type!(try_save(img, file_path, encoding))
+        .unwrap_or_else(|| Err(NrrdError::UnsupportedType("unsupported pixel type".into())))
+}
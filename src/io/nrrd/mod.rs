@@ -0,0 +1,7 @@
+pub mod error;
+pub mod header;
+pub mod image;
+
+pub use error::NrrdError;
+pub use header::NrrdEncoding;
+pub use image::{load_nrrd, save_nrrd};
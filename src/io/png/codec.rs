@@ -0,0 +1,319 @@
+use std::io::{Read, Write};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::{Compression, Crc};
+
+use super::error::PngError;
+
+const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+// Adam7 passes: first column, first row, column step, row step.
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8), (4, 0, 8, 8), (0, 4, 4, 8), (2, 0, 4, 4), (0, 2, 2, 4), (1, 0, 2, 2), (0, 1, 1, 2),
+];
+
+
+/// Decoded pixels, samples of a pixel interleaved, rows top to bottom.
+#[derive(Debug)]
+pub(super) struct Png {
+    pub width: usize,
+    pub height: usize,
+    pub channels: usize,
+    /// Samples of 16 bit images; 1, 2, 4 and 8 bit samples are in `samples8`.
+    pub samples16: Option<Vec<u16>>,
+    pub samples8: Vec<u8>,
+    /// Physical pixel size from pHYs, in pixels per metre.
+    pub pixels_per_metre: Option<(u32, u32)>,
+    pub text: Vec<(String, String)>,
+}
+
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes(bytes[..4].try_into().unwrap())
+}
+
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+
+// Reverse the filter of `row` in place, `previous` is the unfiltered row above (zeros for the first).
+fn unfilter(filter: u8, row: &mut [u8], previous: &[u8], bpp: usize) -> Result<(), PngError> {
+    match filter {
+        0 => {}
+        1 => (bpp..row.len()).for_each(|i| row[i] = row[i].wrapping_add(row[i - bpp])),
+        2 => (0..row.len()).for_each(|i| row[i] = row[i].wrapping_add(previous[i])),
+        3 => (0..row.len()).for_each(|i| {
+            let left = if i >= bpp { row[i - bpp] } else { 0 };
+            row[i] = row[i].wrapping_add(((left as u16 + previous[i] as u16) / 2) as u8);
+        }),
+        4 => (0..row.len()).for_each(|i| {
+            let (left, corner) = if i >= bpp { (row[i - bpp], previous[i - bpp]) } else { (0, 0) };
+            row[i] = row[i].wrapping_add(paeth(left, previous[i], corner));
+        }),
+        other => return Err(PngError::InvalidData(format!("filter type {}", other))),
+    }
+    Ok(())
+}
+
+
+// Apply `filter` to `row`, the inverse of `unfilter`.
+fn filter_row(filter: u8, row: &[u8], previous: &[u8], bpp: usize, out: &mut Vec<u8>) {
+    out.push(filter);
+    for i in 0..row.len() {
+        let left = if i >= bpp { row[i - bpp] } else { 0 };
+        let corner = if i >= bpp { previous[i - bpp] } else { 0 };
+        let predicted = match filter {
+            1 => left,
+            2 => previous[i],
+            3 => ((left as u16 + previous[i] as u16) / 2) as u8,
+            4 => paeth(left, previous[i], corner),
+            _ => 0,
+        };
+        out.push(row[i].wrapping_sub(predicted));
+    }
+}
+
+
+/// Decode a PNG file. Palette images are expanded to RGB (RGBA with
+/// transparency), samples below 8 bits keep their value, e.g. 0/1 for 1 bit.
+pub(super) fn decode(bytes: &[u8]) -> Result<Png, PngError> {
+    if !bytes.starts_with(SIGNATURE) {
+        return Err(PngError::NotPng);
+    }
+
+    let mut header = None;
+    let mut palette = Vec::new();
+    let mut transparency = Vec::new();
+    let mut pixels_per_metre = None;
+    let mut text = Vec::new();
+    let mut compressed = Vec::new();
+    let mut pos = SIGNATURE.len();
+    loop {
+        if bytes.len() < pos + 12 {
+            return Err(PngError::InvalidData("missing IEND chunk".into()));
+        }
+        let length = be_u32(&bytes[pos..]) as usize;
+        let end = pos + 8 + length;
+        if bytes.len() < end + 4 {
+            return Err(PngError::InvalidData("truncated chunk".into()));
+        }
+        let kind = String::from_utf8_lossy(&bytes[pos + 4..pos + 8]).to_string();
+        let data = &bytes[pos + 8..end];
+        let mut crc = Crc::new();
+        crc.update(&bytes[pos + 4..end]);
+        if crc.sum() != be_u32(&bytes[end..]) {
+            return Err(PngError::Checksum(kind));
+        }
+        pos = end + 4;
+
+        match kind.as_str() {
+            "IHDR" if length >= 13 => header = Some(data.to_vec()),
+            "PLTE" => palette = data.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect(),
+            "tRNS" => transparency = data.to_vec(),
+            "pHYs" if length >= 9 && data[8] == 1 => pixels_per_metre = Some((be_u32(data), be_u32(&data[4..]))),
+            "tEXt" => {
+                if let Some(split) = data.iter().position(|&b| b == 0) {
+                    // Latin-1, every byte is one character.
+                    let latin1 = |b: &[u8]| b.iter().map(|&c| c as char).collect::<String>();
+                    text.push((latin1(&data[..split]), latin1(&data[split + 1..])));
+                }
+            }
+            "IDAT" => compressed.extend_from_slice(data),
+            "IEND" => break,
+            // Unknown critical chunks (upper case first letter) change how the image is read.
+            _ if kind.as_bytes()[0].is_ascii_uppercase() && kind != "IHDR" => {
+                return Err(PngError::Unsupported(format!("critical chunk {}", kind)));
+            }
+            _ => {}
+        }
+    }
+
+    let header = header.ok_or_else(|| PngError::InvalidData("missing IHDR chunk".into()))?;
+    let (width, height) = (be_u32(&header) as usize, be_u32(&header[4..]) as usize);
+    let (bit_depth, color_type, interlace) = (header[8] as usize, header[9], header[12]);
+    let channels = match (color_type, bit_depth) {
+        (0, 1 | 2 | 4 | 8 | 16) => 1,
+        (3, 1 | 2 | 4 | 8) => 1,
+        (4, 8 | 16) => 2,
+        (2, 8 | 16) => 3,
+        (6, 8 | 16) => 4,
+        _ => return Err(PngError::InvalidData(format!("color type {} with bit depth {}", color_type, bit_depth))),
+    };
+    // The spec limits both to 2^31 - 1.
+    let max_size = i32::MAX as usize;
+    if width == 0 || height == 0 || width > max_size || height > max_size {
+        return Err(PngError::InvalidData(format!("size {}x{}", width, height)));
+    }
+
+    let bits_per_pixel = channels * bit_depth;
+    let bpp = bits_per_pixel.div_ceil(8);
+    let passes: &[(usize, usize, usize, usize)] = match interlace {
+        0 => &[(0, 0, 1, 1)],
+        1 => &ADAM7,
+        other => return Err(PngError::Unsupported(format!("interlace method {}", other))),
+    };
+    // Width, height and bytes per row (without the filter type) of each pass.
+    let too_large = || PngError::InvalidData(format!("size {}x{} is too large", width, height));
+    let pass_sizes = passes.iter().map(|&(x0, y0, dx, dy)| {
+        let pass_width = (width + dx - 1 - x0) / dx;
+        let pass_height = (height + dy - 1 - y0) / dy;
+        let stride = pass_width.checked_mul(bits_per_pixel).ok_or_else(too_large)?.div_ceil(8);
+        Ok((pass_width, pass_height, stride))
+    }).collect::<Result<Vec<_>, PngError>>()?;
+    let expected = pass_sizes.iter()
+        .filter(|(pass_width, _, _)| *pass_width > 0)
+        .try_fold(0usize, |total, &(_, pass_height, stride)| {
+            (stride + 1).checked_mul(pass_height).and_then(|bytes| bytes.checked_add(total))
+        })
+        .ok_or_else(too_large)?;
+
+    // Nothing is allocated for the pixels before the data is known to cover them.
+    let mut filtered = Vec::new();
+    ZlibDecoder::new(&compressed[..]).take(expected as u64).read_to_end(&mut filtered).map_err(PngError::Decompression)?;
+    if filtered.len() < expected {
+        return Err(PngError::InvalidData("not enough image data".into()));
+    }
+
+    let count = [height, channels].into_iter().try_fold(width, usize::checked_mul).ok_or_else(too_large)?;
+    let mut samples = vec![0u16; count];
+    let mut pos = 0;
+    for (&(x0, y0, dx, dy), &(pass_width, pass_height, stride)) in passes.iter().zip(&pass_sizes) {
+        if pass_width == 0 || pass_height == 0 {
+            continue;
+        }
+        let mut previous = vec![0u8; stride];
+        for row in 0..pass_height {
+            let filter = filtered[pos];
+            let mut line = filtered[pos + 1..pos + 1 + stride].to_vec();
+            pos += 1 + stride;
+            unfilter(filter, &mut line, &previous, bpp)?;
+
+            let y = y0 + row * dy;
+            for i in 0..pass_width * channels {
+                let value = match bit_depth {
+                    16 => u16::from_be_bytes([line[2 * i], line[2 * i + 1]]),
+                    8 => line[i] as u16,
+                    _ => {
+                        let bit = i * bit_depth;
+                        ((line[bit / 8] >> (8 - bit_depth - bit % 8)) & ((1 << bit_depth) - 1) as u8) as u16
+                    }
+                };
+                let x = x0 + (i / channels) * dx;
+                samples[(y * width + x) * channels + i % channels] = value;
+            }
+            previous = line;
+        }
+    }
+
+    if color_type == 3 {
+        let alpha = !transparency.is_empty();
+        let mut expanded = Vec::with_capacity(samples.len() * if alpha { 4 } else { 3 });
+        for &index in &samples {
+            let color = palette.get(index as usize)
+                .ok_or_else(|| PngError::InvalidData(format!("palette index {} of {}", index, palette.len())))?;
+            expanded.extend_from_slice(color);
+            if alpha {
+                expanded.push(transparency.get(index as usize).copied().unwrap_or(255));
+            }
+        }
+        return Ok(Png {
+            width,
+            height,
+            channels: if alpha { 4 } else { 3 },
+            samples16: None,
+            samples8: expanded,
+            pixels_per_metre,
+            text,
+        });
+    }
+
+    let (samples16, samples8) = if bit_depth == 16 {
+        (Some(samples), Vec::new())
+    } else {
+        (None, samples.into_iter().map(|v| v as u8).collect())
+    };
+    Ok(Png { width, height, channels, samples16, samples8, pixels_per_metre, text })
+}
+
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend((data.len() as u32).to_be_bytes());
+    out.extend(kind);
+    out.extend(data);
+    let mut crc = Crc::new();
+    crc.update(kind);
+    crc.update(data);
+    out.extend(crc.sum().to_be_bytes());
+}
+
+
+/// Encode 8 bit (`samples8`) or 16 bit (`samples16`) pixels with 1 to 4 channels
+/// (gray, gray + alpha, RGB, RGBA).
+pub(super) fn encode(png: &Png) -> Result<Vec<u8>, PngError> {
+    let color_type: u8 = match png.channels {
+        1 => 0,
+        2 => 4,
+        3 => 2,
+        4 => 6,
+        n => return Err(PngError::Unsupported(format!("{} channels, PNG has 1 to 4", n))),
+    };
+    let raw: Vec<u8> = match &png.samples16 {
+        Some(samples) => samples.iter().flat_map(|v| v.to_be_bytes()).collect(),
+        None => png.samples8.clone(),
+    };
+    let bit_depth: u8 = if png.samples16.is_some() { 16 } else { 8 };
+    let bpp = png.channels * bit_depth as usize / 8;
+    let stride = png.width * bpp;
+
+    // Per row, the filter with the smallest sum of absolute (signed) differences.
+    let mut filtered = Vec::with_capacity(raw.len() + png.height);
+    let zeros = vec![0u8; stride];
+    let mut candidate = Vec::with_capacity(stride + 1);
+    for (y, row) in raw.chunks_exact(stride).enumerate() {
+        let previous = if y == 0 { &zeros[..] } else { &raw[(y - 1) * stride..y * stride] };
+        let mut best: Option<(u64, Vec<u8>)> = None;
+        for filter in 0..5 {
+            candidate.clear();
+            filter_row(filter, row, previous, bpp, &mut candidate);
+            let cost = candidate[1..].iter().map(|&b| (b as i8).unsigned_abs() as u64).sum();
+            if best.as_ref().is_none_or(|(best_cost, _)| cost < *best_cost) {
+                best = Some((cost, candidate.clone()));
+            }
+        }
+        filtered.extend(best.unwrap().1);
+    }
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&filtered)?;
+    let compressed = encoder.finish()?;
+
+    let mut out = SIGNATURE.to_vec();
+    let mut header = Vec::with_capacity(13);
+    header.extend((png.width as u32).to_be_bytes());
+    header.extend((png.height as u32).to_be_bytes());
+    header.extend([bit_depth, color_type, 0, 0, 0]);
+    write_chunk(&mut out, b"IHDR", &header);
+    if let Some((x, y)) = png.pixels_per_metre {
+        write_chunk(&mut out, b"pHYs", &[&x.to_be_bytes()[..], &y.to_be_bytes(), &[1]].concat());
+    }
+    for (key, value) in &png.text {
+        // tEXt is Latin-1 with 1 to 79 character keywords.
+        let latin1 = |s: &str| s.chars().map(|c| u8::try_from(u32::from(c)).unwrap_or(b'?')).collect::<Vec<u8>>();
+        let key = latin1(key);
+        if (1..80).contains(&key.len()) {
+            write_chunk(&mut out, b"tEXt", &[&key[..], &[0], &latin1(value)].concat());
+        }
+    }
+    write_chunk(&mut out, b"IDAT", &compressed);
+    write_chunk(&mut out, b"IEND", &[]);
+    Ok(out)
+}
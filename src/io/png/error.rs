@@ -0,0 +1,41 @@
+#[derive(Debug)]
+pub enum PngError {
+    Io(std::io::Error),     // Generic I/O error (file issue)
+    NotPng,                 // No PNG signature
+    InvalidData(String),
+    Checksum(String),       // Chunk with a wrong CRC
+    UnsupportedType(String),
+    Unsupported(String),
+    Decompression(std::io::Error),
+    OutOfRange { index: usize, size: usize }, // Slice index beyond the image
+}
+
+impl std::fmt::Display for PngError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PngError::Io(e) => write!(f, "I/O error: {}", e),
+            PngError::NotPng => write!(f, "Not a PNG file"),
+            PngError::InvalidData(what) => write!(f, "Invalid PNG data: {}", what),
+            PngError::Checksum(chunk) => write!(f, "CRC mismatch in {} chunk", chunk),
+            PngError::UnsupportedType(t) => write!(f, "Unsupported pixel type for PNG: {}", t),
+            PngError::Unsupported(what) => write!(f, "Unsupported PNG: {}", what),
+            PngError::Decompression(e) => write!(f, "Failed to decompress image data: {}", e),
+            PngError::OutOfRange { index, size } => {
+                write!(f, "Slice {} is out of range, the image has {} slices", index, size)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PngError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PngError::Io(e) | PngError::Decompression(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for PngError {
+    fn from(e: std::io::Error) -> Self { PngError::Io(e) }
+}
@@ -0,0 +1,116 @@
+use crate::image::{Image, VectorImage, AnyImage, Metadata};
+use crate::image::dyn_image::ImageParts;
+use crate::image::nd_image::padded3;
+
+use super::codec::{decode, encode, Png};
+use super::error::PngError;
+use std::fs::{read, write};
+
+use bytemuck::Pod;
+use num_traits::NumCast;
+
+const IDENTITY: [f64; 9] = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+
+
+fn build_image<T>(voxels: Vec<T>, png: &Png) -> Box<dyn AnyImage>
+where
+    T: Pod + NumCast + Copy + 'static,
+{
+    // pHYs holds pixels per metre, spacing is in mm.
+    let spacing = match png.pixels_per_metre {
+        Some((x, y)) if x > 0 && y > 0 => [1000.0 / x as f64, 1000.0 / y as f64, 1.0],
+        _ => [1.0; 3],
+    };
+    let metadata: Metadata = png.text.iter().cloned().collect();
+    if png.channels == 1 {
+        return Box::new(Image {
+            voxels,
+            width: png.width as u32,
+            height: png.height as u32,
+            depth: 1,
+            spacing,
+            origin: [0.0; 3],
+            direction: IDENTITY,
+            metadata,
+        });
+    }
+    Box::new(VectorImage {
+        voxels,
+        channels: png.channels as u32,
+        width: png.width as u32,
+        height: png.height as u32,
+        depth: 1,
+        spacing,
+        origin: [0.0; 3],
+        direction: IDENTITY,
+        metadata,
+    })
+}
+
+
+/// Load a PNG as a single slice, i.e. with `depth` 1.
+///
+/// Grayscale images load as `Image<u8>` or `Image<u16>`, images with
+/// color or alpha as `VectorImage` with 2 (gray + alpha), 3 (RGB) or 4
+/// (RGBA) channels. Palette images are expanded to RGB(A). The pixel size
+/// comes from the pHYs chunk, text chunks go into the metadata.
+pub fn load_png(filename: &str) -> Result<Box<dyn AnyImage>, PngError> {
+    let mut png = decode(&read(filename)?)?;
+    Ok(match png.samples16.take() {
+        Some(samples) => build_image(samples, &png),
+        None => build_image(std::mem::take(&mut png.samples8), &png),
+    })
+}
+
+
+fn to_png<T: Pod>(voxels: &[T], size: [u32; 3], channels: u32, spacing: &[f64], metadata: &Metadata) -> Result<Png, PngError> {
+    if size[2] != 1 {
+        return Err(PngError::Unsupported(format!(
+            "{} slices, PNG holds a single slice, see export_png_slice", size[2]
+        )));
+    }
+    if size.contains(&0) || channels == 0 {
+        return Err(PngError::Unsupported(format!("empty image of size {:?}", size)));
+    }
+    let (samples8, samples16) = if size_of::<T>() == 1 {
+        (bytemuck::cast_slice::<T, u8>(voxels).to_vec(), None)
+    } else {
+        (Vec::new(), Some(bytemuck::cast_slice::<T, u16>(voxels).to_vec()))
+    };
+    let per_metre = |s: f64| if s.is_finite() && s > 0.0 { (1000.0 / s).round() as u32 } else { 1000 };
+    Ok(Png {
+        width: size[0] as usize,
+        height: size[1] as usize,
+        channels: channels as usize,
+        samples16,
+        samples8,
+        pixels_per_metre: Some((per_metre(spacing[0]), per_metre(spacing[1]))),
+        text: metadata.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+    })
+}
+
+
+fn try_png<T: Pod + 'static>(img: &dyn AnyImage) -> Option<Result<Png, PngError>> {
+    let parts = ImageParts::<T>::of(img)?;
+    if parts.size.len() > 3 {
+        return Some(Err(PngError::Unsupported(format!("{}D images", parts.size.len()))));
+    }
+    let size = [0, 1, 2].map(|a| parts.size.get(a).copied().unwrap_or(1));
+    Some(to_png(parts.voxels, size, parts.channels, &padded3(parts.spacing, 1.0), parts.metadata))
+}
+
+
+/// Save a single slice image (`depth` 1) with `u8` or `u16` voxels as PNG,
+/// 1 to 4 channels for gray, gray + alpha, RGB and RGBA. The pixel size
+/// is written to the pHYs chunk, the metadata to text chunks.
+///
+/// Use `export_png_slice` for volumes and other pixel types.
+pub fn save_png(img: &dyn AnyImage, file_path: &str) -> Result<(), PngError> {
+    let png = try_png::<u8>(img)
+        .or_else(|| try_png::<u16>(img))
+        .unwrap_or_else(|| Err(PngError::UnsupportedType(
+            "PNG stores u8 and u16 voxels, see export_png_slice".into()
+        )))?;
+    write(file_path, encode(&png)?)?;
+    Ok(())
+}
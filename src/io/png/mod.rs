@@ -0,0 +1,8 @@
+pub mod codec;
+pub mod error;
+pub mod image;
+pub mod slice;
+
+pub use error::PngError;
+pub use image::{load_png, save_png};
+pub use slice::{export_png_slice, SlicePlane, Window};
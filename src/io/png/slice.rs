@@ -0,0 +1,150 @@
+use crate::image::Image;
+
+use super::codec::{encode, Png};
+use super::error::PngError;
+use std::fs::write;
+
+use num_traits::NumCast;
+
+/// Anatomical plane of an exported slice, relative to the LPS world axes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlicePlane {
+    /// Normal along S, displayed with the patient's right on the left and anterior up.
+    Axial,
+    /// Normal along P, displayed with right on the left and superior up.
+    Coronal,
+    /// Normal along L, displayed with anterior on the left and superior up.
+    Sagittal,
+}
+
+/// Intensity window (center and width, as DICOM's window/level) that is
+/// mapped to the 0-255 display range.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Window {
+    pub center: f64,
+    pub width: f64,
+}
+
+impl Window {
+    pub fn new(center: f64, width: f64) -> Self {
+        Window { center, width }
+    }
+
+    /// Window that maps `min` to black and `max` to white.
+    pub fn from_range(min: f64, max: f64) -> Self {
+        Window { center: (min + max) / 2.0, width: max - min }
+    }
+
+    fn map(&self, value: f64) -> u8 {
+        if self.width <= 0.0 {
+            return if value > self.center { 255 } else { 0 };
+        }
+        let low = self.center - self.width / 2.0;
+        ((value - low) / self.width * 255.0).round().clamp(0.0, 255.0) as u8
+    }
+}
+
+
+// Image axis closest to each world (LPS) axis, from the rows of `direction`.
+fn world_axes(direction: &[f64; 9]) -> [usize; 3] {
+    let mut pairs: Vec<(usize, usize)> = (0..3).flat_map(|a| (0..3).map(move |w| (a, w))).collect();
+    pairs.sort_by(|&(a, w), &(b, v)| direction[b * 3 + v].abs().total_cmp(&direction[a * 3 + w].abs()));
+    let mut axis_of = [usize::MAX; 3];
+    let mut used = [false; 3];
+    for (a, w) in pairs {
+        if !used[a] && axis_of[w] == usize::MAX {
+            axis_of[w] = a;
+            used[a] = true;
+        }
+    }
+    axis_of
+}
+
+
+// Linear interpolation of `values` (cols x rows) at a continuous position.
+fn bilinear(values: &[f64], cols: usize, rows: usize, x: f64, y: f64) -> f64 {
+    let x = x.clamp(0.0, (cols - 1) as f64);
+    let y = y.clamp(0.0, (rows - 1) as f64);
+    let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+    let (x1, y1) = ((x0 + 1).min(cols - 1), (y0 + 1).min(rows - 1));
+    let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+    let at = |x: usize, y: usize| values[y * cols + x];
+    let top = at(x0, y0) * (1.0 - fx) + at(x1, y0) * fx;
+    let bottom = at(x0, y1) * (1.0 - fx) + at(x1, y1) * fx;
+    top * (1.0 - fy) + bottom * fy
+}
+
+
+/// Save slice `index` through `plane` as an 8 bit grayscale PNG, with
+/// intensities mapped through `window`.
+///
+/// The plane is picked from the image direction, e.g. axial slices cut
+/// the image axis closest to S, and `index` counts along that axis. The
+/// slice is flipped into the usual radiological display orientation and
+/// non-square pixels are resampled (linearly) to the smaller spacing, so
+/// anatomy keeps its proportions.
+pub fn export_png_slice<T>(image: &Image<T>, plane: SlicePlane, index: usize, window: Window, file_path: &str) -> Result<(), PngError>
+where
+    T: Copy + NumCast,
+{
+    let size = [image.width as usize, image.height as usize, image.depth as usize];
+    let axis_of = world_axes(&image.direction);
+    // (normal world axis), (column world axis, sign), (row world axis, sign)
+    let (normal, (col_world, col_sign), (row_world, row_sign)) = match plane {
+        SlicePlane::Axial => (2, (0, 1.0), (1, 1.0)),
+        SlicePlane::Coronal => (1, (0, 1.0), (2, -1.0)),
+        SlicePlane::Sagittal => (0, (1, 1.0), (2, -1.0)),
+    };
+    let normal = axis_of[normal];
+    let (col_axis, row_axis) = (axis_of[col_world], axis_of[row_world]);
+    if index >= size[normal] {
+        return Err(PngError::OutOfRange { index, size: size[normal] });
+    }
+    let flip_col = image.direction[col_axis * 3 + col_world] * col_sign < 0.0;
+    let flip_row = image.direction[row_axis * 3 + row_world] * row_sign < 0.0;
+
+    let (cols, rows) = (size[col_axis], size[row_axis]);
+    if cols == 0 || rows == 0 {
+        return Err(PngError::Unsupported(format!("empty {}x{} slice", cols, rows)));
+    }
+    let strides = [1, size[0], size[0] * size[1]];
+    let mut values = Vec::with_capacity(cols * rows);
+    for r in 0..rows {
+        for c in 0..cols {
+            let c = if flip_col { cols - 1 - c } else { c };
+            let r = if flip_row { rows - 1 - r } else { r };
+            let voxel = image.voxels[c * strides[col_axis] + r * strides[row_axis] + index * strides[normal]];
+            values.push(NumCast::from(voxel).unwrap_or(f64::NAN));
+        }
+    }
+
+    // Square display pixels at the finer of the two spacings.
+    let (col_spacing, row_spacing) = match (image.spacing[col_axis], image.spacing[row_axis]) {
+        (c, r) if c.is_finite() && r.is_finite() && c > 0.0 && r > 0.0 => (c, r),
+        _ => (1.0, 1.0),
+    };
+    let spacing = col_spacing.min(row_spacing);
+    let out_cols = ((cols as f64 * col_spacing / spacing).round() as usize).max(1);
+    let out_rows = ((rows as f64 * row_spacing / spacing).round() as usize).max(1);
+    let mut pixels = Vec::with_capacity(out_cols * out_rows);
+    for j in 0..out_rows {
+        let y = (j as f64 + 0.5) * rows as f64 / out_rows as f64 - 0.5;
+        for i in 0..out_cols {
+            let x = (i as f64 + 0.5) * cols as f64 / out_cols as f64 - 0.5;
+            pixels.push(window.map(bilinear(&values, cols, rows, x, y)));
+        }
+    }
+
+    let per_metre = (1000.0 / spacing).round() as u32;
+    let png = Png {
+        width: out_cols,
+        height: out_rows,
+        channels: 1,
+        samples16: None,
+        samples8: pixels,
+        pixels_per_metre: Some((per_metre, per_metre)),
+        text: Vec::new(),
+    };
+    write(file_path, encode(&png)?)?;
+    Ok(())
+}
@@ -0,0 +1,43 @@
+#[derive(Debug)]
+pub enum TiffError {
+    Missing(&'static str),  // Missing tags that we need
+    Io(std::io::Error),     // Generic I/O error (file issue)
+    NotTiff,                // No II*/MM* byte order mark
+    InvalidData(String),
+    UnsupportedType(String),
+    Unsupported(String),
+    Truncated { expected: usize, found: usize },
+    Decompression(String),
+    InconsistentPages(String), // Pages of a stack that do not fit together
+}
+
+impl std::fmt::Display for TiffError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TiffError::Missing(tag) => write!(f, "Missing TIFF tag: {}", tag),
+            TiffError::Io(e) => write!(f, "I/O error: {}", e),
+            TiffError::NotTiff => write!(f, "Not a TIFF file"),
+            TiffError::InvalidData(what) => write!(f, "Invalid TIFF data: {}", what),
+            TiffError::UnsupportedType(t) => write!(f, "Unsupported TIFF sample type: {}", t),
+            TiffError::Unsupported(what) => write!(f, "Unsupported TIFF: {}", what),
+            TiffError::Truncated { expected, found } => {
+                write!(f, "Truncated TIFF data: expected {} bytes, found {}", expected, found)
+            }
+            TiffError::Decompression(e) => write!(f, "Failed to decompress TIFF strip: {}", e),
+            TiffError::InconsistentPages(what) => write!(f, "Pages do not form a stack: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for TiffError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TiffError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for TiffError {
+    fn from(e: std::io::Error) -> Self { TiffError::Io(e) }
+}
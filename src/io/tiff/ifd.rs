@@ -0,0 +1,298 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+
+use super::error::TiffError;
+
+pub(super) type Tag = u16;
+
+pub(super) const NEW_SUBFILE_TYPE: Tag = 254;
+pub(super) const IMAGE_WIDTH: Tag = 256;
+pub(super) const IMAGE_LENGTH: Tag = 257;
+pub(super) const BITS_PER_SAMPLE: Tag = 258;
+pub(super) const COMPRESSION: Tag = 259;
+pub(super) const PHOTOMETRIC_INTERPRETATION: Tag = 262;
+pub(super) const IMAGE_DESCRIPTION: Tag = 270;
+pub(super) const STRIP_OFFSETS: Tag = 273;
+pub(super) const SAMPLES_PER_PIXEL: Tag = 277;
+pub(super) const ROWS_PER_STRIP: Tag = 278;
+pub(super) const STRIP_BYTE_COUNTS: Tag = 279;
+pub(super) const X_RESOLUTION: Tag = 282;
+pub(super) const Y_RESOLUTION: Tag = 283;
+pub(super) const PLANAR_CONFIGURATION: Tag = 284;
+pub(super) const RESOLUTION_UNIT: Tag = 296;
+pub(super) const SOFTWARE: Tag = 305;
+pub(super) const PREDICTOR: Tag = 317;
+pub(super) const TILE_WIDTH: Tag = 322;
+pub(super) const EXTRA_SAMPLES: Tag = 338;
+pub(super) const SAMPLE_FORMAT: Tag = 339;
+
+// Field types, TIFF 6.0 section 2 and BigTIFF.
+pub(super) const ASCII: u16 = 2;
+pub(super) const SHORT: u16 = 3;
+pub(super) const LONG: u16 = 4;
+pub(super) const RATIONAL: u16 = 5;
+pub(super) const LONG8: u16 = 16;
+
+fn type_size(field_type: u16) -> Option<usize> {
+    match field_type {
+        1 | 2 | 6 | 7 => Some(1),
+        3 | 8 => Some(2),
+        4 | 9 | 11 | 13 => Some(4),
+        5 | 10 | 12 | 16 | 17 | 18 => Some(8),
+        _ => None,
+    }
+}
+
+
+#[derive(Debug)]
+struct Entry {
+    field_type: u16,
+    count: usize,
+    value: Vec<u8>,
+}
+
+
+/// One image file directory, i.e. one page of the file.
+#[derive(Debug)]
+pub(super) struct Ifd {
+    pub big_endian: bool,
+    entries: HashMap<Tag, Entry>,
+}
+
+impl Ifd {
+    fn read<const N: usize>(&self, bytes: &[u8]) -> [u8; N] {
+        let mut raw: [u8; N] = bytes[..N].try_into().unwrap();
+        if self.big_endian {
+            raw.reverse();
+        }
+        raw
+    }
+
+    /// Integer values (BYTE, SHORT, LONG, LONG8 and their signed variants).
+    pub fn uints(&self, tag: Tag) -> Option<Vec<u64>> {
+        let entry = self.entries.get(&tag)?;
+        let size = type_size(entry.field_type)?;
+        let values = entry.value.chunks_exact(size).take(entry.count);
+        match entry.field_type {
+            1 | 6 | 7 => Some(values.map(|v| v[0] as u64).collect()),
+            3 | 8 => Some(values.map(|v| u16::from_le_bytes(self.read(v)) as u64).collect()),
+            4 | 9 | 13 => Some(values.map(|v| u32::from_le_bytes(self.read(v)) as u64).collect()),
+            16..=18 => Some(values.map(|v| u64::from_le_bytes(self.read(v))).collect()),
+            _ => None,
+        }
+    }
+
+    pub fn uint(&self, tag: Tag) -> Option<u64> {
+        self.uints(tag)?.first().copied()
+    }
+
+    /// First value of a RATIONAL, SRATIONAL, FLOAT or DOUBLE field.
+    pub fn real(&self, tag: Tag) -> Option<f64> {
+        let entry = self.entries.get(&tag)?;
+        let value = &entry.value;
+        if entry.count == 0 {
+            return None;
+        }
+        match entry.field_type {
+            RATIONAL => {
+                let (n, d) = (u32::from_le_bytes(self.read(value)), u32::from_le_bytes(self.read(&value[4..])));
+                Some(n as f64 / d as f64)
+            }
+            10 => {
+                let (n, d) = (i32::from_le_bytes(self.read(value)), i32::from_le_bytes(self.read(&value[4..])));
+                Some(n as f64 / d as f64)
+            }
+            11 => Some(f32::from_le_bytes(self.read(value)) as f64),
+            12 => Some(f64::from_le_bytes(self.read(value))),
+            _ => self.uint(tag).map(|v| v as f64),
+        }
+    }
+
+    pub fn ascii(&self, tag: Tag) -> Option<String> {
+        let entry = self.entries.get(&tag)?;
+        let text = String::from_utf8_lossy(&entry.value);
+        Some(text.trim_end_matches('\0').to_string())
+    }
+}
+
+
+// Endian aware reads from the whole file.
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    big_endian: bool,
+}
+
+impl Cursor<'_> {
+    fn get(&self, offset: u64, len: usize) -> Result<&[u8], TiffError> {
+        let start = usize::try_from(offset).unwrap_or(usize::MAX);
+        let found = self.bytes.len().saturating_sub(start);
+        if found < len {
+            return Err(TiffError::Truncated { expected: len, found });
+        }
+        Ok(&self.bytes[start..start + len])
+    }
+
+    fn u16(&self, offset: u64) -> Result<u16, TiffError> {
+        let raw = self.get(offset, 2)?.try_into().unwrap();
+        Ok(if self.big_endian { u16::from_be_bytes(raw) } else { u16::from_le_bytes(raw) })
+    }
+
+    fn u32(&self, offset: u64) -> Result<u32, TiffError> {
+        let raw = self.get(offset, 4)?.try_into().unwrap();
+        Ok(if self.big_endian { u32::from_be_bytes(raw) } else { u32::from_le_bytes(raw) })
+    }
+
+    fn u64(&self, offset: u64) -> Result<u64, TiffError> {
+        let raw = self.get(offset, 8)?.try_into().unwrap();
+        Ok(if self.big_endian { u64::from_be_bytes(raw) } else { u64::from_le_bytes(raw) })
+    }
+}
+
+
+/// All directories of a classic TIFF or BigTIFF file, in file order.
+pub(super) fn read_ifds(bytes: &[u8]) -> Result<Vec<Ifd>, TiffError> {
+    let big_endian = match bytes.get(..2) {
+        Some(b"II") => false,
+        Some(b"MM") => true,
+        _ => return Err(TiffError::NotTiff),
+    };
+    let cursor = Cursor { bytes, big_endian };
+    let big_tiff = match cursor.u16(2)? {
+        42 => false,
+        43 => true,
+        _ => return Err(TiffError::NotTiff),
+    };
+
+    // Classic TIFF has 16 bit entry counts and 32 bit offsets, BigTIFF 64 bit ones.
+    let (entry_size, inline_size) = if big_tiff { (20, 8) } else { (12, 4) };
+    let offset = |pos: u64| if big_tiff { cursor.u64(pos) } else { cursor.u32(pos).map(u64::from) };
+
+    let mut ifds = Vec::new();
+    let mut visited = HashSet::new();
+    let mut next = offset(if big_tiff { 8 } else { 4 })?;
+    while next != 0 {
+        if !visited.insert(next) {
+            return Err(TiffError::InvalidData(format!("directory loop at offset {}", next)));
+        }
+        let (count, first) = if big_tiff {
+            (cursor.u64(next)?, next + 8)
+        } else {
+            (cursor.u16(next)? as u64, next + 2)
+        };
+
+        let mut entries = HashMap::new();
+        for i in 0..count {
+            let pos = first + i * entry_size;
+            let tag = cursor.u16(pos)?;
+            let field_type = cursor.u16(pos + 2)?;
+            let count = if big_tiff { cursor.u64(pos + 4)? } else { cursor.u32(pos + 4)? as u64 };
+            // Skip types from later extensions we do not know the size of.
+            let Some(size) = type_size(field_type) else { continue };
+            let len = usize::try_from(count).ok().and_then(|c| c.checked_mul(size))
+                .ok_or_else(|| TiffError::InvalidData(format!("tag {} has {} values", tag, count)))?;
+            let value_pos = pos + entry_size - inline_size;
+            let value = if len <= inline_size as usize {
+                cursor.get(value_pos, len)?
+            } else {
+                cursor.get(offset(value_pos)?, len)?
+            };
+            entries.insert(tag, Entry { field_type, count: count as usize, value: value.to_vec() });
+        }
+        ifds.push(Ifd { big_endian, entries });
+        next = offset(first + count * entry_size)?;
+    }
+    Ok(ifds)
+}
+
+
+/// Directory under construction, values are stored little-endian.
+#[derive(Default, Clone)]
+pub(super) struct IfdWriter {
+    entries: Vec<(Tag, u16, u64, Vec<u8>)>,
+}
+
+impl IfdWriter {
+    pub fn short(&mut self, tag: Tag, values: &[u16]) {
+        let bytes = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        self.entries.push((tag, SHORT, values.len() as u64, bytes));
+    }
+
+    /// LONG, or LONG8 in BigTIFF files.
+    pub fn offsets(&mut self, tag: Tag, values: &[u64], big_tiff: bool) {
+        let (field_type, bytes) = if big_tiff {
+            (LONG8, values.iter().flat_map(|v| v.to_le_bytes()).collect())
+        } else {
+            (LONG, values.iter().flat_map(|&v| (v as u32).to_le_bytes()).collect())
+        };
+        self.entries.push((tag, field_type, values.len() as u64, bytes));
+    }
+
+    pub fn long(&mut self, tag: Tag, value: u32) {
+        self.entries.push((tag, LONG, 1, value.to_le_bytes().to_vec()));
+    }
+
+    pub fn rational(&mut self, tag: Tag, numerator: u32, denominator: u32) {
+        let bytes = [numerator.to_le_bytes(), denominator.to_le_bytes()].concat();
+        self.entries.push((tag, RATIONAL, 1, bytes));
+    }
+
+    pub fn ascii(&mut self, tag: Tag, text: &str) {
+        let mut bytes = text.as_bytes().to_vec();
+        bytes.push(0);
+        self.entries.push((tag, ASCII, bytes.len() as u64, bytes));
+    }
+
+    /// Bytes `write` produces, entries plus the values that do not fit inline.
+    pub fn size(&self, big_tiff: bool) -> u64 {
+        let (entry_size, inline_size, header) = if big_tiff { (20, 8, 16) } else { (12, 4, 6) };
+        let values: u64 = self.entries.iter()
+            .map(|(_, _, _, v)| v.len() as u64)
+            .filter(|&len| len > inline_size)
+            .map(|len| len + len % 2)
+            .sum();
+        header + self.entries.len() as u64 * entry_size + values
+    }
+
+    /// Write the directory at file offset `position`, pointing at the next one at `next`.
+    pub fn write<W: Write>(mut self, writer: &mut W, position: u64, next: u64, big_tiff: bool) -> io::Result<()> {
+        self.entries.sort_by_key(|(tag, ..)| *tag);
+        let (entry_size, inline_size) = if big_tiff { (20, 8) } else { (12, 4) };
+        let write_offset = |writer: &mut W, value: u64| if big_tiff {
+            writer.write_all(&value.to_le_bytes())
+        } else {
+            writer.write_all(&(value as u32).to_le_bytes())
+        };
+
+        if big_tiff {
+            writer.write_all(&(self.entries.len() as u64).to_le_bytes())?;
+        } else {
+            writer.write_all(&(self.entries.len() as u16).to_le_bytes())?;
+        }
+        let header = if big_tiff { 8 } else { 2 };
+        let mut value_offset = position + header + self.entries.len() as u64 * entry_size + inline_size as u64;
+        for (tag, field_type, count, value) in &self.entries {
+            writer.write_all(&tag.to_le_bytes())?;
+            writer.write_all(&field_type.to_le_bytes())?;
+            write_offset(writer, *count)?;
+            if value.len() <= inline_size {
+                writer.write_all(value)?;
+                writer.write_all(&vec![0; inline_size - value.len()])?;
+            } else {
+                write_offset(writer, value_offset)?;
+                value_offset += (value.len() + value.len() % 2) as u64;
+            }
+        }
+        write_offset(writer, next)?;
+
+        // Out of line values, word aligned.
+        for (_, _, _, value) in &self.entries {
+            if value.len() > inline_size {
+                writer.write_all(value)?;
+                if value.len() % 2 == 1 {
+                    writer.write_all(&[0])?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,587 @@
+use crate::image::{Image, NdImage, VectorImage, AnyImage, Metadata};
+use crate::image::dyn_image::{first_pixel_type, ImageParts};
+use crate::image::nd_image::padded3;
+use crate::io::meta_image::image::{bytes_to_vec, vec_to_bytes};
+
+use super::error::TiffError;
+use super::ifd::*;
+use std::any::TypeId;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use weezl::BitOrder;
+
+use bytemuck::Pod;
+use num_traits::NumCast;
+
+/// Compression of the strips `save_tiff` writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TiffCompression {
+    #[default]
+    None,
+    Lzw,
+    Deflate,
+}
+
+// Compression tag values.
+const UNCOMPRESSED: u64 = 1;
+const LZW: u64 = 5;
+const ADOBE_DEFLATE: u64 = 8;
+const DEFLATE: u64 = 32946;
+
+// Sample formats.
+const UINT: u64 = 1;
+const INT: u64 = 2;
+const IEEEFP: u64 = 3;
+
+
+// What ImageJ writes into the ImageDescription of its stacks.
+#[derive(Debug, Default)]
+struct ImageJ {
+    images: Option<usize>,
+    channels: Option<usize>,
+    frames: Option<usize>,
+    unit: Option<String>,
+    spacing: Option<f64>,
+    finterval: Option<f64>,
+}
+
+impl ImageJ {
+    fn parse(description: &str) -> Option<ImageJ> {
+        if !description.starts_with("ImageJ=") {
+            return None;
+        }
+        let mut ij = ImageJ::default();
+        for (key, value) in description.lines().filter_map(|line| line.split_once('=')) {
+            let value = value.trim();
+            match key.trim() {
+                "images" => ij.images = value.parse().ok(),
+                "channels" => ij.channels = value.parse().ok(),
+                "frames" => ij.frames = value.parse().ok(),
+                // ImageJ writes µ escaped, "\u00B5m".
+                "unit" => ij.unit = Some(value.replace("\\u00B5", "\u{b5}")),
+                "spacing" => ij.spacing = value.parse().ok().filter(|s: &f64| s.is_finite() && *s > 0.0),
+                "finterval" => ij.finterval = value.parse().ok().filter(|s: &f64| s.is_finite() && *s > 0.0),
+                _ => {}
+            }
+        }
+        Some(ij)
+    }
+}
+
+
+// The tags of one page we need to decode it.
+#[derive(Debug, Clone, PartialEq)]
+struct Page {
+    width: usize,
+    height: usize,
+    bits: u64,
+    sample_format: u64,
+    samples: usize,
+    planar: bool,
+    compression: u64,
+    predictor: u64,
+    rows_per_strip: usize,
+    strips: Vec<(u64, u64)>,
+    bytes: usize,
+}
+
+impl Page {
+    fn from_ifd(ifd: &Ifd) -> Result<Page, TiffError> {
+        if ifd.uint(TILE_WIDTH).is_some() {
+            return Err(TiffError::Unsupported("tiled images".into()));
+        }
+        let width = ifd.uint(IMAGE_WIDTH).ok_or(TiffError::Missing("ImageWidth"))? as usize;
+        let height = ifd.uint(IMAGE_LENGTH).ok_or(TiffError::Missing("ImageLength"))? as usize;
+        let samples = ifd.uint(SAMPLES_PER_PIXEL).unwrap_or(1) as usize;
+        let bits = ifd.uints(BITS_PER_SAMPLE).unwrap_or_else(|| vec![1]);
+        if bits.iter().any(|&b| b != bits[0]) {
+            return Err(TiffError::UnsupportedType(format!("mixed bits per sample {:?}", bits)));
+        }
+        let offsets = ifd.uints(STRIP_OFFSETS).ok_or(TiffError::Missing("StripOffsets"))?;
+        let counts = ifd.uints(STRIP_BYTE_COUNTS).ok_or(TiffError::Missing("StripByteCounts"))?;
+        if offsets.len() != counts.len() {
+            return Err(TiffError::InvalidData(format!("{} strip offsets but {} byte counts", offsets.len(), counts.len())));
+        }
+        let bytes = [height, samples, bits[0] as usize / 8].into_iter()
+            .try_fold(width, usize::checked_mul)
+            .ok_or_else(|| TiffError::InvalidData(format!(
+                "{}x{} page with {} samples of {} bits is too large", width, height, samples, bits[0]
+            )))?;
+        Ok(Page {
+            width,
+            height,
+            bits: bits[0],
+            sample_format: ifd.uint(SAMPLE_FORMAT).unwrap_or(UINT),
+            samples,
+            planar: ifd.uint(PLANAR_CONFIGURATION) == Some(2),
+            compression: ifd.uint(COMPRESSION).unwrap_or(UNCOMPRESSED),
+            predictor: ifd.uint(PREDICTOR).unwrap_or(1),
+            rows_per_strip: ifd.uint(ROWS_PER_STRIP).map_or(height, |r| (r as usize).clamp(1, height.max(1))),
+            strips: offsets.into_iter().zip(counts).collect(),
+            bytes,
+        })
+    }
+
+    fn sample_bytes(&self) -> usize {
+        self.bits as usize / 8
+    }
+
+    // Whether `other` has the same size and sample type, i.e. fits into one stack.
+    fn same_layout(&self, other: &Page) -> bool {
+        (self.width, self.height, self.bits, self.sample_format, self.samples)
+            == (other.width, other.height, other.bits, other.sample_format, other.samples)
+    }
+}
+
+
+fn decompress(raw: &[u8], compression: u64) -> Result<Vec<u8>, TiffError> {
+    match compression {
+        UNCOMPRESSED => Ok(raw.to_vec()),
+        LZW => weezl::decode::Decoder::with_tiff_size_switch(BitOrder::Msb, 8)
+            .decode(raw)
+            .map_err(|e| TiffError::Decompression(e.to_string())),
+        ADOBE_DEFLATE | DEFLATE => {
+            let mut buffer = Vec::new();
+            ZlibDecoder::new(raw).read_to_end(&mut buffer).map_err(|e| TiffError::Decompression(e.to_string()))?;
+            Ok(buffer)
+        }
+        other => Err(TiffError::Unsupported(format!("compression {}", other))),
+    }
+}
+
+
+// Undo predictor 2, each sample is stored as the difference to the one `stride` samples before it.
+fn undo_horizontal(row: &mut [u8], stride: usize, sample_bytes: usize, big_endian: bool) {
+    let mask = if sample_bytes == 8 { u64::MAX } else { (1u64 << (8 * sample_bytes)) - 1 };
+    let read = |bytes: &[u8]| bytes.iter().enumerate().fold(0u64, |acc, (i, &b)| {
+        let shift = if big_endian { 8 * (sample_bytes - 1 - i) } else { 8 * i };
+        acc | (b as u64) << shift
+    });
+    let step = stride * sample_bytes;
+    for start in (step..row.len()).step_by(sample_bytes) {
+        let sum = read(&row[start - step..start]).wrapping_add(read(&row[start..start + sample_bytes])) & mask;
+        for i in 0..sample_bytes {
+            let shift = if big_endian { 8 * (sample_bytes - 1 - i) } else { 8 * i };
+            row[start + i] = (sum >> shift) as u8;
+        }
+    }
+}
+
+
+// Undo predictor 3: byte-wise differences over a row whose sample bytes are
+// regrouped, all most significant bytes first.
+fn undo_floating_point(row: &mut [u8], stride: usize, sample_bytes: usize, big_endian: bool) {
+    for i in stride..row.len() {
+        row[i] = row[i].wrapping_add(row[i - stride]);
+    }
+    let count = row.len() / sample_bytes;
+    let shuffled = row.to_vec();
+    for i in 0..count {
+        for b in 0..sample_bytes {
+            let byte = if big_endian { b } else { sample_bytes - 1 - b };
+            row[i * sample_bytes + byte] = shuffled[b * count + i];
+        }
+    }
+}
+
+
+// Voxels of one page, in the file's byte order and with interleaved samples.
+fn read_page(bytes: &[u8], page: &Page, big_endian: bool) -> Result<Vec<u8>, TiffError> {
+    let planes = if page.planar { page.samples } else { 1 };
+    let row_samples = page.width * page.samples / planes;
+    let row_bytes = row_samples * page.sample_bytes();
+    let strips_per_plane = page.height.div_ceil(page.rows_per_strip).max(1);
+    if page.strips.len() < strips_per_plane * planes {
+        return Err(TiffError::InvalidData(format!("{} strips, expected {}", page.strips.len(), strips_per_plane * planes)));
+    }
+
+    let mut data = Vec::new();
+    for (s, &(offset, count)) in page.strips.iter().take(strips_per_plane * planes).enumerate() {
+        let rows = page.rows_per_strip.min(page.height - (s % strips_per_plane) * page.rows_per_strip);
+        let start = usize::try_from(offset).unwrap_or(usize::MAX);
+        let count = count as usize;
+        let found = bytes.len().saturating_sub(start);
+        if found < count {
+            return Err(TiffError::Truncated { expected: count, found });
+        }
+        let mut strip = decompress(&bytes[start..start + count], page.compression)?;
+        if strip.len() < rows * row_bytes {
+            return Err(TiffError::Truncated { expected: rows * row_bytes, found: strip.len() });
+        }
+        strip.truncate(rows * row_bytes);
+
+        let stride = page.samples / planes;
+        match page.predictor {
+            1 => {}
+            2 => strip.chunks_exact_mut(row_bytes).for_each(|row| undo_horizontal(row, stride, page.sample_bytes(), big_endian)),
+            3 => strip.chunks_exact_mut(row_bytes).for_each(|row| undo_floating_point(row, stride, page.sample_bytes(), big_endian)),
+            other => return Err(TiffError::Unsupported(format!("predictor {}", other))),
+        }
+        data.extend_from_slice(&strip);
+    }
+
+    // Separate planes, one after the other, get interleaved like `VectorImage` voxels.
+    if planes > 1 {
+        let size = page.sample_bytes();
+        let plane_len = page.width * page.height * size;
+        let mut interleaved = vec![0; data.len()];
+        for (p, plane) in data.chunks_exact(plane_len).enumerate() {
+            for (i, sample) in plane.chunks_exact(size).enumerate() {
+                let at = (i * planes + p) * size;
+                interleaved[at..at + size].copy_from_slice(sample);
+            }
+        }
+        data = interleaved;
+    }
+    Ok(data)
+}
+
+
+// Interleave `channels` consecutive pages (ImageJ's channel-first page order) into one page.
+fn interleave_pages(data: Vec<u8>, page_bytes: usize, channels: usize, sample_bytes: usize) -> Vec<u8> {
+    let mut interleaved = vec![0; data.len()];
+    for (group, pages) in data.chunks_exact(page_bytes * channels).enumerate() {
+        let out = &mut interleaved[group * page_bytes * channels..];
+        for (c, page) in pages.chunks_exact(page_bytes).enumerate() {
+            for (i, sample) in page.chunks_exact(sample_bytes).enumerate() {
+                let at = (i * channels + c) * sample_bytes;
+                out[at..at + sample_bytes].copy_from_slice(sample);
+            }
+        }
+    }
+    interleaved
+}
+
+
+// Layout of the loaded voxels.
+struct Stack {
+    size: Vec<usize>,
+    channels: usize,
+    spacing: Vec<f64>,
+    big_endian: bool,
+    metadata: Metadata,
+}
+
+
+// `build_image` for the sample type of a file.
+type BuildImage = fn(Vec<u8>, Stack) -> Result<Box<dyn AnyImage>, TiffError>;
+
+fn build_image<T: Pod + NumCast + 'static>(data: Vec<u8>, stack: Stack) -> Result<Box<dyn AnyImage>, TiffError> {
+    let voxels: Vec<T> = bytes_to_vec(data, stack.big_endian);
+    let n = stack.size.len();
+    let mut direction = vec![0.0; n * n];
+    (0..n).for_each(|i| direction[i * n + i] = 1.0);
+    let image = NdImage {
+        voxels,
+        size: stack.size.iter().map(|&s| s as u32).collect(),
+        spacing: stack.spacing,
+        origin: vec![0.0; n],
+        direction,
+        metadata: stack.metadata,
+    };
+
+    if stack.channels > 1 {
+        let image = Image::try_from(image).map_err(|_| TiffError::Unsupported(
+            format!("{} channels in a {}D stack, vector images are at most 3D", stack.channels, n)
+        ))?;
+        return Ok(Box::new(VectorImage {
+            voxels: image.voxels,
+            channels: stack.channels as u32,
+            width: image.width,
+            height: image.height,
+            depth: image.depth,
+            spacing: image.spacing,
+            origin: image.origin,
+            direction: image.direction,
+            metadata: image.metadata,
+        }));
+    }
+    match Image::try_from(image) {
+        Ok(image) => Ok(Box::new(image)),
+        Err(image) => Ok(Box::new(image)),
+    }
+}
+
+
+/// Load a (multi-page) TIFF file, one z-slice per page.
+///
+/// 8, 16, 32 and 64 bit integer and 32/64 bit float samples are supported,
+/// uncompressed or LZW/deflate compressed, in strips. Pages with several
+/// samples (RGB etc.) and ImageJ hyperstacks with channels load as
+/// `VectorImage`, ImageJ stacks with time frames as a 4D `NdImage`.
+///
+/// The pixel size comes from XResolution/YResolution, the slice distance
+/// from ImageJ's `spacing`. Both are in the file's unit, which is kept in
+/// the "unit" metadata entry (e.g. "µm" or "cm").
+pub fn load_tiff(filename: &str) -> Result<Box<dyn AnyImage>, TiffError> {
+    let mut bytes = Vec::new();
+    BufReader::new(File::open(filename)?).read_to_end(&mut bytes)?;
+    let ifds = read_ifds(&bytes)?;
+    // Reduced resolution pages (thumbnails, pyramid levels) are not part of the stack.
+    let ifds: Vec<&Ifd> = ifds.iter().filter(|ifd| ifd.uint(NEW_SUBFILE_TYPE).unwrap_or(0) & 1 == 0).collect();
+    let first = *ifds.first().ok_or_else(|| TiffError::InvalidData("no pages".into()))?;
+    let big_endian = first.big_endian;
+
+    let page = Page::from_ifd(first)?;
+    // Checked before any strip is read, the predictors only handle these.
+    let build: BuildImage = match (page.sample_format, page.bits) {
+        (UINT, 8) => build_image::<u8>,
+        (INT, 8) => build_image::<i8>,
+        (UINT, 16) => build_image::<u16>,
+        (INT, 16) => build_image::<i16>,
+        (UINT, 32) => build_image::<u32>,
+        (INT, 32) => build_image::<i32>,
+        (IEEEFP, 32) => build_image::<f32>,
+        (UINT, 64) => build_image::<u64>,
+        (INT, 64) => build_image::<i64>,
+        (IEEEFP, 64) => build_image::<f64>,
+        (format, bits) => return Err(TiffError::UnsupportedType(format!("sample format {} with {} bits", format, bits))),
+    };
+    let pages = ifds.iter().map(|ifd| Page::from_ifd(ifd)).collect::<Result<Vec<_>, _>>()?;
+    if let Some((i, other)) = pages.iter().enumerate().find(|(_, p)| !p.same_layout(&page)) {
+        return Err(TiffError::InconsistentPages(format!(
+            "page {} is {}x{}x{} with {} bit samples, page 0 {}x{}x{} with {} bit",
+            i, other.width, other.height, other.samples, other.bits, page.width, page.height, page.samples, page.bits
+        )));
+    }
+
+    let description = first.ascii(IMAGE_DESCRIPTION);
+    let imagej = description.as_deref().and_then(ImageJ::parse);
+    let mut metadata = Metadata::new();
+    if let (Some(description), None) = (&description, &imagej) {
+        metadata.insert("ImageDescription", description.clone());
+    }
+
+    let mut data = Vec::new();
+    let image_count = imagej.as_ref().and_then(|ij| ij.images).unwrap_or(1);
+    if pages.len() == 1 && image_count > 1 && page.compression == UNCOMPRESSED && !page.planar {
+        // ImageJ writes stacks over 4 GB with a single directory and the slices back to back.
+        let start = page.strips.first().ok_or(TiffError::Missing("StripOffsets"))?.0 as usize;
+        let expected = page.bytes.checked_mul(image_count).ok_or_else(|| TiffError::InvalidData(
+            format!("{} images of {} bytes", image_count, page.bytes)
+        ))?;
+        let found = bytes.len().saturating_sub(start);
+        if found < expected {
+            return Err(TiffError::Truncated { expected, found });
+        }
+        data.extend_from_slice(&bytes[start..start + expected]);
+    } else {
+        for page in &pages {
+            data.extend(read_page(&bytes, page, big_endian)?);
+        }
+    }
+    let page_count = data.len() / page.bytes.max(1);
+
+    // Pixel size from the resolution (pixels per unit), slice distance from ImageJ.
+    let resolution = |tag| first.real(tag).filter(|r| r.is_finite() && *r > 0.0).map_or(1.0, |r| 1.0 / r);
+    let mut spacing = vec![resolution(X_RESOLUTION), resolution(Y_RESOLUTION)];
+    let unit = match imagej.as_ref().and_then(|ij| ij.unit.clone()) {
+        Some(unit) => Some(unit),
+        None => match first.uint(RESOLUTION_UNIT) {
+            Some(2) => Some("inch".to_string()),
+            Some(3) => Some("cm".to_string()),
+            _ => None,
+        },
+    };
+    if let Some(unit) = unit {
+        metadata.insert("unit", unit);
+    }
+
+    let mut size = vec![page.width, page.height];
+    let mut channels = page.samples;
+    let ij = imagej.unwrap_or_default();
+    let ij_channels = ij.channels.unwrap_or(1);
+    if ij_channels > 1 && page.samples == 1 {
+        if page_count % ij_channels != 0 {
+            return Err(TiffError::InconsistentPages(format!("{} pages for {} channels", page_count, ij_channels)));
+        }
+        data = interleave_pages(data, page.bytes, ij_channels, page.sample_bytes());
+        channels = ij_channels;
+    }
+    let depth = page_count / (channels / page.samples);
+    let frames = ij.frames.unwrap_or(1);
+    if frames > 1 && depth % frames == 0 && depth / frames > 1 {
+        size.extend([depth / frames, frames]);
+        spacing.extend([ij.spacing.unwrap_or(1.0), ij.finterval.unwrap_or(1.0)]);
+    } else {
+        size.push(depth);
+        spacing.push(ij.spacing.unwrap_or(1.0));
+    }
+
+    let stack = Stack { size, channels, spacing, big_endian, metadata };
+    build(data, stack)
+}
+
+
+fn sample_format<T: 'static>() -> Option<(u64, u16)> {
+    let tid = TypeId::of::<T>();
+    [
+        (TypeId::of::<u8>(), (UINT, 8)), (TypeId::of::<i8>(), (INT, 8)),
+        (TypeId::of::<u16>(), (UINT, 16)), (TypeId::of::<i16>(), (INT, 16)),
+        (TypeId::of::<u32>(), (UINT, 32)), (TypeId::of::<i32>(), (INT, 32)), (TypeId::of::<f32>(), (IEEEFP, 32)),
+        (TypeId::of::<u64>(), (UINT, 64)), (TypeId::of::<i64>(), (INT, 64)), (TypeId::of::<f64>(), (IEEEFP, 64)),
+    ].into_iter().find(|(t, _)| *t == tid).map(|(_, format)| format)
+}
+
+
+// Pixels per unit as a rational; spacing with up to 6 decimals stays exact.
+fn resolution(spacing: f64) -> (u32, u32) {
+    if !(spacing.is_finite() && spacing > 0.0) {
+        return (1, 1);
+    }
+    let scale = (1e6f64).min(u32::MAX as f64 / spacing).max(1.0);
+    (scale as u32, ((spacing * scale).round() as u32).max(1))
+}
+
+
+// Everything the writer needs to know about an image besides its voxels.
+struct ImageInfo<'a> {
+    size: &'a [u32],
+    spacing: &'a [f64],
+    channels: u32,
+    metadata: &'a Metadata,
+}
+
+
+fn write_tiff<T: Pod + 'static>(voxels: &[T], info: &ImageInfo, file_path: &str, compression: TiffCompression) -> Result<(), TiffError> {
+    let (format, bits) = sample_format::<T>().ok_or_else(|| TiffError::UnsupportedType(std::any::type_name::<T>().into()))?;
+    if !(2..=4).contains(&info.size.len()) {
+        return Err(TiffError::Unsupported(format!("{}D images, TIFF stacks are 2D to 4D", info.size.len())));
+    }
+    if info.size.contains(&0) || info.channels == 0 {
+        return Err(TiffError::Unsupported(format!("empty image of size {:?}", info.size)));
+    }
+    let (width, height) = (info.size[0], info.size[1]);
+    let slices = info.size.get(2).copied().unwrap_or(1) as usize;
+    let frames = info.size.get(3).copied().unwrap_or(1) as usize;
+    let samples = info.channels as usize;
+    let page_len = width as usize * height as usize * samples;
+    let spacing = padded3(info.spacing, 1.0);
+
+    // ImageJ reads the stack layout and slice distance from the description of the first page.
+    let mut description = format!("ImageJ=1.11a\nimages={}\n", slices * frames);
+    if frames > 1 {
+        description.push_str(&format!("slices={}\nframes={}\nhyperstack=true\n", slices, frames));
+    } else if slices > 1 {
+        description.push_str(&format!("slices={}\n", slices));
+    }
+    if let Some(unit) = info.metadata.get("unit") {
+        description.push_str(&format!("unit={}\n", unit.replace('\u{b5}', "\\u00B5")));
+    }
+    description.push_str(&format!("spacing={}\n", spacing[2]));
+    if frames > 1 {
+        description.push_str(&format!("finterval={}\n", info.spacing[3]));
+    }
+    description.push_str("loop=false\n");
+
+    // Strips plus directories, with a margin for compression that does not pay off.
+    let estimate = std::mem::size_of_val(voxels) as u64 * 9 / 8 + (slices * frames) as u64 * 1024 + description.len() as u64;
+    let big_tiff = estimate >= u32::MAX as u64;
+
+    let mut file = BufWriter::new(File::create(file_path)?);
+    let mut position;
+    if big_tiff {
+        file.write_all(b"II\x2b\x00\x08\x00\x00\x00")?;
+        file.write_all(&16u64.to_le_bytes())?;
+        position = 16;
+    } else {
+        file.write_all(b"II\x2a\x00")?;
+        file.write_all(&8u32.to_le_bytes())?;
+        position = 8;
+    }
+
+    let pages = slices * frames;
+    for (p, page) in voxels.chunks_exact(page_len).enumerate() {
+        let raw = vec_to_bytes(page, false);
+        let data = match compression {
+            TiffCompression::None => raw.into_owned(),
+            TiffCompression::Lzw => weezl::encode::Encoder::with_tiff_size_switch(BitOrder::Msb, 8)
+                .encode(&raw)
+                .map_err(|e| TiffError::InvalidData(e.to_string()))?,
+            TiffCompression::Deflate => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(&raw)?;
+                encoder.finish()?
+            }
+        };
+
+        // Directory first, its strip right after it.
+        let mut ifd = IfdWriter::default();
+        ifd.long(NEW_SUBFILE_TYPE, 0);
+        ifd.long(IMAGE_WIDTH, width);
+        ifd.long(IMAGE_LENGTH, height);
+        ifd.short(BITS_PER_SAMPLE, &vec![bits; samples]);
+        ifd.short(COMPRESSION, &[match compression {
+            TiffCompression::None => UNCOMPRESSED as u16,
+            TiffCompression::Lzw => LZW as u16,
+            TiffCompression::Deflate => ADOBE_DEFLATE as u16,
+        }]);
+        // RGB(A) for 3 and 4 channels, otherwise gray with extra samples.
+        let rgb = samples == 3 || samples == 4;
+        ifd.short(PHOTOMETRIC_INTERPRETATION, &[if rgb { 2 } else { 1 }]);
+        if p == 0 {
+            ifd.ascii(IMAGE_DESCRIPTION, &description);
+            ifd.ascii(SOFTWARE, "oxels");
+        }
+        ifd.short(SAMPLES_PER_PIXEL, &[samples as u16]);
+        ifd.long(ROWS_PER_STRIP, height);
+        let (x_num, x_den) = resolution(spacing[0]);
+        let (y_num, y_den) = resolution(spacing[1]);
+        ifd.rational(X_RESOLUTION, x_num, x_den);
+        ifd.rational(Y_RESOLUTION, y_num, y_den);
+        ifd.short(PLANAR_CONFIGURATION, &[1]);
+        ifd.short(RESOLUTION_UNIT, &[match info.metadata.get("unit") {
+            Some("inch") => 2,
+            Some("cm") => 3,
+            _ => 1,
+        }]);
+        let extra = if rgb { samples - 3 } else { samples - 1 };
+        if extra > 0 {
+            // Unassociated alpha for RGBA, unspecified otherwise.
+            ifd.short(EXTRA_SAMPLES, &vec![if rgb { 2 } else { 0 }; extra]);
+        }
+        ifd.short(SAMPLE_FORMAT, &vec![format as u16; samples]);
+
+        ifd.offsets(STRIP_BYTE_COUNTS, &[data.len() as u64], big_tiff);
+        // The offset value does not change the directory size.
+        let mut sized = ifd.clone();
+        sized.offsets(STRIP_OFFSETS, &[0], big_tiff);
+        let data_position = position + sized.size(big_tiff);
+        ifd.offsets(STRIP_OFFSETS, &[data_position], big_tiff);
+        let mut end = data_position + data.len() as u64;
+        end += end % 2;
+        let next = if p + 1 < pages { end } else { 0 };
+
+        ifd.write(&mut file, position, next, big_tiff)?;
+        file.write_all(&data)?;
+        if data.len() % 2 == 1 {
+            file.write_all(&[0])?;
+        }
+        position = end;
+    }
+    file.flush()?;
+    Ok(())
+}
+
+
+fn try_save<T: Pod + 'static>(img: &dyn AnyImage, file_path: &str, compression: TiffCompression) -> Option<Result<(), TiffError>> {
+    let parts = ImageParts::<T>::of(img)?;
+    let info = ImageInfo { size: &parts.size, spacing: parts.spacing, channels: parts.channels, metadata: parts.metadata };
+    Some(write_tiff(parts.voxels, &info, file_path, compression))
+}
+
+
+/// Save as a multi-page TIFF, one page per z-slice, readable by ImageJ/Fiji.
+///
+/// The pixel size goes into XResolution/YResolution, the slice distance
+/// and the "unit" metadata entry into an ImageJ description. 4D images
+/// are written as ImageJ hyperstacks with time frames, `VectorImage`s
+/// with one sample per channel (RGB for 3 and 4 channels). The origin
+/// and direction are not stored.
+pub fn save_tiff(img: &dyn AnyImage, file_path: &str, compression: TiffCompression) -> Result<(), TiffError> {
+    first_pixel_type!(try_save(img, file_path, compression))
+        .unwrap_or_else(|| Err(TiffError::UnsupportedType("unsupported pixel type".into())))
+}
@@ -0,0 +1,6 @@
+pub mod error;
+pub mod ifd;
+pub mod image;
+
+pub use error::TiffError;
+pub use image::{load_tiff, save_tiff, TiffCompression};
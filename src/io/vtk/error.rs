@@ -0,0 +1,43 @@
+#[derive(Debug)]
+pub enum VtkError {
+    Missing(&'static str),  // Missing keywords or attributes that we need
+    Io(std::io::Error),     // Generic I/O error (file issue)
+    InvalidHeader(String),  // Not a VTK file, or one we can not parse
+    UnsupportedType(String),
+    Unsupported(String),
+    Truncated { expected: usize, found: usize },
+    MissingValues { expected: usize, found: usize },
+    Decompression(std::io::Error),
+}
+
+impl std::fmt::Display for VtkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VtkError::Missing(what) => write!(f, "Missing VTK field: {}", what),
+            VtkError::Io(e) => write!(f, "I/O error: {}", e),
+            VtkError::InvalidHeader(what) => write!(f, "Invalid VTK file: {}", what),
+            VtkError::UnsupportedType(t) => write!(f, "Unsupported VTK data type: {}", t),
+            VtkError::Unsupported(what) => write!(f, "Unsupported VTK file: {}", what),
+            VtkError::Truncated { expected, found } => {
+                write!(f, "Truncated voxel data: expected {} bytes, found {}", expected, found)
+            }
+            VtkError::MissingValues { expected, found } => {
+                write!(f, "ASCII voxel data has {} values, expected {}", found, expected)
+            }
+            VtkError::Decompression(e) => write!(f, "Failed to decompress voxel data: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for VtkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            VtkError::Io(e) | VtkError::Decompression(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for VtkError {
+    fn from(e: std::io::Error) -> Self { VtkError::Io(e) }
+}
@@ -0,0 +1,123 @@
+use super::error::VtkError;
+
+/// How `save_vtk` stores the voxels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VtkEncoding {
+    /// Whitespace separated text.
+    Ascii,
+    /// Binary; appended raw data in .vti files.
+    #[default]
+    Binary,
+    /// zlib compressed appended data, .vti only.
+    Zlib,
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ElementType {
+    I8, U8,
+    I16, U16,
+    I32, U32, F32,
+    I64, U64, F64,
+}
+
+impl ElementType {
+    /// Type names of legacy files.
+    pub(super) fn from_legacy(name: &str) -> Result<Self, VtkError> {
+        use ElementType::*;
+        Ok(match name {
+            "char" | "signed_char" => I8,
+            "unsigned_char" => U8,
+            "short" => I16,
+            "unsigned_short" => U16,
+            "int" => I32,
+            "unsigned_int" => U32,
+            "float" => F32,
+            "long" | "vtktypeint64" => I64,
+            "unsigned_long" | "vtktypeuint64" => U64,
+            "double" => F64,
+            other => return Err(VtkError::UnsupportedType(other.to_string())),
+        })
+    }
+
+    pub(super) fn legacy_name(self) -> &'static str {
+        use ElementType::*;
+        match self {
+            I8 => "char", U8 => "unsigned_char",
+            I16 => "short", U16 => "unsigned_short",
+            I32 => "int", U32 => "unsigned_int", F32 => "float",
+            I64 => "vtktypeint64", U64 => "vtktypeuint64", F64 => "double",
+        }
+    }
+
+    /// `type` attribute of XML data arrays.
+    pub(super) fn from_xml(name: &str) -> Result<Self, VtkError> {
+        use ElementType::*;
+        Ok(match name {
+            "Int8" | "Char" => I8,
+            "UInt8" => U8,
+            "Int16" => I16,
+            "UInt16" => U16,
+            "Int32" => I32,
+            "UInt32" => U32,
+            "Float32" => F32,
+            "Int64" => I64,
+            "UInt64" => U64,
+            "Float64" => F64,
+            other => return Err(VtkError::UnsupportedType(other.to_string())),
+        })
+    }
+
+    pub(super) fn xml_name(self) -> &'static str {
+        use ElementType::*;
+        match self {
+            I8 => "Int8", U8 => "UInt8",
+            I16 => "Int16", U16 => "UInt16",
+            I32 => "Int32", U32 => "UInt32", F32 => "Float32",
+            I64 => "Int64", U64 => "UInt64", F64 => "Float64",
+        }
+    }
+}
+
+
+// Voxel data as found in the file.
+#[derive(Debug)]
+pub(super) enum Values {
+    Binary { bytes: Vec<u8>, big_endian: bool },
+    Ascii(String),
+}
+
+
+/// Image data of a legacy or XML file, with the geometry in our conventions.
+#[derive(Debug)]
+pub(super) struct VtkData {
+    pub size: [usize; 3],
+    pub spacing: [f64; 3],
+    pub origin: [f64; 3],
+    /// Rows are the axis directions, as `Image::direction`.
+    pub direction: [f64; 9],
+    pub components: usize,
+    pub element_type: ElementType,
+    pub name: String,
+    pub values: Values,
+}
+
+
+/// Voxels per axis, checked to fit the `u32` sizes of `Image`.
+pub(super) fn image_size(size: [f64; 3], field: &str) -> Result<[usize; 3], VtkError> {
+    if size.iter().any(|s| !(0.0..=u32::MAX as f64).contains(s)) {
+        return Err(VtkError::InvalidHeader(format!("{} of {:?} voxels", field, size)));
+    }
+    Ok(size.map(|s| s as usize))
+}
+
+/// Number of voxels times `components`, `None` if it overflows.
+pub(super) fn value_count(size: &[usize; 3], components: usize) -> Option<usize> {
+    size.iter().try_fold(components, |count, &s| count.checked_mul(s))
+}
+
+
+/// The rows of `direction` as columns, VTK (like ITK) stores axis directions in columns.
+pub(super) fn transpose(direction: &[f64; 9]) -> [f64; 9] {
+    std::array::from_fn(|i| direction[(i % 3) * 3 + i / 3])
+}
@@ -0,0 +1,202 @@
+use crate::image::{Image, VectorImage, AnyImage, Metadata};
+use crate::image::dyn_image::{first_pixel_type, ImageParts};
+use crate::image::nd_image::{direction3, padded3};
+use crate::io::meta_image::image::{bytes_to_vec, vec_to_bytes};
+
+use super::error::VtkError;
+use super::header::{value_count, ElementType, Values, VtkData, VtkEncoding};
+use super::legacy::{parse_legacy, write_legacy};
+use super::xml::{parse_xml, write_xml};
+use std::any::TypeId;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use bytemuck::Pod;
+use num_traits::NumCast;
+
+// Name of the point data array we write.
+const ARRAY_NAME: &str = "scalars";
+
+
+fn parse_value<T: FromStr + NumCast>(token: &str) -> Result<T, VtkError> {
+    // Integer arrays are sometimes written as "3.0".
+    token.parse::<T>().ok()
+        .or_else(|| token.parse::<f64>().ok().and_then(NumCast::from))
+        .ok_or_else(|| VtkError::InvalidHeader(format!("bad voxel value '{}'", token)))
+}
+
+
+fn build_image<T>(data: VtkData) -> Result<Box<dyn AnyImage>, VtkError>
+where
+    T: Pod + NumCast + FromStr + Copy + 'static,
+{
+    let too_large = || VtkError::InvalidHeader(format!(
+        "{:?} voxels with {} components is too large", data.size, data.components
+    ));
+    let count = value_count(&data.size, data.components).ok_or_else(too_large)?;
+    let voxels: Vec<T> = match data.values {
+        Values::Binary { mut bytes, big_endian } => {
+            let expected = count.checked_mul(size_of::<T>()).ok_or_else(too_large)?;
+            if bytes.len() < expected {
+                return Err(VtkError::Truncated { expected, found: bytes.len() });
+            }
+            bytes.truncate(expected);
+            bytes_to_vec(bytes, big_endian)
+        }
+        Values::Ascii(text) => {
+            // Legacy files may have more sections after the voxels.
+            let voxels = text.split_whitespace().take(count).map(parse_value).collect::<Result<Vec<T>, _>>()?;
+            if voxels.len() != count {
+                return Err(VtkError::MissingValues { expected: count, found: voxels.len() });
+            }
+            voxels
+        }
+    };
+
+    let [width, height, depth] = data.size.map(|s| s as u32);
+    if data.components > 1 {
+        return Ok(Box::new(VectorImage {
+            voxels,
+            channels: data.components as u32,
+            width,
+            height,
+            depth,
+            spacing: data.spacing,
+            origin: data.origin,
+            direction: data.direction,
+            metadata: Metadata::default(),
+        }));
+    }
+    Ok(Box::new(Image {
+        voxels,
+        width,
+        height,
+        depth,
+        spacing: data.spacing,
+        origin: data.origin,
+        direction: data.direction,
+        metadata: Metadata::default(),
+    }))
+}
+
+
+/// Load a legacy VTK STRUCTURED_POINTS file (.vtk) or an XML ImageData
+/// file (.vti), told apart by their content.
+///
+/// Point data scalars load as `Image<T>`, arrays with more than one
+/// component (e.g. VECTORS) as `VectorImage<T>`. Spacing, origin and
+/// direction come from the dataset, a .vti extent that does not start at 0
+/// moves the origin to its first voxel. Legacy files support ASCII and
+/// BINARY data, .vti files ASCII and raw or zlib compressed appended data.
+pub fn load_vtk(filename: &str) -> Result<Box<dyn AnyImage>, VtkError> {
+    let mut bytes = Vec::new();
+    BufReader::new(File::open(filename)?).read_to_end(&mut bytes)?;
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(512)]).to_string();
+    let data = if head.starts_with(super::legacy::MAGIC) {
+        parse_legacy(bytes)?
+    } else if head.contains(super::xml::MAGIC) {
+        parse_xml(bytes)?
+    } else {
+        return Err(VtkError::InvalidHeader("neither a legacy VTK nor a VTK XML file".into()));
+    };
+
+    match data.element_type {
+        ElementType::U8 => build_image::<u8>(data),
+        ElementType::I8 => build_image::<i8>(data),
+        ElementType::U16 => build_image::<u16>(data),
+        ElementType::I16 => build_image::<i16>(data),
+        ElementType::U32 => build_image::<u32>(data),
+        ElementType::I32 => build_image::<i32>(data),
+        ElementType::F32 => build_image::<f32>(data),
+        ElementType::U64 => build_image::<u64>(data),
+        ElementType::I64 => build_image::<i64>(data),
+        ElementType::F64 => build_image::<f64>(data),
+    }
+}
+
+
+fn element_type<T: 'static>() -> Option<ElementType> {
+    let tid = TypeId::of::<T>();
+    [
+        (TypeId::of::<u8>(), ElementType::U8), (TypeId::of::<i8>(), ElementType::I8),
+        (TypeId::of::<u16>(), ElementType::U16), (TypeId::of::<i16>(), ElementType::I16),
+        (TypeId::of::<u32>(), ElementType::U32), (TypeId::of::<i32>(), ElementType::I32),
+        (TypeId::of::<f32>(), ElementType::F32),
+        (TypeId::of::<u64>(), ElementType::U64), (TypeId::of::<i64>(), ElementType::I64),
+        (TypeId::of::<f64>(), ElementType::F64),
+    ].into_iter().find(|(t, _)| *t == tid).map(|(_, etype)| etype)
+}
+
+
+#[allow(clippy::too_many_arguments)]
+fn to_data<T: Pod + Display + 'static>(
+    voxels: &[T], size: [u32; 3], components: u32, spacing: [f64; 3], origin: [f64; 3], direction: [f64; 9],
+    encoding: VtkEncoding, big_endian: bool,
+) -> VtkData {
+    let values = match encoding {
+        VtkEncoding::Ascii => {
+            // One line per row of voxels.
+            let row = (size[0] * components).max(1) as usize;
+            let lines: Vec<String> = voxels.chunks(row)
+                .map(|r| r.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" "))
+                .collect();
+            Values::Ascii(lines.join("\n"))
+        }
+        VtkEncoding::Binary | VtkEncoding::Zlib => {
+            Values::Binary { bytes: vec_to_bytes(voxels, big_endian).into_owned(), big_endian }
+        }
+    };
+    VtkData {
+        size: size.map(|s| s as usize),
+        spacing,
+        origin,
+        direction,
+        components: components as usize,
+        element_type: element_type::<T>().unwrap(),
+        name: ARRAY_NAME.to_string(),
+        values,
+    }
+}
+
+
+fn try_data<T: Pod + Display + 'static>(img: &dyn AnyImage, encoding: VtkEncoding, big_endian: bool) -> Option<Result<VtkData, VtkError>> {
+    let parts = ImageParts::<T>::of(img)?;
+    let n = parts.size.len();
+    if n > 3 {
+        return Some(Err(VtkError::Unsupported(format!("{}D images, VTK image data is at most 3D", n))));
+    }
+    let size = [0, 1, 2].map(|a| parts.size.get(a).copied().unwrap_or(1));
+    let (spacing, origin, direction) = (padded3(parts.spacing, 1.0), padded3(parts.origin, 0.0), direction3(parts.direction, n));
+    Some(Ok(to_data(parts.voxels, size, parts.channels, spacing, origin, direction, encoding, big_endian)))
+}
+
+
+/// Save as legacy VTK STRUCTURED_POINTS (.vtk) or XML ImageData (.vti),
+/// picked by the extension of `file_path`.
+///
+/// Legacy files are written big-endian with a DIRECTION line (VTK 9) if
+/// the image is not axis aligned; .vti files are little-endian with
+/// appended data, zlib compressed with `VtkEncoding::Zlib`. `VectorImage`s
+/// are written as multi-component scalars.
+pub fn save_vtk(img: &dyn AnyImage, file_path: &str, encoding: VtkEncoding) -> Result<(), VtkError> {
+    let xml = Path::new(file_path).extension().is_some_and(|e| e.eq_ignore_ascii_case("vti"));
+    if !xml && encoding == VtkEncoding::Zlib {
+        return Err(VtkError::Unsupported("zlib compression in legacy files, save as .vti".into()));
+    }
+    // Legacy binary data is always big-endian.
+    let big_endian = !xml;
+    let data = first_pixel_type!(try_data(img, encoding, big_endian))
+        .unwrap_or_else(|| Err(VtkError::UnsupportedType("unsupported pixel type".into())))?;
+
+    let mut file = BufWriter::new(File::create(file_path)?);
+    if xml {
+        write_xml(&mut file, &data, encoding == VtkEncoding::Zlib)?;
+    } else {
+        write_legacy(&mut file, &data)?;
+    }
+    file.flush()?;
+    Ok(())
+}
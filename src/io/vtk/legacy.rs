@@ -0,0 +1,147 @@
+use std::io::Write;
+
+use super::error::VtkError;
+use super::header::{image_size, transpose, value_count, ElementType, Values, VtkData};
+
+pub(super) const MAGIC: &str = "# vtk DataFile Version";
+
+const IDENTITY: [f64; 9] = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+
+
+// Lines of the header, the voxel data starts where the last line ended.
+struct Lines {
+    bytes: Vec<u8>,
+    pos: usize,
+}
+
+impl Lines {
+    fn next(&mut self) -> Option<String> {
+        if self.pos >= self.bytes.len() {
+            return None;
+        }
+        let end = self.bytes[self.pos..].iter().position(|&b| b == b'\n').map_or(self.bytes.len(), |i| self.pos + i);
+        let line = String::from_utf8_lossy(&self.bytes[self.pos..end]).trim().to_string();
+        self.pos = (end + 1).min(self.bytes.len());
+        Some(line)
+    }
+
+    // Next line that is not empty, `None` at the end of the file.
+    fn next_nonempty(&mut self) -> Option<String> {
+        loop {
+            match self.next() {
+                Some(line) if line.is_empty() => continue,
+                other => return other,
+            }
+        }
+    }
+}
+
+
+fn numbers<const N: usize>(keyword: &str, values: &[&str]) -> Result<[f64; N], VtkError> {
+    let parsed: Vec<f64> = values.iter().map(|v| v.parse::<f64>()).collect::<Result<_, _>>()
+        .map_err(|_| VtkError::InvalidHeader(format!("{} {}", keyword, values.join(" "))))?;
+    parsed.try_into().map_err(|_| VtkError::InvalidHeader(format!("{} needs {} values", keyword, N)))
+}
+
+
+/// Parse a legacy STRUCTURED_POINTS file with POINT_DATA scalars or vectors.
+pub(super) fn parse_legacy(bytes: Vec<u8>) -> Result<VtkData, VtkError> {
+    let mut lines = Lines { bytes, pos: 0 };
+    let first = lines.next().unwrap_or_default();
+    if !first.starts_with(MAGIC) {
+        return Err(VtkError::InvalidHeader(format!("expected '{}', found '{}'", MAGIC, first)));
+    }
+    let _title = lines.next();
+    let binary = match lines.next_nonempty().unwrap_or_default().to_uppercase().as_str() {
+        "BINARY" => true,
+        "ASCII" => false,
+        other => return Err(VtkError::InvalidHeader(format!("expected ASCII or BINARY, found '{}'", other))),
+    };
+
+    let mut dataset = false;
+    let mut size = None;
+    let mut spacing = [1.0; 3];
+    let mut origin = [0.0; 3];
+    let mut direction = IDENTITY;
+    let mut point_data = None;
+    let (name, element_type, components) = loop {
+        let line = lines.next_nonempty().ok_or(VtkError::Missing("POINT_DATA"))?;
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let keyword = tokens[0].to_uppercase();
+        match (keyword.as_str(), &tokens[1..]) {
+            ("DATASET", [kind]) if kind.eq_ignore_ascii_case("STRUCTURED_POINTS") => dataset = true,
+            ("DATASET", [kind]) => return Err(VtkError::Unsupported(format!("DATASET {}", kind))),
+            ("DIMENSIONS", values) => {
+                size = Some(image_size(numbers::<3>("DIMENSIONS", values)?, "DIMENSIONS")?);
+            }
+            ("SPACING" | "ASPECT_RATIO", values) => spacing = numbers::<3>(&keyword, values)?,
+            ("ORIGIN", values) => origin = numbers::<3>("ORIGIN", values)?,
+            ("DIRECTION", values) => direction = transpose(&numbers::<9>("DIRECTION", values)?),
+            ("POINT_DATA", [count]) => {
+                point_data = Some(count.parse::<usize>().map_err(|_| VtkError::InvalidHeader(line.clone()))?);
+            }
+            ("SCALARS", [name, type_name, rest @ ..]) => {
+                let components = match rest.first() {
+                    Some(n) => n.parse::<usize>().map_err(|_| VtkError::InvalidHeader(line.clone()))?,
+                    None => 1,
+                };
+                // A lookup table line follows, it has no meaning for us.
+                let before = lines.pos;
+                if !lines.next_nonempty().is_some_and(|l| l.to_uppercase().starts_with("LOOKUP_TABLE")) {
+                    lines.pos = before;
+                }
+                break (name.to_string(), ElementType::from_legacy(type_name)?, components);
+            }
+            ("VECTORS", [name, type_name]) => break (name.to_string(), ElementType::from_legacy(type_name)?, 3),
+            ("CELL_DATA" | "FIELD" | "COLOR_SCALARS" | "NORMALS" | "TENSORS" | "TEXTURE_COORDINATES", _) => {
+                return Err(VtkError::Unsupported(format!("{} (only POINT_DATA SCALARS and VECTORS)", keyword)));
+            }
+            _ => return Err(VtkError::InvalidHeader(line.clone())),
+        }
+    };
+
+    if !dataset {
+        return Err(VtkError::Missing("DATASET"));
+    }
+    let size = size.ok_or(VtkError::Missing("DIMENSIONS"))?;
+    let count = value_count(&size, 1)
+        .ok_or_else(|| VtkError::InvalidHeader(format!("DIMENSIONS {:?} is too large", size)))?;
+    if point_data.is_some_and(|n| n != count) {
+        return Err(VtkError::InvalidHeader(format!("POINT_DATA {} for {:?} points", point_data.unwrap(), size)));
+    }
+
+    let data = lines.bytes.split_off(lines.pos);
+    let values = if binary {
+        Values::Binary { bytes: data, big_endian: true }
+    } else {
+        Values::Ascii(String::from_utf8_lossy(&data).to_string())
+    };
+    Ok(VtkData { size, spacing, origin, direction, components, element_type, name, values })
+}
+
+
+/// Write a legacy STRUCTURED_POINTS file. `values` are big-endian bytes
+/// (BINARY) or whitespace separated text (ASCII).
+pub(super) fn write_legacy<W: Write>(writer: &mut W, data: &VtkData) -> Result<(), VtkError> {
+    let join = |values: &[f64]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" ");
+    writeln!(writer, "{} 3.0", MAGIC)?;
+    writeln!(writer, "oxels")?;
+    writeln!(writer, "{}", if matches!(data.values, Values::Ascii(_)) { "ASCII" } else { "BINARY" })?;
+    writeln!(writer, "DATASET STRUCTURED_POINTS")?;
+    writeln!(writer, "DIMENSIONS {} {} {}", data.size[0], data.size[1], data.size[2])?;
+    writeln!(writer, "SPACING {}", join(&data.spacing))?;
+    writeln!(writer, "ORIGIN {}", join(&data.origin))?;
+    // Only VTK 9 knows DIRECTION, leave it out for axis aligned images.
+    if data.direction != IDENTITY {
+        writeln!(writer, "DIRECTION {}", join(&transpose(&data.direction)))?;
+    }
+    writeln!(writer, "POINT_DATA {}", data.size.iter().product::<usize>())?;
+    writeln!(writer, "SCALARS {} {} {}", data.name, data.element_type.legacy_name(), data.components)?;
+    writeln!(writer, "LOOKUP_TABLE default")?;
+    match &data.values {
+        Values::Binary { bytes, .. } => writer.write_all(bytes)?,
+        Values::Ascii(text) => writer.write_all(text.as_bytes())?,
+    }
+    writeln!(writer)?;
+    Ok(())
+}
@@ -0,0 +1,9 @@
+pub mod error;
+pub mod header;
+pub mod image;
+pub mod legacy;
+pub mod xml;
+
+pub use error::VtkError;
+pub use header::VtkEncoding;
+pub use image::{load_vtk, save_vtk};
@@ -0,0 +1,287 @@
+use std::io::{Read, Write};
+
+use super::error::VtkError;
+use super::header::{image_size, transpose, ElementType, Values, VtkData};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+pub(super) const MAGIC: &str = "<VTKFile";
+
+const ZLIB: &str = "vtkZLibDataCompressor";
+
+// Uncompressed size of the zlib blocks we write, as VTK's default.
+const BLOCK_SIZE: usize = 1 << 15;
+
+
+// An opening (or empty element) tag with its attributes, or a closing tag.
+#[derive(Debug)]
+struct Tag {
+    name: String,
+    closing: bool,
+    attributes: Vec<(String, String)>,
+    // Byte position just after the '>'.
+    end: usize,
+}
+
+impl Tag {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+}
+
+
+// All tags of `text`, skipping comments, processing instructions and declarations.
+fn tags(text: &str) -> Result<Vec<Tag>, VtkError> {
+    let mut tags = Vec::new();
+    let mut pos = 0;
+    while let Some(start) = text[pos..].find('<').map(|i| pos + i) {
+        let end = text[start..].find('>').map(|i| start + i)
+            .ok_or_else(|| VtkError::InvalidHeader("unterminated XML tag".into()))?;
+        pos = end + 1;
+        let inner = text[start + 1..end].trim_end_matches('/');
+        if inner.starts_with(['?', '!']) {
+            continue;
+        }
+        let (closing, inner) = match inner.strip_prefix('/') {
+            Some(rest) => (true, rest),
+            None => (false, inner),
+        };
+        let name_end = inner.find(char::is_whitespace).unwrap_or(inner.len());
+        let mut attributes = Vec::new();
+        let mut rest = &inner[name_end..];
+        while let Some(eq) = rest.find('=') {
+            let key = rest[..eq].trim().to_string();
+            let value = rest[eq + 1..].trim_start();
+            let quote = value.chars().next()
+                .filter(|c| *c == '"' || *c == '\'')
+                .ok_or_else(|| VtkError::InvalidHeader(format!("unquoted XML attribute {}", key)))?;
+            let close = value[1..].find(quote)
+                .ok_or_else(|| VtkError::InvalidHeader(format!("unterminated XML attribute {}", key)))?;
+            attributes.push((key, value[1..close + 1].to_string()));
+            rest = &value[close + 2..];
+        }
+        tags.push(Tag { name: inner[..name_end].to_string(), closing, attributes, end: pos });
+    }
+    Ok(tags)
+}
+
+
+fn numbers<const N: usize>(tag: &Tag, name: &str) -> Result<Option<[f64; N]>, VtkError> {
+    let Some(value) = tag.attribute(name) else { return Ok(None) };
+    let parsed: Vec<f64> = value.split_whitespace().map(|v| v.parse::<f64>()).collect::<Result<_, _>>()
+        .map_err(|_| VtkError::InvalidHeader(format!("{}=\"{}\"", name, value)))?;
+    parsed.try_into().map(Some).map_err(|_| VtkError::InvalidHeader(format!("{} needs {} values", name, N)))
+}
+
+
+// Reads the size words of the appended block headers.
+struct Words<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    wide: bool,
+    big_endian: bool,
+}
+
+impl Words<'_> {
+    fn next(&mut self) -> Result<usize, VtkError> {
+        let n = if self.wide { 8 } else { 4 };
+        let word = self.bytes.get(self.pos..self.pos + n)
+            .ok_or(VtkError::Truncated { expected: self.pos + n, found: self.bytes.len() })?;
+        self.pos += n;
+        let mut buf = [0u8; 8];
+        if self.big_endian {
+            buf[8 - n..].copy_from_slice(word);
+            Ok(u64::from_be_bytes(buf) as usize)
+        } else {
+            buf[..n].copy_from_slice(word);
+            Ok(u64::from_le_bytes(buf) as usize)
+        }
+    }
+}
+
+
+// Raw bytes of an appended array that starts at `bytes[0]`.
+fn read_appended(bytes: &[u8], wide: bool, big_endian: bool, zlib: bool) -> Result<Vec<u8>, VtkError> {
+    let mut words = Words { bytes, pos: 0, wide, big_endian };
+    if !zlib {
+        let n = words.next()?;
+        let start = words.pos;
+        return start.checked_add(n).and_then(|end| bytes.get(start..end)).map(<[u8]>::to_vec)
+            .ok_or(VtkError::Truncated { expected: start.saturating_add(n), found: bytes.len() });
+    }
+
+    let blocks = words.next()?;
+    let block_size = words.next()?;
+    let last_size = words.next()?;
+    let sizes = (0..blocks).map(|_| words.next()).collect::<Result<Vec<_>, _>>()?;
+    let mut pos = words.pos;
+    // The sizes are not trusted for preallocation, the output grows as blocks inflate.
+    let mut out = Vec::new();
+    for (i, size) in sizes.into_iter().enumerate() {
+        let block = pos.checked_add(size).and_then(|end| bytes.get(pos..end))
+            .ok_or(VtkError::Truncated { expected: pos.saturating_add(size), found: bytes.len() })?;
+        let expected = if i + 1 == blocks && last_size != 0 { last_size } else { block_size };
+        let before = out.len();
+        ZlibDecoder::new(block).read_to_end(&mut out).map_err(VtkError::Decompression)?;
+        if out.len() - before != expected {
+            return Err(VtkError::Truncated { expected: before.saturating_add(expected), found: out.len() });
+        }
+        pos += size;
+    }
+    Ok(out)
+}
+
+
+/// Parse a VTK XML ImageData (.vti) file with a single piece. Arrays can be
+/// ASCII, or raw or zlib compressed appended data.
+pub(super) fn parse_xml(bytes: Vec<u8>) -> Result<VtkData, VtkError> {
+    // Appended data is binary, the XML ends at its '_' marker.
+    let appended = match bytes.windows(13).position(|w| w == b"<AppendedData") {
+        Some(start) => Some(bytes[start..].iter().position(|&b| b == b'_').map(|i| start + i + 1)
+            .ok_or_else(|| VtkError::InvalidHeader("AppendedData without '_' marker".into()))?),
+        None => None,
+    };
+    let text = String::from_utf8_lossy(&bytes[..appended.unwrap_or(bytes.len())]);
+    let tags = tags(&text)?;
+
+    let file = tags.iter().find(|t| t.name == "VTKFile").ok_or(VtkError::Missing("VTKFile"))?;
+    match file.attribute("type") {
+        Some("ImageData") => {}
+        Some(other) => return Err(VtkError::Unsupported(format!("{} files, only ImageData", other))),
+        None => return Err(VtkError::Missing("VTKFile type")),
+    }
+    let big_endian = file.attribute("byte_order") == Some("BigEndian");
+    let wide = match file.attribute("header_type") {
+        None | Some("UInt32") => false,
+        Some("UInt64") => true,
+        Some(other) => return Err(VtkError::Unsupported(format!("header_type {}", other))),
+    };
+    let zlib = match file.attribute("compressor") {
+        None => false,
+        Some(ZLIB) => true,
+        Some(other) => return Err(VtkError::Unsupported(format!("compressor {}", other))),
+    };
+
+    let image = tags.iter().find(|t| t.name == "ImageData" && !t.closing).ok_or(VtkError::Missing("ImageData"))?;
+    let extent = numbers::<6>(image, "WholeExtent")?.ok_or(VtkError::Missing("WholeExtent"))?;
+    let spacing = numbers::<3>(image, "Spacing")?.unwrap_or([1.0; 3]);
+    let mut origin = numbers::<3>(image, "Origin")?.unwrap_or([0.0; 3]);
+    let direction = numbers::<9>(image, "Direction")?
+        .map(|d| transpose(&d))
+        .unwrap_or([1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]);
+    let pieces = tags.iter().filter(|t| t.name == "Piece" && !t.closing).count();
+    if pieces != 1 {
+        return Err(VtkError::Unsupported(format!("{} pieces, only files with a single piece", pieces)));
+    }
+
+    // Voxel (0, 0, 0) is at the extent's start.
+    let start = [extent[0], extent[2], extent[4]];
+    let size = image_size([0, 1, 2].map(|a| (extent[2 * a + 1] - extent[2 * a] + 1.0).max(0.0)), "WholeExtent")?;
+    for (axis, s) in start.iter().enumerate() {
+        for (o, d) in origin.iter_mut().zip(&direction[axis * 3..axis * 3 + 3]) {
+            *o += s * spacing[axis] * d;
+        }
+    }
+
+    // The array named by PointData's Scalars (or Vectors), else its first one.
+    let point_data = tags.iter().position(|t| t.name == "PointData" && !t.closing).ok_or(VtkError::Missing("PointData"))?;
+    let active = tags[point_data].attribute("Scalars").or(tags[point_data].attribute("Vectors"));
+    let arrays: Vec<&Tag> = tags[point_data + 1..].iter()
+        .take_while(|t| t.name != "PointData")
+        .filter(|t| t.name == "DataArray" && !t.closing)
+        .collect();
+    let array = arrays.iter().find(|t| active.is_some() && t.attribute("Name") == active)
+        .or(arrays.first())
+        .ok_or(VtkError::Missing("DataArray"))?;
+
+    let element_type = ElementType::from_xml(array.attribute("type").ok_or(VtkError::Missing("DataArray type"))?)?;
+    let components = match array.attribute("NumberOfComponents") {
+        Some(n) => n.parse::<usize>().map_err(|_| VtkError::InvalidHeader(format!("NumberOfComponents=\"{}\"", n)))?,
+        None => 1,
+    };
+    let name = array.attribute("Name").unwrap_or_default().to_string();
+    let values = match array.attribute("format") {
+        Some("ascii") => {
+            let content = &text[array.end..];
+            Values::Ascii(content[..content.find('<').unwrap_or(content.len())].to_string())
+        }
+        Some("appended") => {
+            let start = appended.ok_or(VtkError::Missing("AppendedData"))?;
+            let offset = array.attribute("offset").ok_or(VtkError::Missing("DataArray offset"))?;
+            let offset = offset.trim().parse::<usize>().map_err(|_| VtkError::InvalidHeader(format!("offset=\"{}\"", offset)))?;
+            if let Some(encoding) = tags.iter().find(|t| t.name == "AppendedData").and_then(|t| t.attribute("encoding")) {
+                if encoding != "raw" {
+                    return Err(VtkError::Unsupported(format!("{} encoded appended data", encoding)));
+                }
+            }
+            let data = bytes.get(start + offset..).ok_or(VtkError::Truncated { expected: start + offset, found: bytes.len() })?;
+            Values::Binary { bytes: read_appended(data, wide, big_endian, zlib)?, big_endian }
+        }
+        other => return Err(VtkError::Unsupported(format!("DataArray format {}", other.unwrap_or("(none)")))),
+    };
+    Ok(VtkData { size, spacing, origin, direction, components, element_type, name, values })
+}
+
+
+/// Write a VTK XML ImageData file, appended data for little-endian binary
+/// `values` (zlib compressed if `zlib`), inline for ASCII ones.
+pub(super) fn write_xml<W: Write>(writer: &mut W, data: &VtkData, zlib: bool) -> Result<(), VtkError> {
+    let join = |values: &[f64]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" ");
+    let extent = data.size.iter().map(|&s| format!("0 {}", s as i64 - 1)).collect::<Vec<_>>().join(" ");
+    let compressor = if zlib { format!(" compressor=\"{}\"", ZLIB) } else { String::new() };
+    writeln!(writer, "<?xml version=\"1.0\"?>")?;
+    writeln!(writer, "<VTKFile type=\"ImageData\" version=\"1.0\" byte_order=\"LittleEndian\" header_type=\"UInt64\"{}>", compressor)?;
+    writeln!(
+        writer,
+        "  <ImageData WholeExtent=\"{}\" Origin=\"{}\" Spacing=\"{}\" Direction=\"{}\">",
+        extent, join(&data.origin), join(&data.spacing), join(&transpose(&data.direction)),
+    )?;
+    writeln!(writer, "    <Piece Extent=\"{}\">", extent)?;
+    writeln!(writer, "      <PointData Scalars=\"{}\">", data.name)?;
+    let array = format!(
+        "<DataArray type=\"{}\" Name=\"{}\" NumberOfComponents=\"{}\"",
+        data.element_type.xml_name(), data.name, data.components,
+    );
+    match &data.values {
+        Values::Ascii(text) => {
+            writeln!(writer, "        {} format=\"ascii\">", array)?;
+            writeln!(writer, "{}", text)?;
+            writeln!(writer, "        </DataArray>")?;
+        }
+        Values::Binary { .. } => writeln!(writer, "        {} format=\"appended\" offset=\"0\"/>", array)?,
+    }
+    writeln!(writer, "      </PointData>")?;
+    writeln!(writer, "      <CellData>")?;
+    writeln!(writer, "      </CellData>")?;
+    writeln!(writer, "    </Piece>")?;
+    writeln!(writer, "  </ImageData>")?;
+
+    if let Values::Binary { bytes, .. } = &data.values {
+        writeln!(writer, "  <AppendedData encoding=\"raw\">")?;
+        writer.write_all(b"   _")?;
+        if zlib {
+            let mut blocks = Vec::new();
+            for chunk in bytes.chunks(BLOCK_SIZE) {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(chunk)?;
+                blocks.push(encoder.finish()?);
+            }
+            let last = bytes.len() % BLOCK_SIZE;
+            let header = [blocks.len(), BLOCK_SIZE, last].into_iter().chain(blocks.iter().map(Vec::len));
+            for word in header {
+                writer.write_all(&(word as u64).to_le_bytes())?;
+            }
+            for block in blocks {
+                writer.write_all(&block)?;
+            }
+        } else {
+            writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+            writer.write_all(bytes)?;
+        }
+        writeln!(writer)?;
+        writeln!(writer, "  </AppendedData>")?;
+    }
+    writeln!(writer, "</VTKFile>")?;
+    Ok(())
+}
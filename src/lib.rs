@@ -5,5 +5,19 @@
 pub mod io;
 pub mod image;
 
-pub use crate::io::meta_image::{load_meta_image, save_meta_image};
-pub use crate::image::{Image, AnyImage};
\ No newline at end of file
+pub use crate::io::{load, save, register_format, ImageFormat, ImageIoError};
+pub use crate::io::dicom::{load_dicom_series, read_dicom_series, DicomError, DicomSeries};
+pub use crate::io::analyze::{load_analyze, AnalyzeError};
+pub use crate::io::meta_image::{
+    load_meta_image, map_meta_image, save_meta_image, save_meta_image_with_options, MetaImageError,
+    MetaImageReader, SaveOptions,
+};
+pub use crate::io::nifti::{load_nifti, save_nifti, save_nifti_with_version, NiftiError, NiftiVersion};
+pub use crate::io::npy::{load_npy, load_npz, save_npy, save_npz, NpyError};
+pub use crate::io::nrrd::{load_nrrd, save_nrrd, NrrdEncoding, NrrdError};
+pub use crate::io::png::{export_png_slice, load_png, save_png, PngError, SlicePlane, Window};
+pub use crate::io::tiff::{load_tiff, save_tiff, TiffCompression, TiffError};
+pub use crate::io::vtk::{load_vtk, save_vtk, VtkEncoding, VtkError};
+pub use crate::image::{Image, NdImage, VectorImage, MappedImage, AnyImage, Metadata};
+pub use crate::image::{Boundary, CastError, CastPolicy, DynImage, IndexTransform, Neighborhood, Neighborhoods, Pixel};
+pub use crate::image::{ImageView, ImageViewMut};
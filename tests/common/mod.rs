@@ -0,0 +1,63 @@
+// Fixtures and helpers shared by the integration tests.
+#![allow(dead_code)]
+
+use oxels::{Image, Metadata};
+
+
+// 4x3x2 image with non-trivial spacing, origin and a direction rotated
+// 90 degrees around z, so a format that drops or transposes any of them fails.
+pub fn rotated_image<T>(voxels: Vec<T>) -> Image<T> {
+    assert_eq!(voxels.len(), 24);
+    Image {
+        voxels,
+        width: 4,
+        height: 3,
+        depth: 2,
+        spacing: [0.5, 0.75, 2.5],
+        origin: [10.0, -20.0, 30.5],
+        direction: [0.0, 1.0, 0.0, -1.0, 0.0, 0.0, 0.0, 0.0, 1.0],
+        metadata: Metadata::default(),
+    }
+}
+
+// 4x3x2 image with axis aligned directions, values are z * 100 + y * 10 + x.
+pub fn indexed_image(spacing: [f64; 3], origin: [f64; 3]) -> Image<i32> {
+    Image {
+        voxels: (0..24).map(|i| (i / 12) * 100 + (i / 4 % 3) * 10 + i % 4).collect(),
+        width: 4,
+        height: 3,
+        depth: 2,
+        spacing,
+        origin,
+        direction: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        metadata: Metadata::default(),
+    }
+}
+
+// Little-endian field bytes of a binary header, swapped for a big-endian file.
+pub fn field<const N: usize>(mut bytes: [u8; N], msb: bool) -> [u8; N] {
+    if msb {
+        bytes.reverse();
+    }
+    bytes
+}
+
+pub fn put(buffer: &mut [u8], at: usize, bytes: &[u8]) {
+    buffer[at..at + bytes.len()].copy_from_slice(bytes);
+}
+
+fn assert_within(a: &[f64], b: &[f64], tolerance: f64) {
+    assert_eq!(a.len(), b.len(), "{:?} != {:?}", a, b);
+    for (x, y) in a.iter().zip(b) {
+        assert!((x - y).abs() < tolerance, "{:?} != {:?}", a, b);
+    }
+}
+
+pub fn assert_close(a: &[f64], b: &[f64]) {
+    assert_within(a, b, 1e-9);
+}
+
+// For values that went through an `f32` field on the way.
+pub fn assert_close_f32(a: &[f64], b: &[f64]) {
+    assert_within(a, b, 1e-5);
+}
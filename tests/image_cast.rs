@@ -0,0 +1,54 @@
+use oxels::{CastPolicy, DynImage, Image, Metadata};
+
+
+fn image<T>(voxels: Vec<T>) -> Image<T> {
+    Image {
+        width: voxels.len() as u32,
+        voxels,
+        height: 1,
+        depth: 1,
+        spacing: [0.7, 0.7, 2.5],
+        origin: [-120.0, -80.0, 30.0],
+        direction: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        metadata: Metadata::default(),
+    }
+}
+
+
+#[test]
+fn cast_policies() {
+    let ct = image(vec![-5i16, 0, 200, 300]);
+    assert_eq!(ct.cast::<u8>(CastPolicy::Saturating).unwrap().voxels, vec![0, 0, 200, 255]);
+    assert_eq!(ct.cast::<u8>(CastPolicy::Wrapping).unwrap().voxels, vec![251, 0, 200, 44]);
+    let err = ct.cast::<u8>(CastPolicy::Checked).unwrap_err();
+    assert_eq!((err.index, err.value, err.target), ([0, 0, 0], -5.0, "u8"));
+    assert_eq!(ct.cast::<i32>(CastPolicy::Checked).unwrap().voxels, vec![-5, 0, 200, 300]);
+
+    let floats = image(vec![-1.7f32, 2.9, 1e10, f32::NAN]);
+    assert_eq!(floats.cast::<i16>(CastPolicy::Saturating).unwrap().voxels, vec![-1, 2, 32767, 0]);
+    assert_eq!(floats.cast::<u8>(CastPolicy::Checked).unwrap_err().index, [0, 0, 0]);
+    assert_eq!(image(vec![1e300f64]).cast::<f32>(CastPolicy::Saturating).unwrap().voxels, vec![f32::MAX]);
+    assert!(image(vec![1e300f64]).cast::<f32>(CastPolicy::Checked).is_err());
+    assert_eq!(image(vec![u64::MAX]).cast::<i64>(CastPolicy::Wrapping).unwrap().voxels, vec![-1]);
+
+    let cast = ct.cast::<f32>(CastPolicy::Checked).unwrap();
+    assert_eq!((cast.width, cast.spacing, cast.origin), (4, ct.spacing, ct.origin));
+}
+
+#[test]
+fn rescale_ct_for_display() {
+    let ct = image(vec![-1024i16, 0, 1000, 3071]);
+    let display = ct.rescale_intensity::<u8>(0.0, 255.0);
+    assert_eq!(display.voxels, vec![0, 64, 126, 255]);
+
+    let unit = ct.rescale_intensity::<f32>(0.0, 1.0);
+    assert_eq!((unit.voxels[0], unit.voxels[3]), (0.0, 1.0));
+    assert_eq!(image(vec![7u8; 3]).rescale_intensity::<u8>(10.0, 20.0).voxels, vec![10; 3]);
+
+    let dynamic = DynImage::from(ct);
+    let processed = dynamic.to_f32();
+    assert_eq!(processed.voxels, vec![-1024.0, 0.0, 1000.0, 3071.0]);
+    assert_eq!(dynamic.to_f64().voxels[3], 3071.0);
+    assert_eq!(dynamic.to_u8_display().voxels, display.voxels);
+    assert_eq!(DynImage::from(processed).cast::<i16>(CastPolicy::Checked).unwrap().voxels[0], -1024);
+}
@@ -0,0 +1,64 @@
+use oxels::{dispatch_image, AnyImage, DynImage, Image, Metadata, Pixel, VectorImage, load, save};
+use num_traits::NumCast;
+use std::fs::remove_file;
+
+
+// A generic algorithm, applied without knowing the pixel type up front.
+fn sum<T: Pixel>(image: &Image<T>) -> f64 {
+    image.voxels.iter().map(|&v| <f64 as NumCast>::from(v).unwrap()).sum()
+}
+
+fn invert<T: Pixel + std::ops::Neg<Output = T>>(image: &mut Image<T>) {
+    image.voxels.iter_mut().for_each(|v| *v = -*v);
+}
+
+
+#[test]
+fn from_loaded_image() {
+    for path in ["assets/tensors/uint8_uncompressed.mhd", "assets/tensors/float64_uncompressed.mha"] {
+        let image = DynImage::try_from(load(path).unwrap()).ok().unwrap();
+        assert_eq!(dispatch_image!(&image, img => sum(img)), 7203.0);
+    }
+
+    let image = DynImage::try_from(load("assets/tensors/int16_uncompressed.mhd").unwrap()).ok().unwrap();
+    assert_eq!(image.pixel_type(), "i16");
+    assert!(image.as_typed::<u16>().is_none());
+    let image = image.try_into_typed::<u8>().unwrap_err();
+    let mut typed = image.try_into_typed::<i16>().unwrap();
+    invert(&mut typed);
+    assert_eq!(sum(&typed), -7203.0);
+}
+
+#[test]
+fn conversions() {
+    let image = Image {
+        voxels: vec![1.5f32, -2.0, 4.0, 0.5],
+        width: 2,
+        height: 2,
+        depth: 1,
+        spacing: [0.5, 0.5, 2.0],
+        origin: [1.0, 2.0, 3.0],
+        direction: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        metadata: Metadata::default(),
+    };
+    let mut dynamic = DynImage::from(image.clone());
+    assert!(matches!(dynamic, DynImage::F32(_)));
+    assert_eq!((dynamic.width(), dynamic.height(), dynamic.depth()), (2, 2, 1));
+    assert_eq!(dynamic.spacing(), [0.5, 0.5, 2.0]);
+    assert_eq!(dynamic.as_any_image().iter_f64().sum::<f64>(), 4.0);
+
+    dispatch_image!(&mut dynamic, img => img.voxels[0] = NumCast::from(3).unwrap());
+    dynamic.as_typed_mut::<f32>().unwrap().voxels[1] = 7.0;
+    save(dynamic.as_any_image(), "test_dyn.mha").unwrap();
+    let loaded = DynImage::try_from(load("test_dyn.mha").unwrap()).ok().unwrap();
+    remove_file("test_dyn.mha").unwrap();
+    assert_eq!(loaded.as_typed::<f32>().unwrap().voxels, vec![3.0, 7.0, 4.0, 0.5]);
+
+    let boxed: Box<dyn AnyImage> = loaded.into();
+    assert!(boxed.as_any().downcast_ref::<Image<f32>>().is_some());
+
+    // Only scalar 3D images convert, others come back unchanged.
+    let vector = VectorImage::from_channels(&[image.clone(), image]).unwrap();
+    let back = DynImage::try_from(Box::new(vector) as Box<dyn AnyImage>).err().unwrap();
+    assert_eq!(back.channels(), 2);
+}
@@ -0,0 +1,79 @@
+use oxels::{Boundary, Image};
+
+mod common;
+use common::indexed_image;
+
+
+fn image() -> Image<i32> {
+    indexed_image([1.0, 1.0, 1.0], [0.0, 0.0, 0.0])
+}
+
+
+#[test]
+fn get_and_index() {
+    let mut img = image();
+    assert_eq!(*img.get(3, 2, 1), 123);
+    assert_eq!(img[[1, 2, 0]], 21);
+    assert_eq!(img.checked_get(3, 2, 1), Some(&123));
+    // Row 0 of slice 0 has flat index 4 in range, but x is not.
+    assert_eq!(img.checked_get(4, 0, 0), None);
+    assert_eq!(img.checked_get(0, 0, 2), None);
+
+    *img.get_mut(0, 1, 1) = -1;
+    img[[2, 0, 0]] += 1000;
+    *img.checked_get_mut(1, 1, 1).unwrap() = -2;
+    assert!(img.checked_get_mut(0, 3, 0).is_none());
+    assert_eq!(img.voxels[16..18], [-1, -2]);
+    assert_eq!(img.voxels[2], 1002);
+
+    assert_eq!(img.linear_index([3, 2, 1]), 23);
+    assert_eq!(img.voxel_index(23), [3, 2, 1]);
+    assert!(img.contains([3, 2, 1]) && !img.contains([3, 3, 1]));
+}
+
+#[test]
+#[should_panic(expected = "out of bounds")]
+fn index_out_of_bounds() {
+    let _ = image()[[4, 0, 0]];
+}
+
+#[test]
+fn indexed_iterators() {
+    let mut img = image();
+    for (index, value) in img.indexed_iter() {
+        assert_eq!(*value as usize, index[2] * 100 + index[1] * 10 + index[0]);
+    }
+    let order: Vec<[usize; 3]> = img.indexed_iter().map(|(index, _)| index).take(5).collect();
+    assert_eq!(order, vec![[0, 0, 0], [1, 0, 0], [2, 0, 0], [3, 0, 0], [0, 1, 0]]);
+
+    for ([x, y, z], value) in img.indexed_iter_mut() {
+        *value = (x + y + z) as i32;
+    }
+    assert_eq!(img[[3, 2, 1]], 6);
+}
+
+#[test]
+fn neighborhoods() {
+    let img = image();
+    assert_eq!(img.neighborhoods([1, 1, 1], Boundary::Clamp).len(), 24);
+
+    let n = img.neighborhood([0, 0, 0], [1, 0, 0], Boundary::Constant(-7));
+    assert_eq!(n.len(), 3);
+    assert_eq!(n.values().collect::<Vec<_>>(), vec![-7, 0, 1]);
+    assert_eq!(n.center_value(), 0);
+
+    let clamp = img.neighborhood([0, 2, 1], [1, 1, 0], Boundary::Clamp);
+    assert_eq!(clamp.values().collect::<Vec<_>>(), vec![110, 110, 111, 120, 120, 121, 120, 120, 121]);
+    let offsets: Vec<[isize; 3]> = clamp.iter().map(|(offset, _)| offset).take(2).collect();
+    assert_eq!(offsets, vec![[-1, -1, 0], [0, -1, 0]]);
+
+    // x = -2 mirrors to 2, x = 5 to 1; wrapping reads 2 and 1 as well.
+    let mirror = img.neighborhood([0, 0, 0], [0, 0, 0], Boundary::Mirror);
+    assert_eq!((mirror.get([-2, 0, 0]), mirror.get([5, 0, 0]), mirror.get([0, 0, -1])), (2, 1, 100));
+    let wrap = img.neighborhood([0, 0, 0], [0, 0, 0], Boundary::Wrap);
+    assert_eq!((wrap.get([-2, 0, 0]), wrap.get([5, 0, 0]), wrap.get([0, -1, 0])), (2, 1, 20));
+
+    // 3x3x3 sums with zero padding, the corner sees 8 of 27 voxels.
+    let sums: Vec<i32> = img.neighborhoods([1, 1, 1], Boundary::Constant(0)).map(|n| n.values().sum()).collect();
+    assert_eq!(sums[0], 1 + 10 + 11 + 100 + 101 + 110 + 111);
+}
@@ -0,0 +1,49 @@
+use oxels::{Image, IndexTransform, Metadata};
+
+mod common;
+use common::assert_close;
+
+
+// Axes rotated 90 degrees around z: x runs along world y, y along world -x.
+fn rotated() -> Image<u8> {
+    Image {
+        voxels: vec![0; 4 * 3 * 2],
+        width: 4,
+        height: 3,
+        depth: 2,
+        spacing: [0.5, 2.0, 3.0],
+        origin: [10.0, 20.0, 30.0],
+        direction: [0.0, 1.0, 0.0, -1.0, 0.0, 0.0, 0.0, 0.0, 1.0],
+        metadata: Metadata::default(),
+    }
+}
+
+
+#[test]
+fn index_to_physical_and_back() {
+    let img = rotated();
+    assert_close(&img.index_to_physical([0, 0, 0]), &[10.0, 20.0, 30.0]);
+    // x moves 0.5 along world y, y moves 2 along world -x.
+    assert_close(&img.index_to_physical([2, 1, 1]), &[8.0, 21.0, 33.0]);
+
+    assert_close(&img.physical_to_continuous_index([8.0, 21.0, 33.0]), &[2.0, 1.0, 1.0]);
+    assert_close(&img.physical_to_continuous_index([9.0, 19.0, 30.0]), &[-2.0, 0.5, 0.0]);
+
+    // 0.5 rounds up, like ITK.
+    assert_eq!(img.physical_to_index([9.0, 21.25, 31.4]), Some([3, 1, 0]));
+    assert_eq!(img.physical_to_index([9.2, 21.74, 31.6]), Some([3, 0, 1]));
+    assert_eq!(img.physical_to_index([10.0, 22.0, 30.0]), None);
+    assert_eq!(img.physical_to_index([10.0, 19.6, 30.0]), None);
+
+    let transform = img.index_transform();
+    for index in [[0, 0, 0], [3, 2, 1], [1, 2, 0]] {
+        assert_eq!(transform.physical_to_index(img.index_to_physical(index)), Some(index));
+    }
+}
+
+#[test]
+fn degenerate_geometry() {
+    let flat = IndexTransform::new([2, 2, 2], [1.0, 0.0, 1.0], [0.0; 3], [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]);
+    assert!(flat.physical_to_continuous_index([0.0; 3]).iter().all(|v| v.is_nan()));
+    assert_eq!(flat.physical_to_index([0.0; 3]), None);
+}
@@ -0,0 +1,87 @@
+use oxels::{Boundary, Image};
+
+mod common;
+use common::indexed_image;
+
+
+fn image() -> Image<i32> {
+    indexed_image([0.5, 1.0, 2.0], [10.0, 20.0, 30.0])
+}
+
+
+#[test]
+fn regions() {
+    let img = image();
+    let region = img.region([1, 1, 0], [2, 2, 2]);
+    assert_eq!(region.size(), [2, 2, 2]);
+    assert_eq!(region.iter().copied().collect::<Vec<_>>(), vec![11, 12, 21, 22, 111, 112, 121, 122]);
+    assert_eq!((region[[1, 0, 1]], region.checked_get(2, 0, 0)), (112, None));
+    assert_eq!(region.origin(), [10.5, 21.0, 30.0]);
+    // The view's voxels lie where they did in the image.
+    assert_eq!(region.index_transform().index_to_physical([1.0, 1.0, 1.0]), img.index_to_physical([2, 2, 1]));
+
+    let inner = region.region([1, 0, 1], [1, 2, 1]);
+    assert_eq!(inner.iter().copied().collect::<Vec<_>>(), vec![112, 122]);
+    let cropped = inner.to_image();
+    assert_eq!((cropped.width, cropped.height, cropped.depth), (1, 2, 1));
+    assert_eq!((cropped.voxels, cropped.origin), (vec![112, 122], [11.0, 21.0, 32.0]));
+}
+
+#[test]
+#[should_panic(expected = "out of bounds")]
+fn region_out_of_bounds() {
+    image().region([2, 0, 0], [3, 1, 1]);
+}
+
+#[test]
+fn slices() {
+    let mut img = image();
+    img.direction = [0.0, 1.0, 0.0, -1.0, 0.0, 0.0, 0.0, 0.0, 1.0];
+
+    let axial = img.slice(2, 1);
+    assert_eq!((axial.size(), axial[[3, 2, 0]], axial.origin()), ([4, 3, 1], 123, [10.0, 20.0, 32.0]));
+
+    // Along x the y and z axes become x and y.
+    let sagittal = img.slice(0, 2);
+    assert_eq!(sagittal.size(), [3, 2, 1]);
+    assert_eq!(sagittal.iter().copied().collect::<Vec<_>>(), vec![2, 12, 22, 102, 112, 122]);
+    assert_eq!(sagittal.spacing(), [1.0, 2.0, 0.5]);
+    assert_eq!(sagittal.direction(), [-1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0]);
+    assert_eq!(sagittal.origin(), [10.0, 21.0, 30.0]);
+    assert_eq!(sagittal.index_transform().index_to_physical([2.0, 1.0, 0.0]), img.index_to_physical([2, 2, 1]));
+
+    let coronal = img.slice(1, 0);
+    assert_eq!(coronal.size(), [4, 2, 1]);
+    assert_eq!(coronal.indexed_iter().nth(5), Some(([1, 1, 0], &101)));
+    assert_eq!(coronal.slice(1, 1).iter().copied().collect::<Vec<_>>(), vec![100, 101, 102, 103]);
+}
+
+#[test]
+fn mutable_views() {
+    let mut img = image();
+    img.region_mut([0, 1, 1], [4, 2, 1]).fill(0);
+    assert_eq!(img.voxels[12..].iter().sum::<i32>(), 100 + 101 + 102 + 103);
+
+    let mut slice = img.slice_mut(0, 3);
+    for ([y, z, _], v) in slice.indexed_iter_mut() {
+        *v = -((z * 10 + y) as i32);
+    }
+    slice[[0, 0, 0]] = 7;
+    assert_eq!(slice.as_view().iter().copied().collect::<Vec<_>>(), vec![7, -1, -2, -10, -11, -12]);
+    *slice.region_mut([1, 1, 0], [1, 1, 1]).get_mut(0, 0, 0) = 99;
+    assert_eq!((img[[3, 0, 0]], img[[3, 1, 1]], img[[3, 2, 1]], img[[2, 2, 1]]), (7, 99, -12, 0));
+
+    let mut whole = img.view_mut();
+    whole.iter_mut().for_each(|v| *v += 1);
+    assert_eq!(img[[3, 0, 0]], 8);
+}
+
+#[test]
+fn filter_on_a_region() {
+    let img = image();
+    // 3x1x1 mean along x on a crop, clamped at the crop's edges.
+    let region = img.region([1, 0, 0], [2, 1, 1]);
+    let means: Vec<i32> = region.neighborhoods([1, 0, 0], Boundary::Clamp).map(|n| n.values().sum::<i32>() / 3).collect();
+    assert_eq!(means, vec![(1 + 1 + 2) / 3, (1 + 2 + 2) / 3]);
+    assert_eq!(img.slice(2, 1).neighborhood([0, 0, 0], [1, 1, 0], Boundary::Constant(0)).values().sum::<i32>(), 100 + 101 + 110 + 111);
+}
@@ -0,0 +1,77 @@
+use oxels::{Image, NdImage, AnalyzeError, load, load_analyze};
+use std::fs::{remove_file, write};
+
+mod common;
+use common::{field, put};
+
+
+// Analyze 7.5 header of an int16 image with the given dim (dim[0] first).
+fn analyze_header(dim: &[i16], msb: bool) -> Vec<u8> {
+    let mut h = vec![0u8; 348];
+    put(&mut h, 0, &field(348i32.to_le_bytes(), msb));
+    for (i, d) in dim.iter().enumerate() {
+        put(&mut h, 40 + 2 * i, &field(d.to_le_bytes(), msb));
+    }
+    put(&mut h, 70, &field(4i16.to_le_bytes(), msb));
+    put(&mut h, 72, &field(16i16.to_le_bytes(), msb));
+    for (i, p) in [0.0f32, 1.25, 1.25, 3.0, 2.0].iter().enumerate() {
+        put(&mut h, 76 + 4 * i, &field(p.to_le_bytes(), msb));
+    }
+    put(&mut h, 148, b"legacy study");
+    h[252] = 1;
+    h
+}
+
+
+#[test]
+fn load_both_byte_orders() {
+    for msb in [false, true] {
+        let name = format!("test_analyze_{}", msb);
+        write(format!("{}.hdr", name), analyze_header(&[3, 3, 2, 2], msb)).unwrap();
+        let data: Vec<u8> = (0..12i16).flat_map(|v| field((v * -300).to_le_bytes(), msb)).collect();
+        write(format!("{}.img", name), data).unwrap();
+
+        // Either file of the pair, and through `load` (detected from sizeof_hdr).
+        let from_img = load_analyze(&format!("{}.img", name)).unwrap();
+        let loaded = load(&format!("{}.hdr", name)).unwrap();
+        remove_file(format!("{}.hdr", name)).unwrap();
+        remove_file(format!("{}.img", name)).unwrap();
+
+        let loaded = loaded.as_any().downcast_ref::<Image<i16>>().unwrap();
+        assert_eq!((loaded.width, loaded.height, loaded.depth), (3, 2, 2));
+        assert_eq!(loaded.voxels, (0..12).map(|v| v * -300).collect::<Vec<i16>>());
+        assert_eq!(loaded.spacing, [1.25, 1.25, 3.0]);
+        assert_eq!(loaded.origin, [0.0; 3]);
+        assert_eq!(loaded.direction, [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(loaded.metadata.get("descrip"), Some("legacy study"));
+        assert_eq!(loaded.metadata.get("orient"), Some("1"));
+        assert_eq!(loaded.metadata.get("warning"), None);
+        assert_eq!(from_img.as_any().downcast_ref::<Image<i16>>().unwrap().voxels, loaded.voxels);
+    }
+}
+
+#[test]
+fn load_4d_and_errors() {
+    write("test_analyze_4d.hdr", analyze_header(&[4, 2, 1, 1, 3], false)).unwrap();
+    write("test_analyze_4d.img", vec![0u8; 12]).unwrap();
+    let loaded = load_analyze("test_analyze_4d.hdr").unwrap();
+    let loaded = loaded.as_any().downcast_ref::<NdImage<i16>>().unwrap();
+    assert_eq!(loaded.size, vec![2, 1, 1, 3]);
+    assert_eq!(loaded.spacing, vec![1.25, 1.25, 3.0, 2.0]);
+
+    write("test_analyze_4d.img", vec![0u8; 11]).unwrap();
+    let result = load_analyze("test_analyze_4d.hdr");
+    assert!(matches!(result.err().unwrap(), AnalyzeError::Truncated { expected: 12, found: 11 }));
+
+    write("test_analyze_4d.hdr", analyze_header(&[7, 32767, 32767, 32767, 32767, 32767, 32767, 32767], false)).unwrap();
+    let result = load_analyze("test_analyze_4d.hdr");
+    assert!(matches!(result.err().unwrap(), AnalyzeError::InvalidHeader(_)));
+
+    let mut header = analyze_header(&[4, 2, 1, 1, 3], false);
+    put(&mut header, 70, &128i16.to_le_bytes());
+    write("test_analyze_4d.hdr", header).unwrap();
+    let result = load_analyze("test_analyze_4d.hdr");
+    remove_file("test_analyze_4d.hdr").unwrap();
+    remove_file("test_analyze_4d.img").unwrap();
+    assert!(matches!(result.err().unwrap(), AnalyzeError::UnsupportedDataType(128)));
+}
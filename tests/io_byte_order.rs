@@ -0,0 +1,80 @@
+use oxels::{Image, Metadata, AnyImage, load_meta_image, save_meta_image_with_options, SaveOptions};
+use std::fs::{read, remove_file, write};
+
+
+const HEADER: &str = "ObjectType = Image
+NDims = 3
+BinaryData = True
+BinaryDataByteOrderMSB = True
+CompressedData = False
+TransformMatrix = 1 0 0 0 1 0 0 0 1
+Offset = 0 0 0
+ElementSpacing = 1 1 1
+DimSize = 2 2 1
+";
+
+
+#[test]
+fn load_big_endian_int16() {
+    let values = [1i16, -2, 300, -4000];
+    let mut contents = format!("{}ElementType = MET_SHORT\nElementDataFile = LOCAL\n", HEADER).into_bytes();
+    for v in values {
+        contents.extend_from_slice(&v.to_be_bytes());
+    }
+    let path = "test_msb_int16.mha";
+    write(path, contents).unwrap();
+
+    let loaded: Box<dyn AnyImage> = load_meta_image(path).unwrap();
+    let loaded_voxels: Vec<i16> = loaded.iter_f64().map(|v| v as i16).collect();
+    assert_eq!(loaded_voxels, values);
+
+    remove_file(path).unwrap();
+}
+
+#[test]
+fn load_big_endian_float64() {
+    let values = [1.5f64, -2.25, 1e10, 0.0];
+    let mut contents = format!("{}ElementType = MET_DOUBLE\nElementDataFile = LOCAL\n", HEADER).into_bytes();
+    for v in values {
+        contents.extend_from_slice(&v.to_be_bytes());
+    }
+    let path = "test_msb_float64.mha";
+    write(path, contents).unwrap();
+
+    let loaded: Box<dyn AnyImage> = load_meta_image(path).unwrap();
+    let loaded_voxels: Vec<f64> = loaded.iter_f64().collect();
+    assert_eq!(loaded_voxels, values);
+
+    remove_file(path).unwrap();
+}
+
+#[test]
+fn roundtrip_save_and_load_big_endian_uint32() {
+    let voxels = vec![1u32, 2, 3, 4, 5, 6, 7, 0xdeadbeef];
+    let img = Image {
+        voxels: voxels.clone(),
+        width: 2,
+        height: 2,
+        depth: 2,
+        spacing: [1.0, 1.0, 1.0],
+        origin: [0.0, 0.0, 0.0],
+        direction: [1.0,0.0,0.0,0.0,1.0,0.0,0.0,0.0,1.0],
+        metadata: Metadata::default(),
+    };
+    let path = "test_msb_uint32.mhd";
+    let options = SaveOptions { byte_order_msb: true, ..Default::default() };
+    save_meta_image_with_options(&img, path, &options).unwrap();
+
+    // The data file itself must be big-endian.
+    let raw = read("test_msb_uint32.raw").unwrap();
+    assert_eq!(&raw[28..32], &0xdeadbeefu32.to_be_bytes());
+    let header = String::from_utf8(read(path).unwrap()).unwrap();
+    assert!(header.contains("BinaryDataByteOrderMSB = True"));
+
+    let loaded: Box<dyn AnyImage> = load_meta_image(path).unwrap();
+    let loaded_voxels: Vec<u32> = loaded.iter_f64().map(|v| v as u32).collect();
+    assert_eq!(loaded_voxels, voxels);
+
+    remove_file(path).unwrap();
+    remove_file("test_msb_uint32.raw").unwrap();
+}
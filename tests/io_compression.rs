@@ -0,0 +1,81 @@
+use oxels::{Image, Metadata, MetaImageReader, SaveOptions, load_meta_image, save_meta_image_with_options};
+use flate2::Compression;
+use flate2::write::{DeflateEncoder, GzEncoder};
+use std::fs::{metadata, remove_file, write};
+use std::io::Write;
+
+
+const HEADER: &str = "ObjectType = Image
+NDims = 3
+BinaryData = True
+BinaryDataByteOrderMSB = False
+CompressedData = True
+TransformMatrix = 1 0 0 0 1 0 0 0 1
+Offset = 0 0 0
+ElementSpacing = 1 1 1
+DimSize = 4 3 2
+ElementType = MET_USHORT
+";
+
+fn voxels() -> Vec<u16> {
+    (0..24).map(|v| v * 1000).collect()
+}
+
+fn raw_bytes() -> Vec<u8> {
+    voxels().iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+
+#[test]
+fn load_gzip_and_raw_deflate() {
+    let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+    gzip.write_all(&raw_bytes()).unwrap();
+    let mut deflate = DeflateEncoder::new(Vec::new(), Compression::best());
+    deflate.write_all(&raw_bytes()).unwrap();
+
+    for (name, data) in [("gzip", gzip.finish().unwrap()), ("deflate", deflate.finish().unwrap())] {
+        let header = format!("test_compression_{}.mhd", name);
+        let zraw = format!("test_compression_{}.zraw", name);
+        write(&header, format!("{}ElementDataFile = {}\n", HEADER, zraw)).unwrap();
+        write(&zraw, data).unwrap();
+
+        let loaded = load_meta_image(&header).unwrap();
+        let region = MetaImageReader::open(&header).unwrap().read_region(&[1, 1, 1], &[2, 2, 1]).unwrap();
+        remove_file(&header).unwrap();
+        remove_file(&zraw).unwrap();
+
+        assert_eq!(loaded.as_any().downcast_ref::<Image<u16>>().unwrap().voxels, voxels());
+        assert_eq!(region.as_any().downcast_ref::<Image<u16>>().unwrap().voxels, vec![17000, 18000, 21000, 22000]);
+    }
+}
+
+#[test]
+fn save_with_compression_level() {
+    let img = Image {
+        voxels: (0..4096u32).map(|v| v % 7).collect(),
+        width: 16,
+        height: 16,
+        depth: 16,
+        spacing: [1.0, 1.0, 1.0],
+        origin: [0.0, 0.0, 0.0],
+        direction: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        metadata: Metadata::default(),
+    };
+
+    let mut sizes = Vec::new();
+    for level in [0, 9] {
+        let options = SaveOptions { compress: true, compression_level: level, ..Default::default() };
+        save_meta_image_with_options(&img, "test_level.mhd", &options).unwrap();
+        sizes.push(metadata("test_level.zraw").unwrap().len());
+        let loaded = load_meta_image("test_level.mhd").unwrap();
+        remove_file("test_level.mhd").unwrap();
+        remove_file("test_level.zraw").unwrap();
+        assert_eq!(loaded.as_any().downcast_ref::<Image<u32>>().unwrap().voxels, img.voxels);
+    }
+    // Level 0 stores the data, 16 KiB plus framing.
+    assert!(sizes[0] > 4 * 4096 && sizes[1] < sizes[0] / 10, "{:?}", sizes);
+
+    let options = SaveOptions { compress: true, compression_level: 10, ..Default::default() };
+    let err = save_meta_image_with_options(&img, "test_level_never.mhd", &options).err().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
@@ -0,0 +1,223 @@
+use oxels::{Image, DicomError, load_dicom_series, read_dicom_series};
+use std::fs::{create_dir_all, remove_dir_all, write};
+
+mod common;
+use common::assert_close;
+
+
+// Explicit or implicit VR little endian element.
+fn element(explicit: bool, group: u16, element: u16, vr: &str, value: &[u8]) -> Vec<u8> {
+    let mut value = value.to_vec();
+    if value.len() % 2 == 1 {
+        value.push(if vr == "UI" || vr == "OB" { 0 } else { b' ' });
+    }
+    let mut out = [group.to_le_bytes(), element.to_le_bytes()].concat();
+    if explicit {
+        out.extend(vr.as_bytes());
+        if ["OB", "OW", "SQ", "UN", "UT"].contains(&vr) {
+            out.extend([0, 0]);
+            out.extend((value.len() as u32).to_le_bytes());
+        } else {
+            out.extend((value.len() as u16).to_le_bytes());
+        }
+    } else {
+        out.extend((value.len() as u32).to_le_bytes());
+    }
+    out.extend(value);
+    out
+}
+
+struct Slice {
+    series: &'static str,
+    instance: i32,
+    position: [f64; 3],
+    orientation: [f64; 6],
+    slope: &'static str,
+    intercept: &'static str,
+    pixels: Vec<u16>,
+}
+
+// Part 10 file with a 2x3 (rows x columns) 16 bit unsigned image.
+fn dicom_file(explicit: bool, slice: &Slice) -> Vec<u8> {
+    let syntax = if explicit { "1.2.840.10008.1.2.1" } else { "1.2.840.10008.1.2" };
+    let join = |v: &[f64]| v.iter().map(|x| x.to_string()).collect::<Vec<_>>().join("\\");
+    let e = |group, elem, vr, value: &[u8]| element(explicit, group, elem, vr, value);
+
+    let mut file = vec![0u8; 128];
+    file.extend(b"DICM");
+    file.extend(element(true, 0x0002, 0x0010, "UI", syntax.as_bytes()));
+    file.extend(e(0x0008, 0x0060, "CS", b"CT"));
+    // A sequence of undefined length with one item of undefined length, to be skipped.
+    let mut sequence = [0x0008u16.to_le_bytes(), 0x1140u16.to_le_bytes()].concat();
+    if explicit {
+        sequence.extend(b"SQ\0\0");
+    }
+    sequence.extend(0xFFFF_FFFFu32.to_le_bytes());
+    sequence.extend([0xFE, 0xFF, 0x00, 0xE0, 0xFF, 0xFF, 0xFF, 0xFF]);
+    sequence.extend(e(0x0008, 0x1150, "UI", b"1.2.3"));
+    sequence.extend([0xFE, 0xFF, 0x0D, 0xE0, 0, 0, 0, 0]);
+    sequence.extend([0xFE, 0xFF, 0xDD, 0xE0, 0, 0, 0, 0]);
+    file.extend(sequence);
+    file.extend(e(0x0010, 0x0010, "PN", b"Doe^Jane"));
+    file.extend(e(0x0020, 0x000E, "UI", slice.series.as_bytes()));
+    file.extend(e(0x0020, 0x0013, "IS", slice.instance.to_string().as_bytes()));
+    file.extend(e(0x0020, 0x0032, "DS", join(&slice.position).as_bytes()));
+    file.extend(e(0x0020, 0x0037, "DS", join(&slice.orientation).as_bytes()));
+    file.extend(e(0x0028, 0x0002, "US", &1u16.to_le_bytes()));
+    file.extend(e(0x0028, 0x0010, "US", &2u16.to_le_bytes()));
+    file.extend(e(0x0028, 0x0011, "US", &3u16.to_le_bytes()));
+    file.extend(e(0x0028, 0x0030, "DS", b"0.6\\0.7"));
+    file.extend(e(0x0028, 0x0100, "US", &16u16.to_le_bytes()));
+    file.extend(e(0x0028, 0x0101, "US", &12u16.to_le_bytes()));
+    file.extend(e(0x0028, 0x0103, "US", &0u16.to_le_bytes()));
+    file.extend(e(0x0028, 0x1052, "DS", slice.intercept.as_bytes()));
+    file.extend(e(0x0028, 0x1053, "DS", slice.slope.as_bytes()));
+    let pixels: Vec<u8> = slice.pixels.iter().flat_map(|p| p.to_le_bytes()).collect();
+    file.extend(e(0x7FE0, 0x0010, "OW", &pixels));
+    file
+}
+
+fn axial(series: &'static str, instance: i32, z: f64, intercept: &'static str) -> Slice {
+    Slice {
+        series,
+        instance,
+        position: [-10.0, 20.0, z],
+        orientation: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0],
+        slope: "1",
+        intercept,
+        pixels: (0..6).map(|p| p + 100 * z as u16).collect(),
+    }
+}
+
+
+#[test]
+fn load_sorted_rescaled_series() {
+    for explicit in [true, false] {
+        let dir = format!("test_dicom_axial_{}", explicit);
+        create_dir_all(&dir).unwrap();
+        // Instance numbers run against the positions, sorting must use the positions.
+        for (name, z) in [("a.dcm", 5.0), ("b.dcm", 1.0), ("c.dcm", 3.0)] {
+            write(format!("{}/{}", dir, name), dicom_file(explicit, &axial("1.2.3.4", 10 - z as i32, z, "-1024"))).unwrap();
+        }
+        write(format!("{}/notes.txt", dir), "not a DICOM file").unwrap();
+        // A compressed transfer syntax we can not read, skipped as well.
+        let jpeg = dicom_file(true, &axial("1.2.3.4", 4, 7.0, "-1024"));
+        let syntax = element(true, 0x0002, 0x0010, "UI", b"1.2.840.10008.1.2.4.50");
+        let jpeg = [&jpeg[..132], &syntax[..], &jpeg[160..]].concat();
+        write(format!("{}/jpeg.dcm", dir), jpeg).unwrap();
+
+        let loaded = load_dicom_series(&dir);
+        remove_dir_all(&dir).unwrap();
+        let loaded = loaded.unwrap();
+
+        let image = loaded.as_any().downcast_ref::<Image<i16>>().unwrap();
+        assert_eq!((image.width, image.height, image.depth), (3, 2, 3));
+        let expected: Vec<i16> = [1, 3, 5].iter()
+            .flat_map(|z| (0..6).map(move |p| p + 100 * z - 1024))
+            .collect();
+        assert_eq!(image.voxels, expected);
+        assert_close(&image.spacing, &[0.7, 0.6, 2.0]);
+        assert_close(&image.origin, &[-10.0, 20.0, 1.0]);
+        assert_close(&image.direction, &[1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(image.metadata.get("SeriesInstanceUID"), Some("1.2.3.4"));
+        assert_eq!(image.metadata.get("PatientName"), Some("Doe^Jane"));
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn skip_unreadable_files() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = "test_dicom_unreadable";
+    create_dir_all(dir).unwrap();
+    for (i, z) in [1.0, 2.0].iter().enumerate() {
+        write(format!("{}/{}.dcm", dir, i), dicom_file(true, &axial("4.4", i as i32, *z, "0"))).unwrap();
+    }
+    // Unreadable except for root, which skips it as not DICOM instead.
+    let locked = format!("{}/locked.dcm", dir);
+    write(&locked, "no permission to read").unwrap();
+    std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000)).unwrap();
+
+    let series = read_dicom_series(dir);
+    remove_dir_all(dir).unwrap();
+    let series = series.unwrap();
+    assert_eq!(series.len(), 1);
+    assert_eq!(series[0].len(), 2);
+}
+
+#[test]
+fn load_sagittal_series_direction() {
+    let dir = "test_dicom_sagittal";
+    create_dir_all(dir).unwrap();
+    // Rows run along y, columns down along z: the normal is -x.
+    for (i, x) in [1.0, 5.0, 3.0].iter().enumerate() {
+        let slice = Slice {
+            series: "9.9",
+            instance: i as i32,
+            position: [*x, 0.0, 0.0],
+            orientation: [0.0, 1.0, 0.0, 0.0, 0.0, -1.0],
+            slope: "0.5",
+            intercept: "0",
+            pixels: vec![*x as u16; 6],
+        };
+        write(format!("{}/{}.dcm", dir, i), dicom_file(true, &slice)).unwrap();
+    }
+
+    let loaded = load_dicom_series(dir);
+    remove_dir_all(dir).unwrap();
+    let loaded = loaded.unwrap();
+
+    let image = loaded.as_any().downcast_ref::<Image<f32>>().unwrap();
+    let first_voxels: Vec<f32> = image.voxels.iter().step_by(6).copied().collect();
+    assert_eq!(first_voxels, vec![2.5, 1.5, 0.5]);
+    assert_close(&image.origin, &[5.0, 0.0, 0.0]);
+    assert_close(&image.spacing, &[0.7, 0.6, 2.0]);
+    assert_close(&image.direction, &[0.0, 1.0, 0.0, 0.0, 0.0, -1.0, -1.0, 0.0, 0.0]);
+}
+
+#[test]
+fn group_by_series_instance_uid() {
+    let dir = "test_dicom_two_series";
+    create_dir_all(dir).unwrap();
+    write(format!("{}/1.dcm", dir), dicom_file(true, &axial("1.1", 1, 1.0, "0"))).unwrap();
+    write(format!("{}/2.dcm", dir), dicom_file(true, &axial("2.2", 1, 1.0, "0"))).unwrap();
+    write(format!("{}/3.dcm", dir), dicom_file(true, &axial("1.1", 2, 2.0, "0"))).unwrap();
+
+    let series = read_dicom_series(dir).unwrap();
+    let err = load_dicom_series(dir).err().unwrap();
+    let first = series[0].load();
+    remove_dir_all(dir).unwrap();
+
+    assert_eq!(series.len(), 2);
+    assert_eq!(series[0].series_instance_uid, "1.1");
+    assert_eq!(series[0].len(), 2);
+    assert_eq!(series[1].len(), 1);
+    assert!(matches!(err, DicomError::MultipleSeries(ref uids) if uids.len() == 2), "{}", err);
+
+    let first = first.unwrap();
+    let image = first.as_any().downcast_ref::<Image<u16>>().unwrap();
+    assert_eq!(image.depth, 2);
+}
+
+#[test]
+fn uneven_slice_spacing_is_an_error() {
+    let dir = "test_dicom_gap";
+    create_dir_all(dir).unwrap();
+    // Slice 3.0 is missing.
+    for (i, z) in [1.0, 2.0, 4.0, 5.0].iter().enumerate() {
+        write(format!("{}/{}.dcm", dir, i), dicom_file(true, &axial("3.3", i as i32, *z, "0"))).unwrap();
+    }
+    let err = load_dicom_series(dir).err().unwrap();
+    remove_dir_all(dir).unwrap();
+    assert!(matches!(err, DicomError::InconsistentSeries(ref what) if what.contains("are 2 apart")), "{}", err);
+}
+
+#[test]
+fn empty_directory_has_no_series() {
+    let dir = "test_dicom_empty";
+    create_dir_all(dir).unwrap();
+    let err = load_dicom_series(dir).err().unwrap();
+    remove_dir_all(dir).unwrap();
+    assert!(matches!(err, DicomError::NoSeries(_)), "{}", err);
+}
@@ -0,0 +1,93 @@
+use oxels::{Image, Metadata, AnyImage, load_meta_image, save_meta_image_with_options, SaveOptions};
+use std::fs::{read, remove_file, write};
+
+
+fn header(element_type: &str, binary: bool) -> String {
+    format!("ObjectType = Image
+NDims = 3
+BinaryData = {}
+BinaryDataByteOrderMSB = False
+CompressedData = False
+TransformMatrix = 1 0 0 0 1 0 0 0 1
+Offset = 0 0 0
+ElementSpacing = 1 1 1
+DimSize = 2 2 1
+ElementType = {}
+ElementDataFile = LOCAL
+", if binary { "True" } else { "False" }, element_type)
+}
+
+
+fn load_values(path: &str, contents: Vec<u8>) -> Vec<f64> {
+    write(path, contents).unwrap();
+    let loaded: Box<dyn AnyImage> = load_meta_image(path).unwrap();
+    remove_file(path).unwrap();
+    loaded.iter_f64().collect()
+}
+
+
+#[test]
+fn load_met_bool() {
+    let mut contents = header("MET_BOOL", true).into_bytes();
+    contents.extend_from_slice(&[0, 1, 1, 0]);
+    assert_eq!(load_values("test_etype_bool.mha", contents), vec![0.0, 1.0, 1.0, 0.0]);
+}
+
+#[test]
+fn load_met_long_and_ulong() {
+    let mut contents = header("MET_LONG", true).into_bytes();
+    for v in [-1i32, 2, -3, 4] {
+        contents.extend_from_slice(&v.to_le_bytes());
+    }
+    assert_eq!(load_values("test_etype_long.mha", contents), vec![-1.0, 2.0, -3.0, 4.0]);
+
+    let mut contents = header("MET_ULONG", true).into_bytes();
+    for v in [1u32, 2, 3, 4_000_000_000] {
+        contents.extend_from_slice(&v.to_le_bytes());
+    }
+    assert_eq!(load_values("test_etype_ulong.mha", contents), vec![1.0, 2.0, 3.0, 4e9]);
+}
+
+#[test]
+fn load_array_element_type() {
+    let mut contents = header("MET_SHORT_ARRAY", true).into_bytes();
+    for v in [-7i16, 8, 9, 10] {
+        contents.extend_from_slice(&v.to_le_bytes());
+    }
+    assert_eq!(load_values("test_etype_array.mha", contents), vec![-7.0, 8.0, 9.0, 10.0]);
+}
+
+#[test]
+fn load_ascii_data() {
+    let mut contents = header("MET_FLOAT", false).into_bytes();
+    contents.extend_from_slice(b"0.5 -1.25\n3 4e2\n");
+    assert_eq!(load_values("test_etype_ascii.mha", contents), vec![0.5, -1.25, 3.0, 400.0]);
+}
+
+#[test]
+fn roundtrip_save_and_load_ascii_int64() {
+    let voxels = vec![i64::MIN, -1, 0, i64::MAX];
+    let img = Image {
+        voxels: voxels.clone(),
+        width: 2,
+        height: 2,
+        depth: 1,
+        spacing: [1.0, 1.0, 1.0],
+        origin: [0.0, 0.0, 0.0],
+        direction: [1.0,0.0,0.0,0.0,1.0,0.0,0.0,0.0,1.0],
+        metadata: Metadata::default(),
+    };
+    let path = "test_etype_ascii_int64.mhd";
+    let options = SaveOptions { ascii: true, ..Default::default() };
+    save_meta_image_with_options(&img, path, &options).unwrap();
+
+    let raw = String::from_utf8(read("test_etype_ascii_int64.raw").unwrap()).unwrap();
+    assert_eq!(raw, format!("{} -1\n0 {}\n", i64::MIN, i64::MAX));
+
+    let loaded = load_meta_image(path).unwrap();
+    let loaded = loaded.as_any().downcast_ref::<Image<i64>>().unwrap();
+    assert_eq!(loaded.voxels, voxels);
+
+    remove_file(path).unwrap();
+    remove_file("test_etype_ascii_int64.raw").unwrap();
+}
@@ -0,0 +1,130 @@
+use oxels::{load_meta_image, MetaImageError};
+use std::fs::{remove_file, write};
+
+
+const HEADER: &str = "ObjectType = Image
+NDims = 3
+BinaryData = True
+BinaryDataByteOrderMSB = False
+CompressedData = False
+TransformMatrix = 1 0 0 0 1 0 0 0 1
+Offset = 0 0 0
+ElementSpacing = 1 1 1
+";
+
+
+// Write a header (plus optional inline data) and return the load error.
+fn load_error(path: &str, contents: &[u8]) -> MetaImageError {
+    write(path, contents).unwrap();
+    let result = load_meta_image(path);
+    remove_file(path).unwrap();
+    match result {
+        Ok(_) => panic!("expected {} to fail loading", path),
+        Err(e) => e,
+    }
+}
+
+
+#[test]
+fn missing_file_is_io_error() {
+    let err = load_meta_image("assets/tensors/does_not_exist.mha").err().unwrap();
+    assert!(matches!(err, MetaImageError::Io(_)));
+}
+
+#[test]
+fn malformed_numeric_value_reports_key_and_line() {
+    let contents = format!("{}DimSize = 2 x 2\nElementType = MET_UCHAR\nElementDataFile = LOCAL\n", HEADER);
+    match load_error("test_error_malformed.mha", contents.as_bytes()) {
+        MetaImageError::InvalidValue { key, value, line } => {
+            assert_eq!(key, "DimSize");
+            assert_eq!(value, "x");
+            assert_eq!(line, 9);
+        }
+        e => panic!("unexpected error: {}", e),
+    }
+}
+
+#[test]
+fn malformed_ascii_value_reports_line() {
+    let contents = format!("{}DimSize = 2 2 2\nElementType = MET_UCHAR\nElementDataFile = LOCAL\n1 2 3 4\n5 six 7 8\n", HEADER)
+        .replace("BinaryData = True", "BinaryData = False");
+    match load_error("test_error_ascii.mha", contents.as_bytes()) {
+        MetaImageError::InvalidValue { key, value, line } => {
+            assert_eq!((key.as_str(), value.as_str()), ("ElementData", "six"));
+            assert_eq!(line, 13);
+        }
+        e => panic!("unexpected error: {}", e),
+    }
+}
+
+#[test]
+fn wrong_value_count() {
+    let contents = format!("{}DimSize = 2 2\nElementType = MET_UCHAR\nElementDataFile = LOCAL\n", HEADER);
+    match load_error("test_error_count.mha", contents.as_bytes()) {
+        MetaImageError::ValueCount { key, expected, found, .. } => {
+            assert_eq!(key, "DimSize");
+            assert_eq!(expected, 3);
+            assert_eq!(found, 2);
+        }
+        e => panic!("unexpected error: {}", e),
+    }
+}
+
+#[test]
+fn missing_key() {
+    let contents = format!("{}ElementType = MET_UCHAR\nElementDataFile = LOCAL\n", HEADER);
+    let err = load_error("test_error_missing.mha", contents.as_bytes());
+    assert!(matches!(err, MetaImageError::Missing("DimSize")));
+}
+
+#[test]
+fn unsupported_element_type() {
+    let contents = format!("{}DimSize = 2 2 2\nElementType = MET_STRING\nElementDataFile = LOCAL\n", HEADER);
+    let err = load_error("test_error_etype.mha", contents.as_bytes());
+    assert!(matches!(err, MetaImageError::UnsupportedElementType(_)));
+}
+
+#[test]
+fn truncated_data() {
+    let mut contents = format!("{}DimSize = 2 2 2\nElementType = MET_UCHAR\nElementDataFile = LOCAL\n", HEADER).into_bytes();
+    contents.extend_from_slice(&[1, 2, 3]);
+    match load_error("test_error_truncated.mha", &contents) {
+        MetaImageError::Truncated { expected, found } => {
+            assert_eq!(expected, 8);
+            assert_eq!(found, 3);
+        }
+        e => panic!("unexpected error: {}", e),
+    }
+}
+
+#[test]
+fn corrupt_compressed_data() {
+    let mut contents = format!("{}DimSize = 2 2 2\nElementType = MET_UCHAR\nElementDataFile = LOCAL\n", HEADER)
+        .replace("CompressedData = False", "CompressedData = True")
+        .into_bytes();
+    contents.extend_from_slice(&[0xde, 0xad, 0xbe, 0xef, 0x00, 0x01]);
+    let err = load_error("test_error_zlib.mha", &contents);
+    assert!(matches!(err, MetaImageError::Decompression(_)));
+}
+
+#[test]
+fn missing_external_data_file() {
+    let contents = format!("{}DimSize = 2 2 2\nElementType = MET_UCHAR\nElementDataFile = nowhere.raw\n", HEADER);
+    let err = load_error("test_error_external.mhd", contents.as_bytes());
+    assert!(matches!(err, MetaImageError::MissingDataFile(_)));
+}
+
+#[test]
+fn huge_dim_size() {
+    // Fits in usize but not in memory, the data is only a few bytes.
+    let mut contents = format!("{}DimSize = 100000 100000 100000\nElementType = MET_DOUBLE\nElementDataFile = LOCAL\n", HEADER).into_bytes();
+    contents.extend_from_slice(&[1, 2, 3]);
+    match load_error("test_error_huge.mha", &contents) {
+        MetaImageError::Truncated { expected, found } => assert_eq!((expected, found), (8_000_000_000_000_000, 3)),
+        e => panic!("unexpected error: {}", e),
+    }
+
+    let contents = format!("{}DimSize = 4000000000 4000000000 4000000000\nElementType = MET_UCHAR\nElementDataFile = LOCAL\n", HEADER);
+    let err = load_error("test_error_overflow.mha", contents.as_bytes());
+    assert!(matches!(err, MetaImageError::TooLarge { .. }));
+}
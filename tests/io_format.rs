@@ -0,0 +1,115 @@
+use oxels::{AnyImage, Image, ImageFormat, ImageIoError, Metadata, load, save, register_format, save_meta_image};
+use std::fs::{copy, read, remove_file, write};
+
+
+fn test_image() -> Image<u16> {
+    Image {
+        voxels: (0..24).collect(),
+        width: 4,
+        height: 3,
+        depth: 2,
+        spacing: [0.5, 1.0, 2.0],
+        origin: [1.0, 2.0, 3.0],
+        direction: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        metadata: Metadata::default(),
+    }
+}
+
+fn voxels(image: &dyn AnyImage) -> Vec<u16> {
+    image.as_any().downcast_ref::<Image<u16>>().unwrap().voxels.clone()
+}
+
+
+#[test]
+fn roundtrip_by_extension() {
+    let img = test_image();
+    for path in ["test_format.mha", "test_format.nii", "test_format.nii.gz", "test_format.nrrd"] {
+        save(&img, path).unwrap();
+        let loaded = load(path).unwrap();
+        remove_file(path).unwrap();
+        assert_eq!(voxels(loaded.as_ref()), img.voxels, "{}", path);
+        assert_eq!(loaded.spacing(), img.spacing, "{}", path);
+    }
+}
+
+#[test]
+fn load_detects_format_from_magic_bytes() {
+    let img = test_image();
+    for path in ["test_magic.mha", "test_magic.nii.gz", "test_magic.nrrd"] {
+        save(&img, path).unwrap();
+        // Same content, misleading file name.
+        copy(path, "test_magic.dat").unwrap();
+        let loaded = load("test_magic.dat");
+        remove_file(path).unwrap();
+        remove_file("test_magic.dat").unwrap();
+        assert_eq!(voxels(loaded.unwrap().as_ref()), img.voxels, "{}", path);
+    }
+}
+
+#[test]
+fn unknown_extension_is_an_error() {
+    let err = save(&test_image(), "test_format.unknown").err().unwrap();
+    assert!(matches!(err, ImageIoError::UnknownFormat(_)), "{}", err);
+
+    write("test_format.unknown", b"not an image").unwrap();
+    let err = load("test_format.unknown").err().unwrap();
+    remove_file("test_format.unknown").unwrap();
+    assert!(matches!(err, ImageIoError::UnknownFormat(_)), "{}", err);
+}
+
+#[test]
+fn save_meta_image_rejects_other_extensions() {
+    let err = save_meta_image(&test_image(), "test_format.png", false).err().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+
+// Width, height, depth as u32 followed by u16 voxels, no geometry.
+struct ToyFormat;
+
+impl ImageFormat for ToyFormat {
+    fn name(&self) -> &str {
+        "Toy"
+    }
+    fn extensions(&self) -> &[&str] {
+        &["toy"]
+    }
+    fn matches_magic(&self, header: &[u8]) -> bool {
+        header.starts_with(b"TOY!")
+    }
+    fn load(&self, path: &str) -> Result<Box<dyn AnyImage>, ImageIoError> {
+        let bytes = read(path)?;
+        let size = |i: usize| u32::from_le_bytes(bytes[4 + 4 * i..8 + 4 * i].try_into().unwrap());
+        Ok(Box::new(Image {
+            voxels: bytes[16..].chunks_exact(2).map(|b| u16::from_le_bytes([b[0], b[1]])).collect(),
+            width: size(0),
+            height: size(1),
+            depth: size(2),
+            spacing: [1.0; 3],
+            origin: [0.0; 3],
+            direction: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+            metadata: Metadata::default(),
+        }))
+    }
+    fn save(&self, image: &dyn AnyImage, path: &str) -> Result<(), ImageIoError> {
+        let mut bytes = b"TOY!".to_vec();
+        for size in image.size() {
+            bytes.extend(size.to_le_bytes());
+        }
+        bytes.extend(image.iter_f64().flat_map(|v| (v as u16).to_le_bytes()));
+        Ok(write(path, bytes)?)
+    }
+}
+
+#[test]
+fn registered_format_is_used() {
+    register_format(ToyFormat);
+    let img = test_image();
+    save(&img, "test_format.toy").unwrap();
+    let magic = read("test_format.toy").unwrap()[..4].to_vec();
+    let loaded = load("test_format.toy");
+    remove_file("test_format.toy").unwrap();
+
+    assert_eq!(magic, b"TOY!");
+    assert_eq!(voxels(loaded.unwrap().as_ref()), img.voxels);
+}
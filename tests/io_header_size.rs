@@ -0,0 +1,109 @@
+use oxels::{Image, Metadata, AnyImage, MetaImageError, load_meta_image, save_meta_image};
+use flate2::{Compression, write::ZlibEncoder};
+use std::fs::{read_to_string, remove_file, write};
+use std::io::Write;
+
+
+const HEADER: &str = "ObjectType = Image
+NDims = 3
+BinaryData = True
+BinaryDataByteOrderMSB = False
+TransformMatrix = 1 0 0 0 1 0 0 0 1
+Offset = 0 0 0
+ElementSpacing = 1 1 1
+DimSize = 2 2 2
+ElementType = MET_UCHAR
+";
+
+
+fn load_values(path: &str) -> Vec<u8> {
+    let loaded: Box<dyn AnyImage> = load_meta_image(path).unwrap();
+    loaded.iter_f64().map(|v| v as u8).collect()
+}
+
+fn zlib(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes).unwrap();
+    encoder.finish().unwrap()
+}
+
+
+#[test]
+fn header_size_skips_leading_bytes() {
+    let header = format!("{}CompressedData = False\nHeaderSize = 5\nElementDataFile = test_hs_skip.raw\n", HEADER);
+    write("test_hs_skip.mhd", header).unwrap();
+    write("test_hs_skip.raw", [9, 9, 9, 9, 9, 1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+
+    assert_eq!(load_values("test_hs_skip.mhd"), (1..=8).collect::<Vec<u8>>());
+
+    remove_file("test_hs_skip.mhd").unwrap();
+    remove_file("test_hs_skip.raw").unwrap();
+}
+
+#[test]
+fn header_size_minus_one_reads_from_the_end() {
+    let header = format!("{}CompressedData = False\nHeaderSize = -1\nElementDataFile = test_hs_end.raw\n", HEADER);
+    write("test_hs_end.mhd", header).unwrap();
+    write("test_hs_end.raw", [0xAA, 0xBB, 0xCC, 1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+
+    assert_eq!(load_values("test_hs_end.mhd"), (1..=8).collect::<Vec<u8>>());
+
+    remove_file("test_hs_end.mhd").unwrap();
+    remove_file("test_hs_end.raw").unwrap();
+}
+
+#[test]
+fn compressed_data_size_ignores_trailing_bytes() {
+    let voxels: Vec<u8> = (1..=8).collect();
+    let mut data = zlib(&voxels);
+    let size = data.len();
+    data.extend_from_slice(b"trailing garbage");
+
+    let header = format!("{}CompressedData = True\nCompressedDataSize = {}\nElementDataFile = test_cds.zraw\n", HEADER, size);
+    write("test_cds.mhd", header).unwrap();
+    write("test_cds.zraw", data).unwrap();
+
+    assert_eq!(load_values("test_cds.mhd"), voxels);
+
+    remove_file("test_cds.mhd").unwrap();
+    remove_file("test_cds.zraw").unwrap();
+}
+
+#[test]
+fn decompressed_size_mismatch_is_an_error() {
+    let data = zlib(&[1, 2, 3, 4]);
+    let header = format!("{}CompressedData = True\nCompressedDataSize = {}\nElementDataFile = test_cds_short.zraw\n", HEADER, data.len());
+    write("test_cds_short.mhd", header).unwrap();
+    write("test_cds_short.zraw", data).unwrap();
+
+    let err = load_meta_image("test_cds_short.mhd").err().unwrap();
+    assert!(matches!(err, MetaImageError::DecompressedSize { expected: 8, found: 4 }), "{}", err);
+
+    remove_file("test_cds_short.mhd").unwrap();
+    remove_file("test_cds_short.zraw").unwrap();
+}
+
+#[test]
+fn save_writes_compressed_data_size() {
+    let img = Image {
+        voxels: vec![7u16; 64],
+        width: 4,
+        height: 4,
+        depth: 4,
+        spacing: [1.0, 1.0, 1.0],
+        origin: [0.0, 0.0, 0.0],
+        direction: [1.0,0.0,0.0,0.0,1.0,0.0,0.0,0.0,1.0],
+        metadata: Metadata::default(),
+    };
+    save_meta_image(&img, "test_cds_save.mhd", true).unwrap();
+
+    let header = read_to_string("test_cds_save.mhd").unwrap();
+    let zraw = std::fs::metadata("test_cds_save.zraw").unwrap().len();
+    assert!(header.contains(&format!("CompressedDataSize = {}\n", zraw)), "{}", header);
+
+    let loaded = load_meta_image("test_cds_save.mhd").unwrap();
+    assert_eq!(loaded.as_any().downcast_ref::<Image<u16>>().unwrap().voxels, img.voxels);
+
+    remove_file("test_cds_save.mhd").unwrap();
+    remove_file("test_cds_save.zraw").unwrap();
+}
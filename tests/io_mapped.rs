@@ -0,0 +1,44 @@
+use oxels::{Image, MappedImage, AnyImage, MetaImageError, load_meta_image, map_meta_image};
+
+
+fn assert_sum7203(path: &str) {
+    let image: Box<dyn AnyImage> = map_meta_image(path).unwrap();
+    let sum: f64 = image.iter_f64().sum();
+    assert_eq!(sum, 7203.0);
+}
+
+
+#[test]
+fn assert_sum7203_mapped_mhd() {
+    for dtype in ["uint8", "int8", "uint16", "int16", "uint32", "int32", "float32", "uint64", "int64", "float64"] {
+        assert_sum7203(&format!("assets/tensors/{}_uncompressed.mhd", dtype));
+    }
+}
+
+#[test]
+fn assert_sum7203_mapped_mha() {
+    for dtype in ["uint8", "int8", "uint16", "int16", "uint32", "int32", "float32", "uint64", "int64", "float64"] {
+        assert_sum7203(&format!("assets/tensors/{}_uncompressed.mha", dtype));
+    }
+}
+
+#[test]
+fn mapped_raw_file_is_zero_copy() {
+    let mapped = map_meta_image("assets/tensors/float32_uncompressed.mhd").unwrap();
+    let mapped = mapped.as_any().downcast_ref::<MappedImage<f32>>().unwrap();
+    let loaded = load_meta_image("assets/tensors/float32_uncompressed.mhd").unwrap();
+    let loaded = loaded.as_any().downcast_ref::<Image<f32>>().unwrap();
+
+    // .raw data starts at offset 0, so it is always aligned.
+    assert_eq!(mapped.voxels().unwrap(), loaded.voxels.as_slice());
+    assert_eq!(mapped.to_image().voxels, loaded.voxels);
+    assert_eq!(mapped.get(1234), loaded.voxels[1234]);
+    assert_eq!(mapped.origin, loaded.origin);
+    assert_eq!(mapped.spacing, loaded.spacing);
+}
+
+#[test]
+fn compressed_data_can_not_be_mapped() {
+    let err = map_meta_image("assets/tensors/uint16_compressed.mha").err().unwrap();
+    assert!(matches!(err, MetaImageError::Unsupported(_)));
+}
@@ -0,0 +1,72 @@
+use oxels::{AnyImage, Metadata, load_meta_image, save_meta_image};
+use std::fs::{read, remove_file, write};
+
+
+#[test]
+fn auxiliary_fields_are_kept() {
+    let image: Box<dyn AnyImage> = load_meta_image("assets/tensors/int32_uncompressed.mhd").unwrap();
+    let metadata = image.metadata();
+    assert_eq!(metadata.get("CenterOfRotation"), Some("0 0 0"));
+    assert_eq!(metadata.get("AnatomicalOrientation"), Some("RAI"));
+    assert!(!metadata.contains_key("DimSize"));
+}
+
+#[test]
+fn roundtrip_custom_fields_in_order() {
+    let mut contents = b"ObjectType = Image
+NDims = 3
+BinaryData = True
+BinaryDataByteOrderMSB = False
+CompressedData = False
+TransformMatrix = 1 0 0 0 1 0 0 0 1
+Offset = 0 0 0
+CenterOfRotation = 0 0 0
+AnatomicalOrientation = LPS
+ElementSpacing = 1 1 1
+DimSize = 2 1 1
+Modality = MET_MOD_CT
+PatientID = 0042
+ITK_InputFilterName = MetaImageIO
+ElementMin = 0
+ElementMax = 7
+ElementType = MET_UCHAR
+ElementDataFile = LOCAL
+".to_vec();
+    contents.extend_from_slice(&[0, 7]);
+    let path = "test_metadata_in.mha";
+    write(path, contents).unwrap();
+
+    let loaded: Box<dyn AnyImage> = load_meta_image(path).unwrap();
+    let expected: Metadata = [
+        ("CenterOfRotation", "0 0 0"),
+        ("AnatomicalOrientation", "LPS"),
+        ("Modality", "MET_MOD_CT"),
+        ("PatientID", "0042"),
+        ("ITK_InputFilterName", "MetaImageIO"),
+        ("ElementMin", "0"),
+        ("ElementMax", "7"),
+    ].into_iter().collect();
+    assert_eq!(loaded.metadata(), &expected);
+
+    let out = "test_metadata_out.mha";
+    save_meta_image(loaded.as_ref(), out, false).unwrap();
+    let header = String::from_utf8_lossy(&read(out).unwrap()).into_owned();
+    assert!(header.contains("PatientID = 0042\n"));
+
+    let reloaded: Box<dyn AnyImage> = load_meta_image(out).unwrap();
+    assert_eq!(reloaded.metadata(), &expected);
+
+    remove_file(path).unwrap();
+    remove_file(out).unwrap();
+}
+
+#[test]
+fn metadata_insert_replaces_in_place() {
+    let mut metadata = Metadata::new();
+    metadata.insert("a", "1");
+    metadata.insert("b", "2");
+    assert_eq!(metadata.insert("a", "3"), Some("1".to_string()));
+    assert_eq!(metadata.iter().collect::<Vec<_>>(), vec![("a", "3"), ("b", "2")]);
+    assert_eq!(metadata.remove("a"), Some("3".to_string()));
+    assert_eq!(metadata.len(), 1);
+}
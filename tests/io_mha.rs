@@ -1,9 +1,9 @@
-use oxels::{Image, AnyImage, load_meta_image, save_meta_image};
+use oxels::{Image, Metadata, AnyImage, load_meta_image, save_meta_image};
 use std::fs::remove_file;
 
 
 fn assert_sum7203(path: &str) {
-    let image: Box<dyn AnyImage> = load_meta_image(path);
+    let image: Box<dyn AnyImage> = load_meta_image(path).unwrap();
     let sum: f64 = image.iter_f64().sum();
     assert_eq!(sum, 7203.0);
 }
@@ -61,8 +61,8 @@ fn assert_sum7203_float64_uncompressed() {
 
 #[test]
 fn assert_equal_with_compression() {
-    let compressed: Box<dyn AnyImage> = load_meta_image("assets/tensors/uint16_compressed.mha");
-    let uncompressed: Box<dyn AnyImage> = load_meta_image("assets/tensors/uint16_uncompressed.mha");
+    let compressed: Box<dyn AnyImage> = load_meta_image("assets/tensors/uint16_compressed.mha").unwrap();
+    let uncompressed: Box<dyn AnyImage> = load_meta_image("assets/tensors/uint16_uncompressed.mha").unwrap();
 
         for (a, b) in compressed.iter_f64().zip(uncompressed.iter_f64()) {
             assert_eq!(a, b);
@@ -81,12 +81,13 @@ fn roundtrip_save_and_load_uint16() {
         spacing: [1.0, 1.0, 1.0],
         origin: [0.0, 0.0, 0.0],
         direction: [1.0,0.0,0.0,0.0,1.0,0.0,0.0,0.0,1.0],
+        metadata: Metadata::default(),
     };
     let path = "test_uint16.mha";
     save_meta_image(&img, path, false).unwrap();
 
     // Load it back
-    let loaded: Box<dyn AnyImage> = oxels::load_meta_image(path);
+    let loaded: Box<dyn AnyImage> = oxels::load_meta_image(path).unwrap();
 
     // Check metadata
     assert_eq!(loaded.width(), 2);
@@ -116,12 +117,13 @@ fn roundtrip_save_and_load_uint16_compressed() {
         spacing: [1.0, 1.0, 1.0],
         origin: [0.0, 0.0, 0.0],
         direction: [1.0,0.0,0.0,0.0,1.0,0.0,0.0,0.0,1.0],
+        metadata: Metadata::default(),
     };
     let path = "test_uint16_compressed.mha";
     save_meta_image(&img, path, true).unwrap();
 
     // Load it back
-    let loaded: Box<dyn AnyImage> = oxels::load_meta_image(path);
+    let loaded: Box<dyn AnyImage> = oxels::load_meta_image(path).unwrap();
 
     // Check metadata
     assert_eq!(loaded.width(), 2);
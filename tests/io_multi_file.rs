@@ -0,0 +1,100 @@
+use oxels::{Image, Metadata, AnyImage, load_meta_image, save_meta_image_with_options, SaveOptions};
+use std::fs::{remove_file, write};
+use std::path::Path;
+
+
+const HEADER: &str = "ObjectType = Image
+NDims = 3
+BinaryData = True
+BinaryDataByteOrderMSB = False
+CompressedData = False
+TransformMatrix = 1 0 0 0 1 0 0 0 1
+Offset = 0 0 0
+ElementSpacing = 1 1 1
+DimSize = 2 2 3
+ElementType = MET_UCHAR
+";
+
+
+fn load_values(path: &str) -> Vec<u8> {
+    let loaded: Box<dyn AnyImage> = load_meta_image(path).unwrap();
+    loaded.iter_f64().map(|v| v as u8).collect()
+}
+
+
+#[test]
+fn load_element_data_file_list() {
+    let header = format!("{}ElementDataFile = LIST 2D\ntest_list_a.raw\ntest_list_b.raw\n\ntest_list_c.raw\n", HEADER);
+    write("test_list.mhd", header).unwrap();
+    write("test_list_a.raw", [1, 2, 3, 4]).unwrap();
+    write("test_list_b.raw", [5, 6, 7, 8]).unwrap();
+    write("test_list_c.raw", [9, 10, 11, 12]).unwrap();
+
+    assert_eq!(load_values("test_list.mhd"), (1..=12).collect::<Vec<u8>>());
+
+    for path in ["test_list.mhd", "test_list_a.raw", "test_list_b.raw", "test_list_c.raw"] {
+        remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn load_element_data_file_pattern() {
+    let header = format!("{}ElementDataFile = test_pattern%02d.raw 1 5 2\n", HEADER);
+    write("test_pattern.mhd", header).unwrap();
+    write("test_pattern01.raw", [1, 1, 1, 1]).unwrap();
+    write("test_pattern03.raw", [3, 3, 3, 3]).unwrap();
+    write("test_pattern05.raw", [5, 5, 5, 5]).unwrap();
+
+    assert_eq!(load_values("test_pattern.mhd"), vec![1, 1, 1, 1, 3, 3, 3, 3, 5, 5, 5, 5]);
+
+    for path in ["test_pattern.mhd", "test_pattern01.raw", "test_pattern03.raw", "test_pattern05.raw"] {
+        remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn roundtrip_save_and_load_split_slices() {
+    let voxels: Vec<u16> = (0..12).collect();
+    let img = Image {
+        voxels: voxels.clone(),
+        width: 2,
+        height: 2,
+        depth: 3,
+        spacing: [1.0, 1.0, 1.0],
+        origin: [0.0, 0.0, 0.0],
+        direction: [1.0,0.0,0.0,0.0,1.0,0.0,0.0,0.0,1.0],
+        metadata: Metadata::default(),
+    };
+
+    for compress in [false, true] {
+        let path = "test_split.mhd";
+        let options = SaveOptions { compress, split_slices: true, ..Default::default() };
+        save_meta_image_with_options(&img, path, &options).unwrap();
+
+        let ext = if compress { "zraw" } else { "raw" };
+        let slices: Vec<String> = (0..3).map(|i| format!("test_split_{:03}.{}", i, ext)).collect();
+        assert!(slices.iter().all(|s| Path::new(s).is_file()));
+
+        let loaded = load_meta_image(path).unwrap();
+        let loaded = loaded.as_any().downcast_ref::<Image<u16>>().unwrap();
+        assert_eq!(loaded.voxels, voxels);
+
+        remove_file(path).unwrap();
+        for slice in slices {
+            remove_file(slice).unwrap();
+        }
+    }
+}
+
+#[test]
+fn missing_slice_file() {
+    let header = format!("{}ElementDataFile = test_missing%d.raw 1 3\n", HEADER);
+    write("test_missing.mhd", header).unwrap();
+    write("test_missing1.raw", [1, 1, 1, 1]).unwrap();
+
+    let err = load_meta_image("test_missing.mhd").err().unwrap();
+    assert!(matches!(err, oxels::MetaImageError::MissingDataFile(_)));
+
+    remove_file("test_missing.mhd").unwrap();
+    remove_file("test_missing1.raw").unwrap();
+}
@@ -0,0 +1,94 @@
+use oxels::{Image, Metadata, NdImage, AnyImage, load_meta_image, save_meta_image};
+use std::fs::{remove_file, write};
+
+
+#[test]
+fn load_2d_image() {
+    let mut contents = b"ObjectType = Image
+NDims = 2
+BinaryData = True
+BinaryDataByteOrderMSB = False
+CompressedData = False
+TransformMatrix = 1 0 0 1
+Offset = 10 20
+ElementSpacing = 0.5 0.25
+DimSize = 3 2
+ElementType = MET_UCHAR
+ElementDataFile = LOCAL
+".to_vec();
+    contents.extend_from_slice(&[1, 2, 3, 4, 5, 6]);
+    let path = "test_ndims_2d.mha";
+    write(path, contents).unwrap();
+
+    let loaded: Box<dyn AnyImage> = load_meta_image(path).unwrap();
+    assert_eq!(loaded.ndims(), 2);
+    assert_eq!(loaded.size(), vec![3, 2]);
+    assert_eq!(loaded.width(), 3);
+    assert_eq!(loaded.height(), 2);
+    assert_eq!(loaded.depth(), 1);
+    assert_eq!(loaded.spacing(), [0.5, 0.25, 1.0]);
+    assert_eq!(loaded.origin(), [10.0, 20.0, 0.0]);
+
+    let image = loaded.as_any().downcast_ref::<NdImage<u8>>().unwrap();
+    assert_eq!(image.voxels, vec![1, 2, 3, 4, 5, 6]);
+    assert_eq!(image.direction, vec![1.0, 0.0, 0.0, 1.0]);
+
+    remove_file(path).unwrap();
+}
+
+#[test]
+fn roundtrip_save_and_load_4d() {
+    let voxels: Vec<i16> = (0..24).collect();
+    let img = NdImage {
+        voxels: voxels.clone(),
+        size: vec![2, 3, 2, 2],
+        spacing: vec![1.0, 1.0, 2.0, 0.5],
+        origin: vec![0.0, 1.0, 2.0, 3.0],
+        direction: (0..16).map(|i| if i % 5 == 0 { 1.0 } else { 0.0 }).collect(),
+        metadata: Metadata::default(),
+    };
+    let path = "test_ndims_4d.mha";
+    save_meta_image(&img, path, true).unwrap();
+
+    let loaded: Box<dyn AnyImage> = load_meta_image(path).unwrap();
+    assert_eq!(loaded.ndims(), 4);
+    let loaded = loaded.as_any().downcast_ref::<NdImage<i16>>().unwrap();
+    assert_eq!(loaded.size, img.size);
+    assert_eq!(loaded.spacing, img.spacing);
+    assert_eq!(loaded.origin, img.origin);
+    assert_eq!(loaded.direction, img.direction);
+    assert_eq!(loaded.voxels, voxels);
+
+    remove_file(path).unwrap();
+}
+
+#[test]
+fn convert_between_image_and_nd_image() {
+    let img = Image {
+        voxels: vec![1u8, 2, 3, 4],
+        width: 2,
+        height: 2,
+        depth: 1,
+        spacing: [1.0, 2.0, 3.0],
+        origin: [4.0, 5.0, 6.0],
+        direction: [1.0,0.0,0.0,0.0,1.0,0.0,0.0,0.0,1.0],
+        metadata: Metadata::default(),
+    };
+    let nd = NdImage::from(img.clone());
+    assert_eq!(nd.ndims(), 3);
+    assert_eq!(nd.size, vec![2, 2, 1]);
+
+    let back = Image::try_from(nd).unwrap();
+    assert_eq!(back.voxels, img.voxels);
+    assert_eq!(back.spacing, img.spacing);
+
+    let four_d = NdImage {
+        voxels: vec![0u8; 16],
+        size: vec![2, 2, 2, 2],
+        spacing: vec![1.0; 4],
+        origin: vec![0.0; 4],
+        direction: vec![0.0; 16],
+        metadata: Metadata::default(),
+    };
+    assert!(Image::try_from(four_d).is_err());
+}
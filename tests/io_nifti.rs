@@ -0,0 +1,205 @@
+use oxels::{Image, NdImage, VectorImage, Metadata, NiftiError, NiftiVersion, load_nifti, save_nifti, save_nifti_with_version};
+use std::fs::{read, remove_file, write};
+
+mod common;
+use common::{assert_close_f32, field, put};
+
+
+// Minimal single-file NIfTI-1 header for a 2x2x2 MET_SHORT (int16) image.
+fn nifti1_header(msb: bool) -> Vec<u8> {
+    let mut h = vec![0u8; 352];
+    put(&mut h, 0, &field(348i32.to_le_bytes(), msb));
+    for (i, d) in [3i16, 2, 2, 2, 1, 1, 1, 1].iter().enumerate() {
+        put(&mut h, 40 + 2 * i, &field(d.to_le_bytes(), msb));
+    }
+    put(&mut h, 70, &field(4i16.to_le_bytes(), msb));
+    put(&mut h, 72, &field(16i16.to_le_bytes(), msb));
+    for (i, p) in [1.0f32, 1.5, 1.5, 2.0, 1.0, 1.0, 1.0, 1.0].iter().enumerate() {
+        put(&mut h, 76 + 4 * i, &field(p.to_le_bytes(), msb));
+    }
+    put(&mut h, 108, &field(352f32.to_le_bytes(), msb));
+    put(&mut h, 344, b"n+1\0");
+    h
+}
+
+fn int16_data(msb: bool) -> Vec<u8> {
+    (1..=8i16).flat_map(|v| field(v.to_le_bytes(), msb)).collect()
+}
+
+
+#[test]
+fn load_sform_converts_ras_to_lps() {
+    for msb in [false, true] {
+        let mut file = nifti1_header(msb);
+        put(&mut file, 254, &field(1i16.to_le_bytes(), msb));
+        let srow = [[-2.0f32, 0.0, 0.0, 10.0], [0.0, 3.0, 0.0, 20.0], [0.0, 0.0, 4.0, 30.0]];
+        for (i, v) in srow.iter().flatten().enumerate() {
+            put(&mut file, 280 + 4 * i, &field(v.to_le_bytes(), msb));
+        }
+        file.extend(int16_data(msb));
+        let path = format!("test_nifti_sform_{}.nii", msb);
+        write(&path, file).unwrap();
+
+        let loaded = load_nifti(&path).unwrap();
+        remove_file(&path).unwrap();
+        let loaded = loaded.as_any().downcast_ref::<Image<i16>>().unwrap();
+        assert_eq!(loaded.voxels, (1..=8).collect::<Vec<i16>>());
+        assert_close_f32(&loaded.spacing, &[2.0, 3.0, 4.0]);
+        assert_close_f32(&loaded.origin, &[-10.0, -20.0, 30.0]);
+        assert_close_f32(&loaded.direction, &[1.0, 0.0, 0.0, 0.0, -1.0, 0.0, 0.0, 0.0, 1.0]);
+    }
+}
+
+#[test]
+fn load_qform() {
+    let mut file = nifti1_header(false);
+    put(&mut file, 252, &1i16.to_le_bytes());
+    // 180 degrees around z: b = c = 0, d = 1
+    for (i, v) in [0.0f32, 0.0, 1.0, 5.0, 6.0, 7.0].iter().enumerate() {
+        put(&mut file, 256 + 4 * i, &v.to_le_bytes());
+    }
+    file.extend(int16_data(false));
+    write("test_nifti_qform.nii", file).unwrap();
+
+    let loaded = load_nifti("test_nifti_qform.nii").unwrap();
+    remove_file("test_nifti_qform.nii").unwrap();
+    assert_close_f32(&loaded.spacing(), &[1.5, 1.5, 2.0]);
+    assert_close_f32(&loaded.origin(), &[-5.0, -6.0, 7.0]);
+    // RAS rotated by 180 degrees around z is the LPS identity.
+    assert_close_f32(&loaded.direction(), &[1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]);
+}
+
+#[test]
+fn load_applies_scl_slope_and_inter() {
+    let mut file = nifti1_header(false);
+    put(&mut file, 112, &0.5f32.to_le_bytes());
+    put(&mut file, 116, &(-1.0f32).to_le_bytes());
+    file.extend(int16_data(false));
+    write("test_nifti_scaled.nii", file).unwrap();
+
+    let loaded = load_nifti("test_nifti_scaled.nii").unwrap();
+    remove_file("test_nifti_scaled.nii").unwrap();
+    let loaded = loaded.as_any().downcast_ref::<Image<f32>>().unwrap();
+    assert_eq!(loaded.voxels, (1..=8).map(|v| v as f32 * 0.5 - 1.0).collect::<Vec<f32>>());
+}
+
+#[test]
+fn roundtrip_oblique_image() {
+    let (c, s) = (0.6f64, 0.8f64);
+    let img = Image {
+        voxels: (0..60).map(|v| v as f32 * 0.25).collect(),
+        width: 5,
+        height: 4,
+        depth: 3,
+        spacing: [0.5, 1.25, 3.0],
+        origin: [-12.0, 4.5, 100.0],
+        direction: [c, s, 0.0, -s, c, 0.0, 0.0, 0.0, -1.0],
+        metadata: Metadata::from_iter([("descrip", "roundtrip test")]),
+    };
+
+    for path in ["test_nifti_roundtrip.nii", "test_nifti_roundtrip.nii.gz"] {
+        save_nifti(&img, path).unwrap();
+        let loaded = load_nifti(path).unwrap();
+        let bytes = read(path).unwrap();
+        remove_file(path).unwrap();
+
+        assert_eq!(bytes.starts_with(&[0x1f, 0x8b]), path.ends_with(".gz"));
+        let loaded = loaded.as_any().downcast_ref::<Image<f32>>().unwrap();
+        assert_eq!(loaded.voxels, img.voxels);
+        assert_eq!((loaded.width, loaded.height, loaded.depth), (5, 4, 3));
+        assert_close_f32(&loaded.spacing, &img.spacing);
+        assert_close_f32(&loaded.origin, &img.origin);
+        assert_close_f32(&loaded.direction, &img.direction);
+        assert_eq!(loaded.metadata.get("descrip"), Some("roundtrip test"));
+    }
+}
+
+#[test]
+fn roundtrip_nifti2_time_series() {
+    let img = NdImage {
+        voxels: (0..24u16).collect(),
+        size: vec![2, 3, 2, 2],
+        spacing: vec![1.0, 2.0, 3.0, 0.5],
+        origin: vec![1.0, 2.0, 3.0, 10.0],
+        direction: vec![
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        ],
+        metadata: Metadata::default(),
+    };
+
+    save_nifti_with_version(&img, "test_nifti2.nii", NiftiVersion::Nifti2).unwrap();
+    let bytes = read("test_nifti2.nii").unwrap();
+    let loaded = load_nifti("test_nifti2.nii").unwrap();
+    remove_file("test_nifti2.nii").unwrap();
+
+    assert_eq!(i32::from_le_bytes(bytes[..4].try_into().unwrap()), 540);
+    let loaded = loaded.as_any().downcast_ref::<NdImage<u16>>().unwrap();
+    assert_eq!(loaded.voxels, img.voxels);
+    assert_eq!(loaded.size, img.size);
+    assert_close_f32(&loaded.spacing, &img.spacing);
+    assert_close_f32(&loaded.origin, &img.origin);
+    assert_close_f32(&loaded.direction, &img.direction);
+}
+
+#[test]
+fn roundtrip_rgb() {
+    let img = VectorImage {
+        voxels: (0..24u8).collect(),
+        channels: 3,
+        width: 2,
+        height: 2,
+        depth: 2,
+        spacing: [1.0, 1.0, 1.0],
+        origin: [0.0, 0.0, 0.0],
+        direction: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        metadata: Metadata::default(),
+    };
+    save_nifti(&img, "test_nifti_rgb.nii").unwrap();
+    let loaded = load_nifti("test_nifti_rgb.nii").unwrap();
+    remove_file("test_nifti_rgb.nii").unwrap();
+
+    let loaded = loaded.as_any().downcast_ref::<VectorImage<u8>>().unwrap();
+    assert_eq!(loaded.channels, 3);
+    assert_eq!(loaded.voxels, img.voxels);
+}
+
+#[test]
+fn invalid_header_is_an_error() {
+    write("test_nifti_invalid.nii", vec![0u8; 400]).unwrap();
+    let err = load_nifti("test_nifti_invalid.nii").err().unwrap();
+    remove_file("test_nifti_invalid.nii").unwrap();
+    assert!(matches!(err, NiftiError::InvalidHeader(_)), "{}", err);
+}
+
+#[test]
+fn oversized_header_is_an_error() {
+    // 7 dimensions of 32767 voxels overflow any byte count.
+    let mut file = nifti1_header(false);
+    for i in 0..8 {
+        put(&mut file, 40 + 2 * i, &(if i == 0 { 7i16 } else { 32767 }).to_le_bytes());
+    }
+    file.extend(int16_data(false));
+    write("test_nifti_oversized.nii", file).unwrap();
+    let err = load_nifti("test_nifti_oversized.nii").err().unwrap();
+    remove_file("test_nifti_oversized.nii").unwrap();
+    assert!(matches!(err, NiftiError::InvalidHeader(_)), "{}", err);
+}
+
+#[test]
+fn save_rejects_other_extensions() {
+    let img = Image {
+        voxels: vec![0u8; 8],
+        width: 2,
+        height: 2,
+        depth: 2,
+        spacing: [1.0, 1.0, 1.0],
+        origin: [0.0, 0.0, 0.0],
+        direction: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        metadata: Metadata::default(),
+    };
+    let err = save_nifti(&img, "test_nifti.mha").err().unwrap();
+    assert!(matches!(err, NiftiError::Unsupported(_)), "{}", err);
+}
@@ -0,0 +1,210 @@
+use oxels::{Image, NdImage, VectorImage, Metadata, NpyError, load, load_npy, load_npz, save_npy, save_npz};
+use std::fs::{read, remove_file, write, File};
+use std::io::Write;
+use zip::write::SimpleFileOptions;
+
+mod common;
+use common::rotated_image;
+
+
+fn npy_file(header: &str, data: &[u8]) -> Vec<u8> {
+    let mut header = header.to_string();
+    while !(10 + header.len() + 1).is_multiple_of(64) {
+        header.push(' ');
+    }
+    header.push('\n');
+    let mut file = b"\x93NUMPY\x01\x00".to_vec();
+    file.extend((header.len() as u16).to_le_bytes());
+    file.extend(header.as_bytes());
+    file.extend(data);
+    file
+}
+
+fn test_image() -> Image<f32> {
+    rotated_image((0..24).map(|v| v as f32 * 0.5 - 3.0).collect())
+}
+
+
+#[test]
+fn load_c_order() {
+    let data: Vec<u8> = (0..24i16).flat_map(|v| v.to_le_bytes()).collect();
+    write("test_npy_c.npy", npy_file("{'descr': '<i2', 'fortran_order': False, 'shape': (2, 3, 4), }", &data)).unwrap();
+
+    let loaded = load_npy("test_npy_c.npy").unwrap();
+    remove_file("test_npy_c.npy").unwrap();
+    let loaded = loaded.as_any().downcast_ref::<Image<i16>>().unwrap();
+    assert_eq!((loaded.width, loaded.height, loaded.depth), (4, 3, 2));
+    assert_eq!(loaded.voxels, (0..24).collect::<Vec<i16>>());
+    assert_eq!(loaded.spacing, [1.0, 1.0, 1.0]);
+}
+
+#[test]
+fn load_fortran_order_big_endian() {
+    // a[i, j] = i + 3 * j, stored first axis fastest; loads as its C order copy would.
+    let data: Vec<u8> = (0..6u32).flat_map(|v| v.to_be_bytes()).collect();
+    write("test_npy_f.npy", npy_file("{'descr': '>u4', 'fortran_order': True, 'shape': (3, 2), }", &data)).unwrap();
+
+    let loaded = load_npy("test_npy_f.npy").unwrap();
+    remove_file("test_npy_f.npy").unwrap();
+    let loaded = loaded.as_any().downcast_ref::<NdImage<u32>>().unwrap();
+    assert_eq!(loaded.size, vec![2, 3]);
+    assert_eq!(loaded.voxels, vec![0, 3, 1, 4, 2, 5]);
+}
+
+#[test]
+fn load_fortran_order_channels() {
+    // A (z, y, x, c) = (2, 1, 3, 2) array a[z, y, x, c] = 100z + 10x + c, in
+    // both orders, with spacing so the last axis is read as channels.
+    let value = |z: u16, x: u16, c: u16| (100 * z + 10 * x + c).to_le_bytes();
+    let c_order: Vec<u8> = (0..2).flat_map(|z| (0..3).flat_map(move |x| (0..2).flat_map(move |c| value(z, x, c)))).collect();
+    let f_order: Vec<u8> = (0..2).flat_map(|c| (0..3).flat_map(move |x| (0..2).flat_map(move |z| value(z, x, c)))).collect();
+
+    let mut images = Vec::new();
+    for (fortran, data) in [("False", c_order), ("True", f_order)] {
+        let mut zip = zip::ZipWriter::new(File::create("test_npy_fortran.npz").unwrap());
+        zip.start_file("image.npy", SimpleFileOptions::default()).unwrap();
+        let header = format!("{{'descr': '<u2', 'fortran_order': {}, 'shape': (2, 1, 3, 2), }}", fortran);
+        zip.write_all(&npy_file(&header, &data)).unwrap();
+        zip.start_file("spacing.npy", SimpleFileOptions::default()).unwrap();
+        let spacing: Vec<u8> = [0.5f64, 1.0, 2.0].iter().flat_map(|v| v.to_le_bytes()).collect();
+        zip.write_all(&npy_file("{'descr': '<f8', 'fortran_order': False, 'shape': (3,), }", &spacing)).unwrap();
+        zip.finish().unwrap();
+        images.push(load_npz("test_npy_fortran.npz").unwrap());
+        remove_file("test_npy_fortran.npz").unwrap();
+    }
+
+    for loaded in images {
+        let loaded = loaded.as_any().downcast_ref::<VectorImage<u16>>().unwrap();
+        assert_eq!((loaded.channels, loaded.width, loaded.height, loaded.depth), (2, 3, 1, 2));
+        assert_eq!(loaded.voxels[..6], [0, 1, 10, 11, 20, 21]);
+        assert_eq!(loaded.voxels[6..8], [100, 101]);
+    }
+}
+
+#[test]
+fn load_bool_as_u8() {
+    write("test_npy_bool.npy", npy_file("{'descr': '|b1', 'fortran_order': False, 'shape': (4,), }", &[1, 0, 0, 1])).unwrap();
+
+    let loaded = load_npy("test_npy_bool.npy").unwrap();
+    remove_file("test_npy_bool.npy").unwrap();
+    let loaded = loaded.as_any().downcast_ref::<NdImage<u8>>().unwrap();
+    assert_eq!(loaded.voxels, vec![1, 0, 0, 1]);
+}
+
+#[test]
+fn roundtrip_npy_element_types() {
+    let image = test_image();
+    save_npy(&image, "test_npy_f32.npy").unwrap();
+    let file = read("test_npy_f32.npy").unwrap();
+    assert_eq!(&file[..8], b"\x93NUMPY\x01\x00");
+    let data_start = file.len() - 24 * 4;
+    assert_eq!(data_start % 64, 0);
+    assert!(String::from_utf8_lossy(&file[10..data_start]).contains("'shape': (2, 3, 4)"));
+
+    let loaded = load("test_npy_f32.npy").unwrap();
+    remove_file("test_npy_f32.npy").unwrap();
+    assert_eq!(loaded.as_any().downcast_ref::<Image<f32>>().unwrap().voxels, image.voxels);
+
+    let image = NdImage {
+        voxels: vec![-5i64, 7, i64::MAX, i64::MIN],
+        size: vec![2, 2],
+        spacing: vec![1.0, 1.0],
+        origin: vec![0.0, 0.0],
+        direction: vec![1.0, 0.0, 0.0, 1.0],
+        metadata: Metadata::default(),
+    };
+    save_npy(&image, "test_npy_i64.npy").unwrap();
+    let loaded = load_npy("test_npy_i64.npy").unwrap();
+    remove_file("test_npy_i64.npy").unwrap();
+    let loaded = loaded.as_any().downcast_ref::<NdImage<i64>>().unwrap();
+    assert_eq!(loaded.voxels, image.voxels);
+    assert_eq!(loaded.size, vec![2, 2]);
+}
+
+#[test]
+fn roundtrip_npz_geometry() {
+    let image = test_image();
+    for compress in [false, true] {
+        save_npz(&image, "test_npy_geometry.npz", compress).unwrap();
+        let loaded = load_npz("test_npy_geometry.npz").unwrap();
+        remove_file("test_npy_geometry.npz").unwrap();
+        let loaded = loaded.as_any().downcast_ref::<Image<f32>>().unwrap();
+        assert_eq!(loaded.voxels, image.voxels);
+        assert_eq!(loaded.spacing, image.spacing);
+        assert_eq!(loaded.origin, image.origin);
+        assert_eq!(loaded.direction, image.direction);
+    }
+
+    let vector = VectorImage {
+        voxels: (0..12u8).collect(),
+        channels: 3,
+        width: 2,
+        height: 2,
+        depth: 1,
+        spacing: [0.3, 0.3, 1.0],
+        origin: [0.0; 3],
+        direction: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        metadata: Metadata::default(),
+    };
+    save_npz(&vector, "test_npy_vector.npz", false).unwrap();
+    let loaded = load("test_npy_vector.npz").unwrap();
+    remove_file("test_npy_vector.npz").unwrap();
+    let loaded = loaded.as_any().downcast_ref::<VectorImage<u8>>().unwrap();
+    assert_eq!(loaded.channels, 3);
+    assert_eq!((loaded.width, loaded.height, loaded.depth), (2, 2, 1));
+    assert_eq!(loaded.voxels, vector.voxels);
+    assert_eq!(loaded.spacing, vector.spacing);
+}
+
+#[test]
+fn npz_without_geometry_and_errors() {
+    // np.savez("x.npz", volume) names the array arr_0.
+    let write_npz = |path: &str, spacing: usize| {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        zip.start_file("arr_0.npy", SimpleFileOptions::default()).unwrap();
+        zip.write_all(&npy_file("{'descr': '|u1', 'fortran_order': False, 'shape': (2, 2, 2), }", &[7; 8])).unwrap();
+        if spacing > 0 {
+            zip.start_file("spacing.npy", SimpleFileOptions::default()).unwrap();
+            let header = format!("{{'descr': '<f8', 'fortran_order': False, 'shape': ({},), }}", spacing);
+            zip.write_all(&npy_file(&header, &vec![0; 8 * spacing])).unwrap();
+        }
+        zip.finish().unwrap();
+    };
+
+    write_npz("test_npy_arr0.npz", 0);
+    let loaded = load_npz("test_npy_arr0.npz").unwrap();
+    remove_file("test_npy_arr0.npz").unwrap();
+    assert_eq!(loaded.as_any().downcast_ref::<Image<u8>>().unwrap().voxels, vec![7; 8]);
+
+    // The entry claims almost 4 GiB in the zip headers, the data is 8 bytes.
+    write_npz("test_npy_size.npz", 0);
+    let mut zip = read("test_npy_size.npz").unwrap();
+    let central = zip.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+    zip[22..26].copy_from_slice(&0xFFFF_FFF0u32.to_le_bytes());
+    zip[central + 24..central + 28].copy_from_slice(&0xFFFF_FFF0u32.to_le_bytes());
+    write("test_npy_size.npz", zip).unwrap();
+    let result = load_npz("test_npy_size.npz");
+    remove_file("test_npy_size.npz").unwrap();
+    assert_eq!(result.unwrap().as_any().downcast_ref::<Image<u8>>().unwrap().voxels, vec![7; 8]);
+
+    write_npz("test_npy_spacing.npz", 4);
+    let result = load_npz("test_npy_spacing.npz");
+    remove_file("test_npy_spacing.npz").unwrap();
+    assert!(matches!(result.err().unwrap(), NpyError::InvalidGeometry { expected: 3, found: 4, .. }));
+
+    write("test_npy_bad.npy", b"\x93NUMPY\x01\x00\x10\x00{'descr': '<c8'}").unwrap();
+    let result = load_npy("test_npy_bad.npy");
+    remove_file("test_npy_bad.npy").unwrap();
+    assert!(matches!(result.err().unwrap(), NpyError::UnsupportedDType(d) if d == "<c8"));
+
+    write("test_npy_short.npy", npy_file("{'descr': '<f8', 'fortran_order': False, 'shape': (10,), }", &[0; 8])).unwrap();
+    let result = load_npy("test_npy_short.npy");
+    remove_file("test_npy_short.npy").unwrap();
+    assert!(matches!(result.err().unwrap(), NpyError::Truncated { expected: 80, found: 8 }));
+
+    let header = "{'descr': '<f8', 'fortran_order': False, 'shape': (4000000000, 4000000000, 4000000000), }";
+    write("test_npy_huge.npy", npy_file(header, &[0; 8])).unwrap();
+    let result = load_npy("test_npy_huge.npy");
+    remove_file("test_npy_huge.npy").unwrap();
+    assert!(matches!(result.err().unwrap(), NpyError::InvalidHeader(_)));
+}
@@ -0,0 +1,170 @@
+use oxels::{Image, NdImage, VectorImage, Metadata, NrrdEncoding, NrrdError, load_nrrd, save_nrrd};
+use flate2::{Compression, write::GzEncoder};
+use std::fs::{read_to_string, remove_file, write};
+use std::io::Write;
+use std::path::Path;
+
+mod common;
+use common::{assert_close, rotated_image};
+
+
+fn test_image() -> Image<i16> {
+    Image {
+        metadata: Metadata::from_iter([("PatientName", "Jane Doe")]),
+        ..rotated_image((0..24).map(|v| v * 3 - 20).collect())
+    }
+}
+
+
+#[test]
+fn load_attached_ras_header() {
+    let mut file = b"NRRD0004
+# a comment
+type: unsigned short
+dimension: 3
+space: right-anterior-superior
+sizes: 2 2 2
+space directions: (-1.5,0,0) (0, -2, 0) (0,0,3)
+kinds: domain domain domain
+endian: big
+encoding: raw
+space origin: (5,6,7)
+Modality:=CT
+
+".to_vec();
+    file.extend((1..=8u16).flat_map(|v| v.to_be_bytes()));
+    write("test_nrrd_ras.nrrd", file).unwrap();
+
+    let loaded = load_nrrd("test_nrrd_ras.nrrd").unwrap();
+    remove_file("test_nrrd_ras.nrrd").unwrap();
+    let loaded = loaded.as_any().downcast_ref::<Image<u16>>().unwrap();
+    assert_eq!(loaded.voxels, (1..=8).collect::<Vec<u16>>());
+    assert_close(&loaded.spacing, &[1.5, 2.0, 3.0]);
+    assert_close(&loaded.origin, &[-5.0, -6.0, 7.0]);
+    assert_close(&loaded.direction, &[1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]);
+    assert_eq!(loaded.metadata.get("Modality"), Some("CT"));
+}
+
+#[test]
+fn load_detached_gzip_vector_header() {
+    let header = "NRRD0005
+type: float
+dimension: 4
+space: left-posterior-superior
+sizes: 3 2 1 1
+space directions: none (1,0,0) (0,1,0) (0,0,1)
+kinds: vector domain domain domain
+endian: little
+encoding: gzip
+space origin: (0,0,0)
+data file: test_nrrd_vector.raw.gz
+";
+    let voxels: Vec<f32> = (0..6).map(|v| v as f32 / 2.0).collect();
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytemuck::cast_slice(&voxels)).unwrap();
+    write("test_nrrd_vector.nhdr", header).unwrap();
+    write("test_nrrd_vector.raw.gz", encoder.finish().unwrap()).unwrap();
+
+    let loaded = load_nrrd("test_nrrd_vector.nhdr").unwrap();
+    remove_file("test_nrrd_vector.nhdr").unwrap();
+    remove_file("test_nrrd_vector.raw.gz").unwrap();
+    let loaded = loaded.as_any().downcast_ref::<VectorImage<f32>>().unwrap();
+    assert_eq!(loaded.channels, 3);
+    assert_eq!((loaded.width, loaded.height, loaded.depth), (2, 1, 1));
+    assert_eq!(loaded.voxels, voxels);
+}
+
+#[test]
+fn load_ascii_without_space() {
+    let file = "NRRD0004
+type: double
+dimension: 2
+sizes: 3 2
+spacings: 0.5 4
+encoding: ascii
+
+1 2 3
+4.5 5 6
+";
+    write("test_nrrd_ascii.nrrd", file).unwrap();
+    let loaded = load_nrrd("test_nrrd_ascii.nrrd").unwrap();
+    remove_file("test_nrrd_ascii.nrrd").unwrap();
+
+    let loaded = loaded.as_any().downcast_ref::<NdImage<f64>>().unwrap();
+    assert_eq!(loaded.size, vec![3, 2]);
+    assert_eq!(loaded.voxels, vec![1.0, 2.0, 3.0, 4.5, 5.0, 6.0]);
+    assert_close(&loaded.spacing, &[0.5, 4.0]);
+
+    write("test_nrrd_ascii.nrrd", file.replace("4.5", "four")).unwrap();
+    let result = load_nrrd("test_nrrd_ascii.nrrd");
+    remove_file("test_nrrd_ascii.nrrd").unwrap();
+    let err = result.err().unwrap();
+    assert!(matches!(err, NrrdError::InvalidValue { line: 9, .. }), "{}", err);
+}
+
+#[test]
+fn roundtrip_all_encodings() {
+    let img = test_image();
+    for encoding in [NrrdEncoding::Raw, NrrdEncoding::Gzip, NrrdEncoding::Ascii] {
+        for path in ["test_nrrd_roundtrip.nrrd", "test_nrrd_roundtrip.nhdr"] {
+            save_nrrd(&img, path, encoding).unwrap();
+            let loaded = load_nrrd(path).unwrap();
+
+            let header = read_to_string(path).unwrap_or_default();
+            remove_file(path).unwrap();
+            for data_file in ["test_nrrd_roundtrip.raw", "test_nrrd_roundtrip.raw.gz", "test_nrrd_roundtrip.txt"] {
+                if Path::new(data_file).is_file() {
+                    assert!(header.contains(&format!("data file: {}", data_file)));
+                    remove_file(data_file).unwrap();
+                }
+            }
+
+            let loaded = loaded.as_any().downcast_ref::<Image<i16>>().unwrap();
+            assert_eq!(loaded.voxels, img.voxels, "{:?} {}", encoding, path);
+            assert_close(&loaded.spacing, &img.spacing);
+            assert_close(&loaded.origin, &img.origin);
+            assert_close(&loaded.direction, &img.direction);
+            assert_eq!(loaded.metadata.get("PatientName"), Some("Jane Doe"));
+        }
+    }
+}
+
+#[test]
+fn roundtrip_vector_image() {
+    let img = VectorImage {
+        voxels: (0..24u8).collect(),
+        channels: 3,
+        width: 2,
+        height: 2,
+        depth: 2,
+        spacing: [1.0, 2.0, 3.0],
+        origin: [0.0, 0.0, 0.0],
+        direction: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        metadata: Metadata::default(),
+    };
+    save_nrrd(&img, "test_nrrd_rgb.nrrd", NrrdEncoding::Raw).unwrap();
+    let loaded = load_nrrd("test_nrrd_rgb.nrrd").unwrap();
+    remove_file("test_nrrd_rgb.nrrd").unwrap();
+
+    let loaded = loaded.as_any().downcast_ref::<VectorImage<u8>>().unwrap();
+    assert_eq!(loaded.channels, 3);
+    assert_eq!(loaded.voxels, img.voxels);
+    assert_close(&loaded.spacing, &img.spacing);
+}
+
+#[test]
+fn missing_type_is_an_error() {
+    write("test_nrrd_missing.nrrd", "NRRD0004\ndimension: 1\nsizes: 2\nencoding: raw\n\n\x01\x02").unwrap();
+    let err = load_nrrd("test_nrrd_missing.nrrd").err().unwrap();
+    remove_file("test_nrrd_missing.nrrd").unwrap();
+    assert!(matches!(err, NrrdError::Missing("type")), "{}", err);
+}
+
+#[test]
+fn oversized_header_is_an_error() {
+    let file = "NRRD0004\ntype: double\ndimension: 3\nsizes: 4000000000 4000000000 4000000000\nendian: little\nencoding: gzip\n\n\x01\x02";
+    write("test_nrrd_oversized.nrrd", file).unwrap();
+    let err = load_nrrd("test_nrrd_oversized.nrrd").err().unwrap();
+    remove_file("test_nrrd_oversized.nrrd").unwrap();
+    assert!(matches!(err, NrrdError::InvalidHeader(_)), "{}", err);
+}
@@ -0,0 +1,171 @@
+use oxels::{Image, VectorImage, Metadata, PngError, SlicePlane, Window, export_png_slice, load, load_png, save_png};
+use flate2::{Compression, Crc, write::ZlibEncoder};
+use std::fs::{read, remove_file, write};
+use std::io::Write;
+
+
+fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend((data.len() as u32).to_be_bytes());
+    out.extend(kind);
+    out.extend(data);
+    let mut crc = Crc::new();
+    crc.update(kind);
+    crc.update(data);
+    out.extend(crc.sum().to_be_bytes());
+}
+
+// PNG with the given IHDR fields and already filtered scanlines.
+fn png_file(width: u32, height: u32, bit_depth: u8, color_type: u8, interlace: u8, palette: &[u8], scanlines: &[u8]) -> Vec<u8> {
+    let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+    let header = [&width.to_be_bytes()[..], &height.to_be_bytes(), &[bit_depth, color_type, 0, 0, interlace]].concat();
+    chunk(&mut out, b"IHDR", &header);
+    if !palette.is_empty() {
+        chunk(&mut out, b"PLTE", palette);
+    }
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(scanlines).unwrap();
+    chunk(&mut out, b"IDAT", &encoder.finish().unwrap());
+    chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+fn volume(spacing: [f64; 3]) -> Image<i16> {
+    Image {
+        voxels: (0..24).map(|i| (i / 8) * 100 + (i / 4 % 2) * 10 + i % 4).collect(),
+        width: 4,
+        height: 2,
+        depth: 3,
+        spacing,
+        origin: [0.0; 3],
+        direction: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        metadata: Metadata::default(),
+    }
+}
+
+
+#[test]
+fn roundtrip_gray_and_rgb() {
+    let gray = Image {
+        voxels: (0..12u8).map(|v| v * 20).collect(),
+        width: 4,
+        height: 3,
+        depth: 1,
+        spacing: [0.5, 0.25, 1.0],
+        origin: [0.0; 3],
+        direction: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        metadata: Metadata::from_iter([("Comment", "QA slice")]),
+    };
+    save_png(&gray, "test_png_gray.png").unwrap();
+    let loaded = load("test_png_gray.png").unwrap();
+    remove_file("test_png_gray.png").unwrap();
+    let loaded = loaded.as_any().downcast_ref::<Image<u8>>().unwrap();
+    assert_eq!((loaded.width, loaded.height, loaded.depth), (4, 3, 1));
+    assert_eq!(loaded.voxels, gray.voxels);
+    assert_eq!(loaded.spacing, gray.spacing);
+    assert_eq!(loaded.metadata.get("Comment"), Some("QA slice"));
+
+    let wide = Image {
+        voxels: vec![0u16, 1, 65535, 300],
+        width: 2,
+        height: 2,
+        depth: 1,
+        spacing: [1.0; 3],
+        origin: [0.0; 3],
+        direction: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        metadata: Metadata::default(),
+    };
+    save_png(&wide, "test_png_16.png").unwrap();
+    let loaded = load_png("test_png_16.png").unwrap();
+    remove_file("test_png_16.png").unwrap();
+    assert_eq!(loaded.as_any().downcast_ref::<Image<u16>>().unwrap().voxels, wide.voxels);
+
+    let rgb = VectorImage {
+        voxels: (0..18u8).map(|v| v * 13).collect(),
+        channels: 3,
+        width: 3,
+        height: 2,
+        depth: 1,
+        spacing: [1.0; 3],
+        origin: [0.0; 3],
+        direction: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        metadata: Metadata::default(),
+    };
+    save_png(&rgb, "test_png_rgb.png").unwrap();
+    let loaded = load_png("test_png_rgb.png").unwrap();
+    remove_file("test_png_rgb.png").unwrap();
+    let loaded = loaded.as_any().downcast_ref::<VectorImage<u8>>().unwrap();
+    assert_eq!((loaded.channels, loaded.depth), (3, 1));
+    assert_eq!(loaded.voxels, rgb.voxels);
+
+    assert!(matches!(save_png(&volume([1.0; 3]), "test_png_never.png").err().unwrap(), PngError::UnsupportedType(_)));
+    let empty = Image { voxels: Vec::<u8>::new(), width: 0, ..gray };
+    assert!(matches!(save_png(&empty, "test_png_empty.png").err().unwrap(), PngError::Unsupported(_)));
+    assert!(read("test_png_empty.png").is_err());
+}
+
+#[test]
+fn load_palette_and_interlaced() {
+    // 2 bit palette indices 0,1,2 / 2,1,0, packed from the high bits.
+    let palette = [255, 0, 0, 0, 255, 0, 0, 0, 255];
+    let file = png_file(3, 2, 2, 3, 0, &palette, &[0, 0b00_01_10_00, 0, 0b10_01_00_00]);
+    write("test_png_palette.png", file).unwrap();
+    let loaded = load_png("test_png_palette.png").unwrap();
+    remove_file("test_png_palette.png").unwrap();
+    let loaded = loaded.as_any().downcast_ref::<VectorImage<u8>>().unwrap();
+    assert_eq!(loaded.channels, 3);
+    assert_eq!(&loaded.voxels[..9], &palette);
+    assert_eq!(&loaded.voxels[9..12], &palette[6..9]);
+
+    // Adam7 passes of a 3x3 image with pixel value y * 3 + x, each scanline unfiltered.
+    let scanlines = [0, 0, 0, 2, 0, 6, 8, 0, 1, 0, 7, 0, 3, 4, 5];
+    write("test_png_adam7.png", png_file(3, 3, 8, 0, 1, &[], &scanlines)).unwrap();
+    let loaded = load_png("test_png_adam7.png").unwrap();
+    remove_file("test_png_adam7.png").unwrap();
+    assert_eq!(loaded.as_any().downcast_ref::<Image<u8>>().unwrap().voxels, (0..9).collect::<Vec<u8>>());
+
+    let mut broken = png_file(1, 1, 8, 0, 0, &[], &[0, 0]);
+    broken[29] ^= 1;
+    write("test_png_crc.png", broken).unwrap();
+    let result = load_png("test_png_crc.png");
+    remove_file("test_png_crc.png").unwrap();
+    assert!(matches!(result.err().unwrap(), PngError::Checksum(kind) if kind == "IHDR"));
+}
+
+#[test]
+fn load_oversized_header() {
+    // Sizes the data does not cover, and beyond the 2^31 - 1 the spec allows.
+    for (width, height) in [(0x7FFF_FFFF, 0x7FFF_FFFF), (0x8000_0000, 1)] {
+        write("test_png_huge.png", png_file(width, height, 16, 6, 0, &[], &[0; 9])).unwrap();
+        let result = load_png("test_png_huge.png");
+        remove_file("test_png_huge.png").unwrap();
+        assert!(matches!(result.err().unwrap(), PngError::InvalidData(_)));
+    }
+}
+
+#[test]
+fn export_slices() {
+    // Values are z * 100 + y * 10 + x, the window maps them 1:1 to gray levels.
+    let image = volume([1.0, 1.0, 1.0]);
+    let window = Window::from_range(0.0, 255.0);
+    export_png_slice(&image, SlicePlane::Coronal, 1, window, "test_png_coronal.png").unwrap();
+    let loaded = load_png("test_png_coronal.png").unwrap();
+    remove_file("test_png_coronal.png").unwrap();
+    let loaded = loaded.as_any().downcast_ref::<Image<u8>>().unwrap();
+    assert_eq!((loaded.width, loaded.height), (4, 3));
+    // Superior (largest z) at the top.
+    assert_eq!(loaded.voxels, vec![210, 211, 212, 213, 110, 111, 112, 113, 10, 11, 12, 13]);
+
+    // Rows are twice as far apart as columns, the slice is stretched to 4x4.
+    let image = volume([1.0, 2.0, 1.0]);
+    export_png_slice(&image, SlicePlane::Axial, 1, window, "test_png_axial.png").unwrap();
+    let loaded = load_png("test_png_axial.png").unwrap();
+    remove_file("test_png_axial.png").unwrap();
+    let loaded = loaded.as_any().downcast_ref::<Image<u8>>().unwrap();
+    assert_eq!((loaded.width, loaded.height), (4, 4));
+    assert_eq!(loaded.spacing, [1.0, 1.0, 1.0]);
+    let column: Vec<u8> = loaded.voxels.iter().step_by(4).copied().collect();
+    assert_eq!(column, vec![100, 103, 108, 110]);
+
+    let result = export_png_slice(&image, SlicePlane::Sagittal, 4, window, "test_png_never.png");
+    assert!(matches!(result.err().unwrap(), PngError::OutOfRange { index: 4, size: 4 }));
+}
@@ -0,0 +1,81 @@
+use oxels::{Image, AnyImage, MetaImageError, MetaImageReader, load_meta_image};
+
+
+fn expected_region(full: &Image<u16>, start: [u32; 3], size: [u32; 3]) -> Vec<u16> {
+    let mut voxels = Vec::new();
+    for z in start[2]..start[2] + size[2] {
+        for y in start[1]..start[1] + size[1] {
+            for x in start[0]..start[0] + size[0] {
+                let index = (x + y * full.width + z * full.width * full.height) as usize;
+                voxels.push(full.voxels[index]);
+            }
+        }
+    }
+    voxels
+}
+
+
+fn assert_region(path: &str) {
+    let full = load_meta_image("assets/tensors/uint16_uncompressed.mha").unwrap();
+    let full = full.as_any().downcast_ref::<Image<u16>>().unwrap();
+
+    let reader = MetaImageReader::open(path).unwrap();
+    assert_eq!(reader.size(), &[50, 50, 50]);
+
+    let (start, size) = ([10, 20, 30], [5, 7, 3]);
+    let region = reader.read_region(&start, &size).unwrap();
+    let region = region.as_any().downcast_ref::<Image<u16>>().unwrap();
+    assert_eq!((region.width, region.height, region.depth), (5, 7, 3));
+    assert_eq!(region.voxels, expected_region(full, start, size));
+
+    // Identity direction, so the origin moves by start * spacing.
+    for (axis, &s) in start.iter().enumerate() {
+        let expected = full.origin[axis] + s as f64 * full.spacing[axis];
+        assert!((region.origin[axis] - expected).abs() < 1e-9);
+    }
+}
+
+
+#[test]
+fn read_region_uncompressed_mha() {
+    assert_region("assets/tensors/uint16_uncompressed.mha");
+}
+
+#[test]
+fn read_region_uncompressed_mhd() {
+    assert_region("assets/tensors/uint16_uncompressed.mhd");
+}
+
+#[test]
+fn read_region_compressed_mhd() {
+    assert_region("assets/tensors/uint16_compressed.mhd");
+}
+
+#[test]
+fn read_region_compressed_mha() {
+    assert_region("assets/tensors/uint16_compressed.mha");
+}
+
+#[test]
+fn slices_cover_volume() {
+    for path in ["assets/tensors/float32_uncompressed.mhd", "assets/tensors/uint16_compressed.mha"] {
+        let reader = MetaImageReader::open(path).unwrap();
+        let mut count = 0;
+        let mut sum = 0.0;
+        for slice in reader.slices().unwrap() {
+            let slice: Box<dyn AnyImage> = slice.unwrap();
+            assert_eq!(slice.size(), vec![50, 50, 1]);
+            sum += slice.iter_f64().sum::<f64>();
+            count += 1;
+        }
+        assert_eq!(count, 50);
+        assert_eq!(sum, 7203.0);
+    }
+}
+
+#[test]
+fn region_out_of_bounds() {
+    let reader = MetaImageReader::open("assets/tensors/uint8_uncompressed.mha").unwrap();
+    let err = reader.read_region(&[45, 0, 0], &[10, 1, 1]).err().unwrap();
+    assert!(matches!(err, MetaImageError::InvalidRegion { .. }));
+}
@@ -0,0 +1,303 @@
+use oxels::{Image, NdImage, VectorImage, Metadata, TiffCompression, TiffError, load, load_tiff, save_tiff};
+use flate2::{Compression, write::ZlibEncoder};
+use std::fs::{read, remove_file, write};
+use std::io::Write;
+
+
+enum Value {
+    Short(Vec<u16>),
+    Long(Vec<u32>),
+    Rational(u32, u32),
+    Ascii(&'static str),
+}
+
+// Tags and strips of one page.
+type Page = (Vec<(u16, Value)>, Vec<Vec<u8>>);
+
+// Classic TIFF with one directory per page, strips are placed before their directory.
+fn tiff_file(big_endian: bool, pages: Vec<Page>) -> Vec<u8> {
+    let u16b = |v: u16| if big_endian { v.to_be_bytes() } else { v.to_le_bytes() };
+    let u32b = |v: u32| if big_endian { v.to_be_bytes() } else { v.to_le_bytes() };
+    let mut out = if big_endian { b"MM\0*".to_vec() } else { b"II*\0".to_vec() };
+    out.extend([0; 4]);
+    let mut next_pointer = 4;
+
+    for (mut entries, strips) in pages {
+        let mut offsets = Vec::new();
+        for strip in &strips {
+            offsets.push(out.len() as u32);
+            out.extend(strip);
+            if out.len() % 2 == 1 {
+                out.push(0);
+            }
+        }
+        entries.push((273, Value::Long(offsets)));
+        entries.push((279, Value::Long(strips.iter().map(|s| s.len() as u32).collect())));
+        entries.sort_by_key(|(tag, _)| *tag);
+
+        let ifd = out.len();
+        out[next_pointer..next_pointer + 4].copy_from_slice(&u32b(ifd as u32));
+        let mut extra_offset = ifd + 2 + entries.len() * 12 + 4;
+        let mut extra = Vec::new();
+        out.extend(u16b(entries.len() as u16));
+        for (tag, value) in entries {
+            let (field_type, count, bytes): (u16, usize, Vec<u8>) = match value {
+                Value::Short(v) => (3, v.len(), v.iter().flat_map(|&x| u16b(x)).collect()),
+                Value::Long(v) => (4, v.len(), v.iter().flat_map(|&x| u32b(x)).collect()),
+                Value::Rational(n, d) => (5, 1, [u32b(n), u32b(d)].concat()),
+                Value::Ascii(text) => (2, text.len() + 1, [text.as_bytes(), &[0]].concat()),
+            };
+            out.extend(u16b(tag));
+            out.extend(u16b(field_type));
+            out.extend(u32b(count as u32));
+            if bytes.len() <= 4 {
+                out.extend(&bytes);
+                out.extend(vec![0; 4 - bytes.len()]);
+            } else {
+                out.extend(u32b(extra_offset as u32));
+                extra_offset += bytes.len() + bytes.len() % 2;
+                extra.extend(&bytes);
+                if bytes.len() % 2 == 1 {
+                    extra.push(0);
+                }
+            }
+        }
+        next_pointer = out.len();
+        out.extend([0; 4]);
+        out.extend(extra);
+    }
+    out
+}
+
+fn test_image() -> Image<u16> {
+    Image {
+        voxels: (0..60).map(|v| v * 1000).collect(),
+        width: 5,
+        height: 4,
+        depth: 3,
+        spacing: [0.25, 0.4, 1.5],
+        origin: [0.0; 3],
+        direction: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        metadata: Metadata::from_iter([("unit", "\u{b5}m")]),
+    }
+}
+
+
+#[test]
+fn roundtrip_compressions() {
+    let image = test_image();
+    for compression in [TiffCompression::None, TiffCompression::Lzw, TiffCompression::Deflate] {
+        save_tiff(&image, "test_tiff_roundtrip.tif", compression).unwrap();
+        let file = read("test_tiff_roundtrip.tif").unwrap();
+        assert_eq!(&file[..4], b"II*\0");
+
+        let loaded = load("test_tiff_roundtrip.tif").unwrap();
+        remove_file("test_tiff_roundtrip.tif").unwrap();
+        let loaded = loaded.as_any().downcast_ref::<Image<u16>>().unwrap();
+        assert_eq!((loaded.width, loaded.height, loaded.depth), (5, 4, 3));
+        assert_eq!(loaded.voxels, image.voxels);
+        assert_eq!(loaded.spacing, image.spacing);
+        assert_eq!(loaded.metadata.get("unit"), Some("\u{b5}m"));
+    }
+}
+
+#[test]
+fn roundtrip_float_rgb_and_frames() {
+    let image = Image {
+        voxels: vec![-1.5f32, 0.0, 2.25, f32::MAX],
+        width: 2,
+        height: 2,
+        depth: 1,
+        spacing: [1.0; 3],
+        origin: [0.0; 3],
+        direction: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        metadata: Metadata::default(),
+    };
+    save_tiff(&image, "test_tiff_float.tiff", TiffCompression::Lzw).unwrap();
+    let loaded = load_tiff("test_tiff_float.tiff").unwrap();
+    remove_file("test_tiff_float.tiff").unwrap();
+    assert_eq!(loaded.as_any().downcast_ref::<Image<f32>>().unwrap().voxels, image.voxels);
+
+    let rgb = VectorImage {
+        voxels: (0..24u8).collect(),
+        channels: 3,
+        width: 2,
+        height: 2,
+        depth: 2,
+        spacing: [1.0; 3],
+        origin: [0.0; 3],
+        direction: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        metadata: Metadata::default(),
+    };
+    save_tiff(&rgb, "test_tiff_rgb.tif", TiffCompression::Deflate).unwrap();
+    let loaded = load_tiff("test_tiff_rgb.tif").unwrap();
+    remove_file("test_tiff_rgb.tif").unwrap();
+    let loaded = loaded.as_any().downcast_ref::<VectorImage<u8>>().unwrap();
+    assert_eq!((loaded.channels, loaded.depth), (3, 2));
+    assert_eq!(loaded.voxels, rgb.voxels);
+
+    let movie = NdImage {
+        voxels: (0..16i32).collect(),
+        size: vec![2, 2, 2, 2],
+        spacing: vec![1.0, 1.0, 3.0, 60.0],
+        origin: vec![0.0; 4],
+        direction: (0..16).map(|i| if i % 5 == 0 { 1.0 } else { 0.0 }).collect(),
+        metadata: Metadata::default(),
+    };
+    save_tiff(&movie, "test_tiff_frames.tif", TiffCompression::None).unwrap();
+    let loaded = load_tiff("test_tiff_frames.tif").unwrap();
+    remove_file("test_tiff_frames.tif").unwrap();
+    let loaded = loaded.as_any().downcast_ref::<NdImage<i32>>().unwrap();
+    assert_eq!(loaded.size, movie.size);
+    assert_eq!(loaded.spacing, movie.spacing);
+    assert_eq!(loaded.voxels, movie.voxels);
+}
+
+#[test]
+fn load_big_endian_deflate_predictor() {
+    // Two 3x2 pages of int16 in two strips each, horizontally differenced.
+    let page = |values: [i16; 6]| -> Vec<Vec<u8>> {
+        values.chunks(3).map(|row| {
+            let diffs = [row[0], row[1].wrapping_sub(row[0]), row[2].wrapping_sub(row[1])];
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&diffs.iter().flat_map(|v| v.to_be_bytes()).collect::<Vec<u8>>()).unwrap();
+            encoder.finish().unwrap()
+        }).collect()
+    };
+    let tags = || vec![
+        (256, Value::Long(vec![3])),
+        (257, Value::Long(vec![2])),
+        (258, Value::Short(vec![16])),
+        (259, Value::Short(vec![8])),
+        (262, Value::Short(vec![1])),
+        (278, Value::Short(vec![1])),
+        (282, Value::Rational(4, 1)),
+        (283, Value::Rational(2, 1)),
+        (296, Value::Short(vec![3])),
+        (317, Value::Short(vec![2])),
+        (339, Value::Short(vec![2])),
+    ];
+    let file = tiff_file(true, vec![
+        (tags(), page([-300, 5, 7, 32767, -32768, 0])),
+        (tags(), page([1, 2, 3, 4, 5, 6])),
+    ]);
+    write("test_tiff_be.tif", file).unwrap();
+
+    let loaded = load_tiff("test_tiff_be.tif").unwrap();
+    remove_file("test_tiff_be.tif").unwrap();
+    let loaded = loaded.as_any().downcast_ref::<Image<i16>>().unwrap();
+    assert_eq!((loaded.width, loaded.height, loaded.depth), (3, 2, 2));
+    assert_eq!(loaded.voxels, vec![-300, 5, 7, 32767, -32768, 0, 1, 2, 3, 4, 5, 6]);
+    assert_eq!(loaded.spacing, [0.25, 0.5, 1.0]);
+    assert_eq!(loaded.metadata.get("unit"), Some("cm"));
+}
+
+#[test]
+fn load_imagej_channels() {
+    // ImageJ stores the channels of a hyperstack as consecutive pages.
+    let description = "ImageJ=1.54f\nimages=4\nchannels=2\nslices=2\nhyperstack=true\nunit=\\u00B5m\nspacing=2.5\nloop=false\n";
+    let page = |first: bool, value: u8| {
+        let mut tags = vec![
+            (256, Value::Long(vec![2])),
+            (257, Value::Long(vec![1])),
+            (258, Value::Short(vec![8])),
+            (282, Value::Rational(10, 1)),
+            (283, Value::Rational(10, 1)),
+        ];
+        if first {
+            tags.push((270, Value::Ascii(description)));
+        }
+        (tags, vec![vec![value, value + 1]])
+    };
+    let file = tiff_file(false, vec![page(true, 0), page(false, 10), page(false, 20), page(false, 30)]);
+    write("test_tiff_imagej.tif", file).unwrap();
+
+    let loaded = load("test_tiff_imagej.tif").unwrap();
+    remove_file("test_tiff_imagej.tif").unwrap();
+    let loaded = loaded.as_any().downcast_ref::<VectorImage<u8>>().unwrap();
+    assert_eq!((loaded.channels, loaded.width, loaded.height, loaded.depth), (2, 2, 1, 2));
+    assert_eq!(loaded.voxels, vec![0, 10, 1, 11, 20, 30, 21, 31]);
+    assert_eq!(loaded.spacing, [0.1, 0.1, 2.5]);
+    assert_eq!(loaded.metadata.get("unit"), Some("\u{b5}m"));
+}
+
+#[test]
+fn load_errors() {
+    write("test_tiff_not.tif", b"P5 2 2 255\n").unwrap();
+    let result = load_tiff("test_tiff_not.tif");
+    remove_file("test_tiff_not.tif").unwrap();
+    assert!(matches!(result.err().unwrap(), TiffError::NotTiff));
+
+    let page = |width: u32| (vec![
+        (256, Value::Long(vec![width])),
+        (257, Value::Long(vec![1])),
+        (258, Value::Short(vec![8])),
+    ], vec![vec![0; width as usize]]);
+    write("test_tiff_mixed.tif", tiff_file(false, vec![page(2), page(3)])).unwrap();
+    let result = load_tiff("test_tiff_mixed.tif");
+    remove_file("test_tiff_mixed.tif").unwrap();
+    assert!(matches!(result.err().unwrap(), TiffError::InconsistentPages(_)));
+}
+
+#[test]
+fn load_corrupt_headers() {
+    let load_page = |tags: Vec<(u16, Value)>, strips: Vec<Vec<u8>>| {
+        write("test_tiff_corrupt.tif", tiff_file(false, vec![(tags, strips)])).unwrap();
+        let result = load_tiff("test_tiff_corrupt.tif");
+        remove_file("test_tiff_corrupt.tif").unwrap();
+        result.err().unwrap()
+    };
+
+    // A page size that does not fit in memory is not allocated, one that overflows is an error.
+    let huge_page = |bits| load_page(vec![
+        (256, Value::Long(vec![u32::MAX])),
+        (257, Value::Long(vec![u32::MAX])),
+        (258, Value::Short(vec![bits])),
+    ], vec![vec![0; 4]]);
+    let err = huge_page(8);
+    assert!(matches!(err, TiffError::Truncated { found: 4, .. }), "{}", err);
+    let err = huge_page(16);
+    assert!(matches!(err, TiffError::InvalidData(_)), "{}", err);
+
+    // 128 bit samples are rejected before the predictor sees them.
+    let err = load_page(vec![
+        (256, Value::Long(vec![2])),
+        (257, Value::Long(vec![1])),
+        (258, Value::Short(vec![128])),
+        (317, Value::Short(vec![2])),
+    ], vec![vec![1; 32]]);
+    assert!(matches!(err, TiffError::UnsupportedType(_)), "{}", err);
+
+    // An ImageJ stack in one directory, without strips.
+    let err = load_page(vec![
+        (256, Value::Long(vec![2])),
+        (257, Value::Long(vec![1])),
+        (258, Value::Short(vec![8])),
+        (270, Value::Ascii("ImageJ=1.54f\nimages=3\n")),
+    ], vec![]);
+    assert!(matches!(err, TiffError::Missing("StripOffsets")), "{}", err);
+    let err = load_page(vec![
+        (256, Value::Long(vec![2])),
+        (257, Value::Long(vec![1])),
+        (258, Value::Short(vec![8])),
+        (270, Value::Ascii("ImageJ=1.54f\nimages=18446744073709551615\n")),
+    ], vec![vec![0; 2]]);
+    assert!(matches!(err, TiffError::InvalidData(_)), "{}", err);
+}
+
+#[test]
+fn save_empty_image_is_an_error() {
+    let img = Image {
+        voxels: Vec::<u8>::new(),
+        width: 0,
+        height: 4,
+        depth: 2,
+        spacing: [1.0, 1.0, 1.0],
+        origin: [0.0; 3],
+        direction: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        metadata: Metadata::default(),
+    };
+    let result = save_tiff(&img, "test_tiff_empty.tif", TiffCompression::None);
+    assert!(matches!(result, Err(TiffError::Unsupported(_))));
+    assert!(read("test_tiff_empty.tif").is_err());
+}
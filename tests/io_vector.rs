@@ -0,0 +1,66 @@
+use oxels::{Image, Metadata, VectorImage, AnyImage, load_meta_image, save_meta_image};
+use std::fs::{remove_file, write};
+
+
+#[test]
+fn load_2d_rgb_image() {
+    let mut contents = b"ObjectType = Image
+NDims = 2
+BinaryData = True
+BinaryDataByteOrderMSB = False
+CompressedData = False
+TransformMatrix = 1 0 0 1
+Offset = 0 0
+ElementSpacing = 1 1
+DimSize = 2 1
+ElementNumberOfChannels = 3
+ElementType = MET_UCHAR
+ElementDataFile = LOCAL
+".to_vec();
+    contents.extend_from_slice(&[255, 0, 0, 0, 255, 10]);
+    let path = "test_vector_rgb.mha";
+    write(path, contents).unwrap();
+
+    let loaded: Box<dyn AnyImage> = load_meta_image(path).unwrap();
+    assert_eq!(loaded.channels(), 3);
+    assert_eq!(loaded.depth(), 1);
+
+    let image = loaded.as_any().downcast_ref::<VectorImage<u8>>().unwrap();
+    assert_eq!(image.num_voxels(), 2);
+    assert_eq!(image.pixel(0), &[255, 0, 0]);
+    assert_eq!(image.pixel(1), &[0, 255, 10]);
+    assert_eq!(image.channel(2).voxels, vec![0, 10]);
+
+    remove_file(path).unwrap();
+}
+
+#[test]
+fn roundtrip_save_and_load_displacement_field() {
+    let geometry = |values: Vec<f32>| Image {
+        voxels: values,
+        width: 2,
+        height: 2,
+        depth: 1,
+        spacing: [1.0, 1.0, 1.0],
+        origin: [0.0, 0.0, 0.0],
+        direction: [1.0,0.0,0.0,0.0,1.0,0.0,0.0,0.0,1.0],
+        metadata: Metadata::default(),
+    };
+    let x = geometry(vec![0.5, 1.5, 2.5, 3.5]);
+    let y = geometry(vec![-1.0, -2.0, -3.0, -4.0]);
+    let z = geometry(vec![0.0, 0.25, 0.0, 0.25]);
+    let field = VectorImage::from_channels(&[x, y.clone(), z]).unwrap();
+    assert_eq!(field.channels, 3);
+
+    let path = "test_vector_field.mhd";
+    save_meta_image(&field, path, true).unwrap();
+
+    let loaded: Box<dyn AnyImage> = load_meta_image(path).unwrap();
+    let loaded = loaded.as_any().downcast_ref::<VectorImage<f32>>().unwrap();
+    assert_eq!(loaded.voxels, field.voxels);
+    assert_eq!(loaded.channel(1).voxels, y.voxels);
+    assert_eq!(loaded.pixels().count(), 4);
+
+    remove_file(path).unwrap();
+    remove_file(path.replace(".mhd", ".zraw")).unwrap();
+}
@@ -0,0 +1,155 @@
+use oxels::{Image, VectorImage, Metadata, VtkEncoding, VtkError, load, load_vtk, save_vtk};
+use std::fs::{read, remove_file, write};
+
+
+fn oblique() -> Image<i16> {
+    // Axes rotated 90 degrees around z: x points along world y, y along -x.
+    Image {
+        voxels: (0..24).map(|i| i * 7 - 50).collect(),
+        width: 4,
+        height: 3,
+        depth: 2,
+        spacing: [0.5, 0.75, 2.0],
+        origin: [10.0, -20.0, 5.5],
+        direction: [0.0, 1.0, 0.0, -1.0, 0.0, 0.0, 0.0, 0.0, 1.0],
+        metadata: Metadata::default(),
+    }
+}
+
+fn check_geometry(loaded: &Image<i16>, image: &Image<i16>) {
+    assert_eq!((loaded.width, loaded.height, loaded.depth), (image.width, image.height, image.depth));
+    assert_eq!(loaded.voxels, image.voxels);
+    assert_eq!(loaded.spacing, image.spacing);
+    assert_eq!(loaded.origin, image.origin);
+    assert_eq!(loaded.direction, image.direction);
+}
+
+
+#[test]
+fn legacy_roundtrip() {
+    let image = oblique();
+    for (encoding, name) in [(VtkEncoding::Binary, "test_vtk_binary.vtk"), (VtkEncoding::Ascii, "test_vtk_ascii.vtk")] {
+        save_vtk(&image, name, encoding).unwrap();
+        let text = read(name).unwrap();
+        let loaded = load(name).unwrap();
+        remove_file(name).unwrap();
+        // VTK's direction matrix has the axes in its columns.
+        assert!(String::from_utf8_lossy(&text).contains("DIRECTION 0 -1 0 1 0 0 0 0 1\n"));
+        check_geometry(loaded.as_any().downcast_ref::<Image<i16>>().unwrap(), &image);
+    }
+
+    let result = save_vtk(&image, "test_vtk_never.vtk", VtkEncoding::Zlib);
+    assert!(matches!(result.err().unwrap(), VtkError::Unsupported(_)));
+}
+
+#[test]
+fn vti_roundtrip() {
+    let image = oblique();
+    for (encoding, name) in [
+        (VtkEncoding::Ascii, "test_vti_ascii.vti"),
+        (VtkEncoding::Binary, "test_vti_raw.vti"),
+        (VtkEncoding::Zlib, "test_vti_zlib.vti"),
+    ] {
+        save_vtk(&image, name, encoding).unwrap();
+        let loaded = load(name).unwrap();
+        remove_file(name).unwrap();
+        check_geometry(loaded.as_any().downcast_ref::<Image<i16>>().unwrap(), &image);
+    }
+
+    // More than one zlib block.
+    let vectors = VectorImage {
+        voxels: (0..3 * 40 * 30 * 20).map(|i| i as f32 * 0.25).collect(),
+        channels: 3,
+        width: 40,
+        height: 30,
+        depth: 20,
+        spacing: [1.0, 1.0, 3.0],
+        origin: [0.0; 3],
+        direction: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        metadata: Metadata::default(),
+    };
+    save_vtk(&vectors, "test_vti_vectors.vti", VtkEncoding::Zlib).unwrap();
+    let loaded = load_vtk("test_vti_vectors.vti").unwrap();
+    remove_file("test_vti_vectors.vti").unwrap();
+    let loaded = loaded.as_any().downcast_ref::<VectorImage<f32>>().unwrap();
+    assert_eq!((loaded.channels, loaded.depth), (3, 20));
+    assert_eq!(loaded.voxels, vectors.voxels);
+    assert_eq!(loaded.spacing, vectors.spacing);
+}
+
+#[test]
+fn load_handwritten() {
+    let legacy = "# vtk DataFile Version 2.0\nbox\nASCII\n\nDATASET STRUCTURED_POINTS\nDIMENSIONS 2 2 1\n\
+                  ASPECT_RATIO 1 2 1\nORIGIN 0 0 -3\nPOINT_DATA 4\nSCALARS density float\nLOOKUP_TABLE default\n\
+                  0.5 1.5\n2.5 3.5\n";
+    write("test_vtk_hand.vtk", legacy).unwrap();
+    let loaded = load_vtk("test_vtk_hand.vtk").unwrap();
+    remove_file("test_vtk_hand.vtk").unwrap();
+    let loaded = loaded.as_any().downcast_ref::<Image<f32>>().unwrap();
+    assert_eq!(loaded.voxels, vec![0.5, 1.5, 2.5, 3.5]);
+    assert_eq!(loaded.spacing, [1.0, 2.0, 1.0]);
+    assert_eq!(loaded.origin, [0.0, 0.0, -3.0]);
+
+    // Extent starting at 1 along x, UInt32 headers, raw big-endian appended data.
+    let mut vti = b"<?xml version=\"1.0\"?>\n<VTKFile type=\"ImageData\" version=\"0.1\" byte_order=\"BigEndian\">\n\
+        <ImageData WholeExtent=\"1 2 0 0 0 0\" Origin=\"0 0 0\" Spacing=\"2 1 1\">\n<Piece Extent=\"1 2 0 0 0 0\">\n\
+        <PointData Scalars=\"b\">\n<DataArray type=\"Int32\" Name=\"a\" format=\"ascii\">9 9</DataArray>\n\
+        <DataArray type=\"UInt16\" Name=\"b\" format=\"appended\" offset=\"0\"/>\n</PointData>\n</Piece>\n</ImageData>\n\
+        <AppendedData encoding=\"raw\">\n_".to_vec();
+    vti.extend([0, 0, 0, 4, 1, 2, 3, 4]);
+    vti.extend(b"\n</AppendedData>\n</VTKFile>\n");
+    write("test_vti_hand.vti", vti).unwrap();
+    let loaded = load_vtk("test_vti_hand.vti").unwrap();
+    remove_file("test_vti_hand.vti").unwrap();
+    let loaded = loaded.as_any().downcast_ref::<Image<u16>>().unwrap();
+    assert_eq!(loaded.voxels, vec![0x0102, 0x0304]);
+    assert_eq!(loaded.origin, [2.0, 0.0, 0.0]);
+
+    let grid = "# vtk DataFile Version 3.0\ngrid\nASCII\nDATASET RECTILINEAR_GRID\nDIMENSIONS 1 1 1\nPOINT_DATA 1\nSCALARS s int\n0\n";
+    write("test_vtk_grid.vtk", grid).unwrap();
+    let result = load_vtk("test_vtk_grid.vtk");
+    remove_file("test_vtk_grid.vtk").unwrap();
+    assert!(matches!(result.err().unwrap(), VtkError::Unsupported(_)));
+}
+
+#[test]
+fn corrupt_zlib_block_sizes() {
+    // One block claiming 2^62 bytes, and a compressed size beyond the file.
+    let mut vti = b"<?xml version=\"1.0\"?>\n<VTKFile type=\"ImageData\" version=\"1.0\" byte_order=\"LittleEndian\" \
+        header_type=\"UInt64\" compressor=\"vtkZLibDataCompressor\">\n\
+        <ImageData WholeExtent=\"0 1 0 0 0 0\" Origin=\"0 0 0\" Spacing=\"1 1 1\">\n<Piece Extent=\"0 1 0 0 0 0\">\n\
+        <PointData Scalars=\"a\">\n<DataArray type=\"UInt8\" Name=\"a\" format=\"appended\" offset=\"0\"/>\n</PointData>\n\
+        </Piece>\n</ImageData>\n<AppendedData encoding=\"raw\">\n_".to_vec();
+    for word in [1u64, 1 << 62, 1 << 62, u64::MAX] {
+        vti.extend(word.to_le_bytes());
+    }
+    vti.extend(b"\n</AppendedData>\n</VTKFile>\n");
+    write("test_vti_corrupt.vti", vti).unwrap();
+    let result = load_vtk("test_vti_corrupt.vti");
+    remove_file("test_vti_corrupt.vti").unwrap();
+    assert!(matches!(result.err().unwrap(), VtkError::Truncated { .. }));
+}
+
+#[test]
+fn oversized_header_is_an_error() {
+    let legacy = |dimensions: &str, components: u32| format!(
+        "# vtk DataFile Version 3.0\nhuge\nBINARY\nDATASET STRUCTURED_POINTS\nDIMENSIONS {}\n\
+         SCALARS s double {}\nLOOKUP_TABLE default\n\0\0\0\0\0\0\0\0\n", dimensions, components
+    );
+    let vti = "<?xml version=\"1.0\"?>\n<VTKFile type=\"ImageData\" version=\"0.1\" byte_order=\"LittleEndian\">\n\
+        <ImageData WholeExtent=\"0 4294967296 0 0 0 0\" Origin=\"0 0 0\" Spacing=\"1 1 1\">\n\
+        <Piece Extent=\"0 4294967296 0 0 0 0\">\n<PointData Scalars=\"a\">\n\
+        <DataArray type=\"UInt8\" Name=\"a\" format=\"ascii\">0</DataArray>\n</PointData>\n</Piece>\n</ImageData>\n</VTKFile>\n";
+    // Sizes beyond u32, a voxel count that overflows, and one that overflows with its components.
+    for (path, file) in [
+        ("test_vtk_huge_axis.vtk", legacy("4294967296 4294967296 1", 1)),
+        ("test_vtk_huge_count.vtk", legacy("4294967295 4294967295 4294967295", 1)),
+        ("test_vtk_huge_values.vtk", legacy("4294967295 4294967295 1", 4)),
+        ("test_vti_huge_extent.vti", vti.to_string()),
+    ] {
+        write(path, file).unwrap();
+        let result = load_vtk(path);
+        remove_file(path).unwrap();
+        assert!(matches!(result.err().unwrap(), VtkError::InvalidHeader(_)), "{}", path);
+    }
+}